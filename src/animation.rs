@@ -0,0 +1,197 @@
+use crate::utils::interpolation::{lerp, Easing};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+const CURSOR_DURATION: Duration = Duration::from_millis(100);
+const SCROLL_DURATION: Duration = Duration::from_millis(150);
+const OVERLAY_DURATION: Duration = Duration::from_millis(120);
+
+// a number easing from one value to another over `duration`, the same on any frame rate
+#[derive(Clone, Copy, Debug)]
+pub struct Animation {
+    from: f32,
+    to: f32,
+    started: Instant,
+    duration: Duration,
+    easing: Easing,
+}
+
+impl Animation {
+    // resting at `value`
+    pub fn new(value: f32, duration: Duration, easing: Easing) -> Self {
+        Animation {
+            from: value,
+            to: value,
+            started: Instant::now(),
+            duration,
+            easing,
+        }
+    }
+
+    // fraction of the time passed, from 0 to 1
+    fn progress(&self, now: Instant) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+
+        let elapsed = now.saturating_duration_since(self.started);
+
+        (elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    pub fn value(&self, now: Instant) -> f32 {
+        lerp(self.from, self.to, self.easing.apply(self.progress(now)))
+    }
+
+    pub fn running(&self, now: Instant) -> bool {
+        self.from != self.to && self.progress(now) < 1.0
+    }
+
+    // heads for `to` from wherever it is at `now`, carrying on if it's going there already
+    pub fn animate_to(&mut self, to: f32, now: Instant) {
+        if to != self.to {
+            self.from = self.value(now);
+            self.to = to;
+            self.started = now;
+        }
+    }
+
+    // jumps to `value` without animating
+    pub fn set(&mut self, value: f32) {
+        self.from = value;
+        self.to = value;
+    }
+}
+
+// popups and the picker, which unfold when they open
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Overlay {
+    Completion,
+    Picker,
+}
+
+// the animated parts of the editor, moved along as frames are drawn
+#[derive(Default)]
+pub struct Motion {
+    pub reduced: bool, // everything jumps into place instead of animating
+    cursor: Option<(Animation, Animation)>, // x and document row of the focused cursor
+    scrolls: HashMap<usize, Animation>, // how far each pane's text is moved up, by pane id
+    overlays: HashMap<Overlay, Animation>, // how far each open one has unfolded
+}
+
+impl Motion {
+    pub fn new(reduced: bool) -> Self {
+        Motion {
+            reduced,
+            ..Default::default()
+        }
+    }
+
+    fn ease(reduced: bool, animation: &mut Animation, to: f32, now: Instant) -> f32 {
+        if reduced {
+            animation.set(to);
+        } else {
+            animation.animate_to(to, now);
+        }
+
+        animation.value(now)
+    }
+
+    // where the focused cursor is drawn on its way to `to`, as x and document row
+    pub fn cursor(&mut self, to: (f32, f32), now: Instant) -> (f32, f32) {
+        // the first cursor shows up where it is
+        let (x, row) = self.cursor.get_or_insert_with(|| {
+            (
+                Animation::new(to.0, CURSOR_DURATION, Easing::EaseOut),
+                Animation::new(to.1, CURSOR_DURATION, Easing::EaseOut),
+            )
+        });
+
+        (
+            Self::ease(self.reduced, x, to.0, now),
+            Self::ease(self.reduced, row, to.1, now),
+        )
+    }
+
+    // how far a pane's text is moved up on its way to being scrolled by `to` pixels
+    pub fn scroll(&mut self, pane: usize, to: f32, now: Instant) -> f32 {
+        let scroll = self
+            .scrolls
+            .entry(pane)
+            .or_insert_with(|| Animation::new(to, SCROLL_DURATION, Easing::EaseInOut));
+
+        Self::ease(self.reduced, scroll, to, now)
+    }
+
+    // how far an overlay has unfolded from 0 to 1, starting over each time it opens
+    pub fn overlay(&mut self, overlay: Overlay, open: bool, now: Instant) -> f32 {
+        if !open {
+            self.overlays.remove(&overlay);
+            return 0.0;
+        }
+
+        let shown = self
+            .overlays
+            .entry(overlay)
+            .or_insert_with(|| Animation::new(0.0, OVERLAY_DURATION, Easing::EaseOut));
+
+        Self::ease(self.reduced, shown, 1.0, now)
+    }
+
+    // true while something still has to move, so frames keep being drawn
+    pub fn running(&self, now: Instant) -> bool {
+        let cursor = self.cursor.iter().flat_map(|(x, row)| [x, row]);
+
+        cursor
+            .chain(self.scrolls.values())
+            .chain(self.overlays.values())
+            .any(|x| x.running(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animations_ease_over_time_not_frames() {
+        let start = Instant::now();
+        let mut animation = Animation::new(0.0, Duration::from_millis(100), Easing::EaseOut);
+
+        animation.animate_to(10.0, start);
+
+        assert_eq!(animation.value(start), 0.0);
+        assert!(animation.value(start + Duration::from_millis(50)) > 5.0);
+        assert_eq!(animation.value(start + Duration::from_millis(100)), 10.0);
+        assert!(!animation.running(start + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn retargeting_starts_from_the_current_value() {
+        let start = Instant::now();
+        let halfway = start + Duration::from_millis(50);
+        let mut animation = Animation::new(0.0, Duration::from_millis(100), Easing::EaseInOut);
+
+        animation.animate_to(10.0, start);
+        let value = animation.value(halfway);
+
+        animation.animate_to(-10.0, halfway);
+
+        assert_eq!(animation.value(halfway), value);
+        assert!(animation.running(halfway));
+    }
+
+    #[test]
+    fn reduced_motion_jumps_into_place() {
+        let now = Instant::now();
+        let mut motion = Motion::new(true);
+
+        motion.scroll(0, 0.0, now);
+
+        assert_eq!(motion.scroll(0, 120.0, now), 120.0);
+        assert_eq!(motion.overlay(Overlay::Picker, true, now), 1.0);
+        assert!(!motion.running(now));
+    }
+}
@@ -0,0 +1,265 @@
+// a tiny language server for the lsp client tests, speaks json-rpc over stdio
+//
+// documents are synced incrementally, every "bad" is reported as an error, hover shows the word
+// under the cursor, definition is the first occurrence of that word, references and rename
+// cover every occurrence and completion offers the document's words starting with the prefix
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+};
+
+use serde_json::{json, Value};
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut length = 0;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+
+        let header = header.trim();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok()?;
+        }
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+    serde_json::from_slice(&body).ok()
+}
+
+fn write_message(message: Value) {
+    let body = message.to_string();
+    let mut stdout = io::stdout().lock();
+
+    write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    stdout.flush().unwrap();
+}
+
+// byte offset of a utf-16 line/character position
+fn offset(text: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap() as usize;
+    let character = position["character"].as_u64().unwrap() as usize;
+
+    let start = match line {
+        0 => 0,
+        line => text.match_indices('\n').nth(line - 1).unwrap().0 + 1,
+    };
+
+    let mut count = 0;
+
+    for (idx, char) in text[start..].char_indices() {
+        if count >= character || char == '\n' {
+            return start + idx;
+        }
+        count += char.len_utf16();
+    }
+
+    text.len()
+}
+
+fn position(text: &str, offset: usize) -> Value {
+    let before = &text[..offset];
+    let start = before.rfind('\n').map_or(0, |x| x + 1);
+
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[start..].encode_utf16().count(),
+    })
+}
+
+fn range(text: &str, start: usize, end: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, end) })
+}
+
+fn is_word(char: char) -> bool {
+    char.is_alphanumeric() || char == '_'
+}
+
+// start and end of the word touching the offset
+fn word_at(text: &str, offset: usize) -> (usize, usize) {
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, x)| is_word(*x))
+        .last()
+        .map_or(offset, |(idx, _)| idx);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, x)| !is_word(*x))
+        .map_or(text.len(), |(idx, _)| offset + idx);
+
+    (start, end)
+}
+
+// whole word occurrences
+fn occurrences(text: &str, word: &str) -> Vec<usize> {
+    text.match_indices(word)
+        .map(|(idx, _)| idx)
+        .filter(|idx| {
+            let before = text[..*idx].chars().next_back().is_some_and(is_word);
+            let after = text[idx + word.len()..].chars().next().is_some_and(is_word);
+            !before && !after
+        })
+        .collect()
+}
+
+fn publish_diagnostics(uri: &str, text: &str) {
+    let diagnostics: Vec<Value> = occurrences(text, "bad")
+        .into_iter()
+        .map(|idx| {
+            json!({
+                "range": range(text, idx, idx + 3),
+                "severity": 1,
+                "source": "mock",
+                "message": "bad word",
+            })
+        })
+        .collect();
+
+    write_message(json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    }));
+}
+
+fn main() {
+    let mut documents: HashMap<String, String> = HashMap::new();
+    let mut stdin = io::stdin().lock();
+
+    while let Some(message) = read_message(&mut stdin) {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 2 },
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "completionProvider": {},
+                }
+            }),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap().to_string();
+                publish_diagnostics(&uri, &text);
+                documents.insert(uri, text);
+                continue;
+            }
+            "textDocument/didChange" => {
+                let text = documents.get_mut(&uri).unwrap();
+
+                for change in params["contentChanges"].as_array().unwrap() {
+                    let new_text = change["text"].as_str().unwrap();
+
+                    match &change["range"] {
+                        Value::Null => *text = new_text.to_string(),
+                        range => {
+                            let start = offset(text, &range["start"]);
+                            let end = offset(text, &range["end"]);
+                            text.replace_range(start..end, new_text);
+                        }
+                    }
+                }
+
+                publish_diagnostics(&uri, text);
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+                continue;
+            }
+            "textDocument/hover" => {
+                let text = &documents[&uri];
+                let (start, end) = word_at(text, offset(text, &params["position"]));
+
+                json!({
+                    "contents": { "kind": "plaintext", "value": &text[start..end] },
+                    "range": range(text, start, end),
+                })
+            }
+            "textDocument/definition" => {
+                let text = &documents[&uri];
+                let (start, end) = word_at(text, offset(text, &params["position"]));
+
+                match occurrences(text, &text[start..end]).first() {
+                    Some(idx) => {
+                        json!({ "uri": uri, "range": range(text, *idx, idx + end - start) })
+                    }
+                    None => Value::Null,
+                }
+            }
+            "textDocument/references" => {
+                let text = &documents[&uri];
+                let (start, end) = word_at(text, offset(text, &params["position"]));
+
+                occurrences(text, &text[start..end])
+                    .into_iter()
+                    .map(|idx| json!({ "uri": uri, "range": range(text, idx, idx + end - start) }))
+                    .collect()
+            }
+            "textDocument/rename" => {
+                let text = &documents[&uri];
+                let (start, end) = word_at(text, offset(text, &params["position"]));
+
+                let edits: Vec<Value> = occurrences(text, &text[start..end])
+                    .into_iter()
+                    .map(|idx| {
+                        json!({
+                            "range": range(text, idx, idx + end - start),
+                            "newText": params["newName"],
+                        })
+                    })
+                    .collect();
+
+                json!({ "changes": { uri: edits } })
+            }
+            "textDocument/completion" => {
+                let text = &documents[&uri];
+                let offset = offset(text, &params["position"]);
+                let (start, _) = word_at(text, offset);
+                let prefix = &text[start..offset];
+
+                let mut words: Vec<&str> = text
+                    .split(|x: char| !is_word(x))
+                    .filter(|x| x.starts_with(prefix) && !x.is_empty())
+                    .collect();
+                words.sort();
+                words.dedup();
+
+                json!({
+                    "isIncomplete": false,
+                    "items": words
+                        .into_iter()
+                        .map(|word| json!({ "label": word, "detail": "word" }))
+                        .collect::<Vec<_>>(),
+                })
+            }
+            "shutdown" => Value::Null,
+            "exit" => return,
+            _ => {
+                if message["id"].is_null() {
+                    continue;
+                }
+                Value::Null
+            }
+        };
+
+        write_message(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }));
+    }
+}
@@ -0,0 +1,811 @@
+use std::{
+    cmp::Reverse,
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cursor::Cursor,
+    fold::Folds,
+    lsp::{self, Change, Position},
+    search::Location,
+    snippet::{Expansion, Session},
+    status::Indentation,
+};
+
+pub enum BufferKind {
+    File,
+    // read-only, holds the jump location of every line
+    SearchResults(Vec<Option<Location>>),
+}
+
+#[derive(Debug)]
+enum Edit {
+    Insert {
+        idx: usize,
+        text: String,
+    },
+    Remove {
+        idx: usize,
+        text: String,
+    },
+    Replace {
+        idx: usize,
+        removed: String,
+        inserted: String,
+    },
+    // edits made at several cursors at once, undone together
+    Group(Vec<Edit>),
+}
+
+#[derive(Debug, Default)]
+struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    saved_at: Option<usize>, // undo stack depth matching the file on disk
+    coalesce: bool,          // whether the next edit may merge into the last one
+}
+
+pub struct Buffer {
+    pub content: String,
+    pub cursor: Cursor,
+    pub anchor: Option<usize>, // where the selection started, the cursor is its other end
+    pub path: Option<PathBuf>,
+    pub name: String,
+    pub crlf: bool, // lines end with \r\n on disk, in `content` they're always \n
+    pub kind: BufferKind,
+    pub scroll: usize, // first visible line
+    pub version: i32,
+    pub changes: Vec<Change>, // edits not yet sent to a language server
+    pub edits: Vec<(Range<usize>, usize)>, // replaced ranges and inserted lengths, for other panes
+    pub cursors: Vec<Cursor>, // extra cursors, edited along with the main one
+    pub snippet: Option<Session>,
+    pub folds: Folds,
+    pub indentation: Indentation, // what the status bar shows, worked out once per version
+    followed: Option<(usize, i32)>, // cursor position and version the view last scrolled to
+    history: History,
+}
+
+impl Buffer {
+    pub fn new(name: &str, content: String) -> Self {
+        Buffer {
+            content,
+            cursor: Cursor::new(),
+            anchor: None,
+            path: None,
+            name: name.to_string(),
+            crlf: false,
+            kind: BufferKind::File,
+            scroll: 0,
+            version: 0,
+            changes: Vec::new(),
+            edits: Vec::new(),
+            cursors: Vec::new(),
+            snippet: None,
+            folds: Folds::default(),
+            indentation: Indentation::default(),
+            followed: None,
+            history: History {
+                saved_at: Some(0),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let raw = fs::read_to_string(path)?;
+        let content = raw.chars().filter(|x| *x != '\r').collect::<String>();
+
+        let mut buffer = Buffer::new(&file_name(path), content);
+        buffer.crlf = raw.contains("\r\n");
+        // absolute so paths coming back from language servers compare equal
+        buffer.path = Some(std::path::absolute(path)?);
+
+        Ok(buffer)
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "buffer has no path",
+            ));
+        };
+
+        if self.crlf {
+            fs::write(path, self.content.replace('\n', "\r\n"))?;
+        } else {
+            fs::write(path, &self.content)?;
+        }
+
+        self.history.saved_at = Some(self.history.undo.len());
+        self.history.coalesce = false;
+
+        Ok(())
+    }
+
+    pub fn save_as(&mut self, path: &Path) -> io::Result<()> {
+        self.path = Some(path.to_path_buf());
+        self.name = file_name(path);
+        self.save()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.kind, BufferKind::File)
+            && self.history.saved_at != Some(self.history.undo.len())
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        !matches!(self.kind, BufferKind::File)
+    }
+
+    pub fn insert(&mut self, char: char) {
+        if self.is_read_only() {
+            return;
+        }
+
+        if let Some(selection) = self.selection() {
+            self.replace_selection(selection, &char.to_string());
+            return;
+        }
+
+        // typing over a placeholder replaces it everywhere it's linked
+        if self.snippet.as_ref().is_some_and(|x| x.fresh) {
+            self.fill_stop(&char.to_string());
+            return;
+        }
+
+        if !self.cursors.is_empty() {
+            self.edit_cursors(|_, idx| idx..idx, &char.to_string());
+            return;
+        }
+
+        let idx = self.cursor.idx;
+
+        self.track_change(idx..idx, &char.to_string());
+        self.content.insert(idx, char);
+        self.cursor.move_right(&self.content);
+
+        // typing a word keeps extending the same undo step
+        if let Some(Edit::Insert { idx: last, text }) = self.history.undo.last_mut() {
+            if self.history.coalesce && *last + text.len() == idx && !char.is_whitespace() {
+                text.push(char);
+                self.history.redo.clear();
+                return;
+            }
+        }
+
+        self.push_edit(Edit::Insert {
+            idx,
+            text: char.to_string(),
+        });
+    }
+
+    pub fn backspace(&mut self) {
+        if self.is_read_only() {
+            return;
+        }
+
+        if let Some(selection) = self.selection() {
+            self.replace_selection(selection, "");
+            return;
+        }
+
+        if self.snippet.as_ref().is_some_and(|x| x.fresh) {
+            self.fill_stop("");
+            return;
+        }
+
+        if !self.cursors.is_empty() {
+            self.edit_cursors(
+                |content, idx| {
+                    let len = content[..idx].chars().next_back().map_or(0, char::len_utf8);
+                    idx - len..idx
+                },
+                "",
+            );
+            return;
+        }
+
+        if self.cursor.idx == 0 {
+            return;
+        }
+
+        self.cursor.move_left(&self.content);
+
+        let idx = self.cursor.idx;
+        let len = self.content[idx..].chars().next().map_or(0, char::len_utf8);
+
+        self.track_change(idx..idx + len, "");
+        let char = self.content.remove(idx);
+
+        if let Some(Edit::Remove { idx: last, text }) = self.history.undo.last_mut() {
+            if self.history.coalesce && idx + char.len_utf8() == *last {
+                text.insert(0, char);
+                *last = idx;
+                self.history.redo.clear();
+                return;
+            }
+        }
+
+        self.push_edit(Edit::Remove {
+            idx,
+            text: char.to_string(),
+        });
+    }
+
+    // the selected byte range, if anything is selected
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        let cursor = self.cursor.idx;
+
+        (anchor != cursor).then(|| anchor.min(cursor)..anchor.max(cursor))
+    }
+
+    fn replace_selection(&mut self, selection: Range<usize>, text: &str) {
+        self.end_snippet();
+        self.replace(selection.clone(), text);
+        self.cursor
+            .set_idx(&self.content, selection.start + text.len());
+    }
+
+    // moves the cursor, the anchor stays where it is
+    pub fn set_cursor(&mut self, idx: usize) {
+        self.cursor
+            .set_idx(&self.content, idx.min(self.content.len()));
+        self.leave_placeholder();
+    }
+
+    // replaces a byte range, the cursor keeps its place relative to the text around it
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        if self.is_read_only() {
+            return;
+        }
+
+        let cursor = self.cursor.idx;
+        let removed = self.content[range.clone()].to_string();
+
+        self.track_change(range.clone(), text);
+        self.content.replace_range(range.clone(), text);
+
+        let cursor = if cursor >= range.end {
+            cursor + text.len() - removed.len()
+        } else if cursor > range.start {
+            range.start + text.len()
+        } else {
+            cursor
+        };
+        self.cursor.set_idx(&self.content, cursor);
+
+        self.push_edit(Edit::Replace {
+            idx: range.start,
+            removed,
+            inserted: text.to_string(),
+        });
+        self.history.coalesce = false;
+    }
+
+    // replaces every range with the same text as a single undo step, returns where each range
+    // ends afterwards
+    fn replace_all(&mut self, ranges: &[Range<usize>], text: &str) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_by_key(|x| Reverse(ranges[*x].start));
+
+        let mut edits = Vec::new();
+        let mut applied: Vec<Range<usize>> = Vec::new();
+
+        // back to front so earlier offsets stay valid, overlapping ranges are cut short
+        for idx in order {
+            // cursors on the same spot edit it once
+            if applied.last().is_some_and(|x| x.start == ranges[idx].start) {
+                continue;
+            }
+
+            let end = applied
+                .last()
+                .map_or(ranges[idx].end, |x| ranges[idx].end.min(x.start));
+            let range = ranges[idx].start..end;
+
+            let removed = self.content[range.clone()].to_string();
+
+            self.track_change(range.clone(), text);
+            self.content.replace_range(range.clone(), text);
+
+            edits.push(Edit::Replace {
+                idx: range.start,
+                removed,
+                inserted: text.to_string(),
+            });
+            applied.push(range);
+        }
+
+        self.push_edit(Edit::Group(edits));
+        self.history.coalesce = false;
+
+        ranges
+            .iter()
+            .map(|range| {
+                let before: Vec<&Range<usize>> =
+                    applied.iter().filter(|x| x.start < range.start).collect();
+                let removed: usize = before.iter().map(|x| x.len()).sum();
+
+                range.start + before.len() * text.len() - removed + text.len()
+            })
+            .collect()
+    }
+
+    // applies language server edits, all ranges refer to the content before any of them
+    pub fn apply_edits(&mut self, edits: &[lsp::TextEdit]) {
+        let mut edits: Vec<(Range<usize>, &str)> = edits
+            .iter()
+            .map(|edit| {
+                let start = edit.range.start.to_offset(&self.content);
+                let end = edit.range.end.to_offset(&self.content);
+                (start..end.max(start), edit.new_text.as_str())
+            })
+            .collect();
+
+        // back to front so earlier offsets stay valid
+        edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+
+        for (range, text) in edits {
+            self.replace(range, text);
+        }
+    }
+
+    // queues the edit for language servers, called before the content changes
+    fn track_change(&mut self, range: Range<usize>, text: &str) {
+        // offsets of the selection would be off after the edit
+        self.anchor = None;
+
+        if let Some(session) = &mut self.snippet {
+            session.adjust(range.clone(), text.len());
+        }
+
+        let start = self.content[..range.start].matches('\n').count();
+        let removed = self.content[range.clone()].matches('\n').count();
+        let inserted = text.matches('\n').count();
+        self.folds
+            .edit(start, start + removed, inserted as isize - removed as isize);
+
+        self.edits.push((range.clone(), text.len()));
+
+        self.version += 1;
+        self.changes.push(Change {
+            range: lsp::Range {
+                start: Position::from_offset(&self.content, range.start),
+                end: Position::from_offset(&self.content, range.end),
+            },
+            text: text.to_string(),
+        });
+    }
+
+    fn push_edit(&mut self, edit: Edit) {
+        // the saved state can't be reached again once its redo branch is dropped
+        if self.history.saved_at > Some(self.history.undo.len()) {
+            self.history.saved_at = None;
+        }
+
+        self.history.undo.push(edit);
+        self.history.redo.clear();
+        self.history.coalesce = true;
+    }
+
+    pub fn undo(&mut self) {
+        let Some(edit) = self.history.undo.pop() else {
+            return;
+        };
+
+        self.end_snippet();
+
+        let idx = self.revert(&edit);
+
+        self.cursor.set_idx(&self.content, idx);
+        self.history.redo.push(edit);
+        self.history.coalesce = false;
+    }
+
+    pub fn redo(&mut self) {
+        let Some(edit) = self.history.redo.pop() else {
+            return;
+        };
+
+        self.end_snippet();
+
+        let idx = self.reapply(&edit);
+
+        self.cursor.set_idx(&self.content, idx);
+        self.history.undo.push(edit);
+        self.history.coalesce = false;
+    }
+
+    // takes an edit back, returns where the cursor goes
+    fn revert(&mut self, edit: &Edit) -> usize {
+        let (idx, removed, inserted) = match edit {
+            Edit::Insert { idx, text } => (*idx, text.as_str(), ""),
+            Edit::Remove { idx, text } => (*idx, "", text.as_str()),
+            Edit::Replace {
+                idx,
+                removed,
+                inserted,
+            } => (*idx, inserted.as_str(), removed.as_str()),
+            // in reverse, the group was made back to front
+            Edit::Group(edits) => {
+                return edits
+                    .iter()
+                    .rev()
+                    .map(|x| self.revert(x))
+                    .last()
+                    .unwrap_or(self.cursor.idx)
+            }
+        };
+
+        self.splice(idx, removed, inserted)
+    }
+
+    fn reapply(&mut self, edit: &Edit) -> usize {
+        let (idx, removed, inserted) = match edit {
+            Edit::Insert { idx, text } => (*idx, "", text.as_str()),
+            Edit::Remove { idx, text } => (*idx, text.as_str(), ""),
+            Edit::Replace {
+                idx,
+                removed,
+                inserted,
+            } => (*idx, removed.as_str(), inserted.as_str()),
+            Edit::Group(edits) => {
+                return edits
+                    .iter()
+                    .map(|x| self.reapply(x))
+                    .last()
+                    .unwrap_or(self.cursor.idx)
+            }
+        };
+
+        self.splice(idx, removed, inserted)
+    }
+
+    fn splice(&mut self, idx: usize, removed: &str, inserted: &str) -> usize {
+        self.track_change(idx..idx + removed.len(), inserted);
+        self.content
+            .replace_range(idx..idx + removed.len(), inserted);
+
+        idx + inserted.len()
+    }
+
+    pub fn move_left(&mut self) {
+        self.anchor = None;
+        self.cursor.move_left(&self.content);
+        for cursor in self.cursors.iter_mut() {
+            cursor.move_left(&self.content);
+        }
+        self.leave_placeholder();
+    }
+
+    pub fn move_right(&mut self) {
+        self.anchor = None;
+        self.cursor.move_right(&self.content);
+        for cursor in self.cursors.iter_mut() {
+            cursor.move_right(&self.content);
+        }
+        self.leave_placeholder();
+    }
+
+    // moving between lines leaves the extra cursors behind
+    pub fn move_up(&mut self) {
+        self.anchor = None;
+        self.end_snippet();
+        self.cursor.move_up(&self.content, &self.folds);
+        self.history.coalesce = false;
+    }
+
+    pub fn move_down(&mut self) {
+        self.anchor = None;
+        self.end_snippet();
+        self.cursor.move_down(&self.content, &self.folds);
+        self.history.coalesce = false;
+    }
+
+    fn leave_placeholder(&mut self) {
+        if let Some(session) = &mut self.snippet {
+            session.fresh = false;
+        }
+        self.history.coalesce = false;
+    }
+
+    // the same edit at every cursor, `range` gives what's replaced around a cursor
+    fn edit_cursors(&mut self, range: impl Fn(&str, usize) -> Range<usize>, text: &str) {
+        let ranges: Vec<Range<usize>> = std::iter::once(&self.cursor)
+            .chain(&self.cursors)
+            .map(|x| range(&self.content, x.idx))
+            .collect();
+
+        let ends = self.replace_all(&ranges, text);
+        self.place_cursors(&ends);
+    }
+
+    // the main cursor goes to the first position, extra cursors to the others
+    fn place_cursors(&mut self, positions: &[usize]) {
+        let Some((first, rest)) = positions.split_first() else {
+            return;
+        };
+
+        self.cursor.set_idx(&self.content, *first);
+        self.cursors.clear();
+
+        for idx in rest {
+            if *idx == self.cursor.idx || self.cursors.iter().any(|x| x.idx == *idx) {
+                continue;
+            }
+
+            let mut cursor = Cursor::new();
+            cursor.set_idx(&self.content, *idx);
+            self.cursors.push(cursor);
+        }
+    }
+
+    // replaces the snippet with its text and puts the cursors on the first stop
+    pub fn insert_snippet(&mut self, range: Range<usize>, expansion: Expansion) {
+        if self.is_read_only() {
+            return;
+        }
+
+        self.end_snippet();
+        self.replace(range.clone(), &expansion.text);
+        self.snippet = Some(Session::new(range.start, expansion));
+        self.enter_stop();
+    }
+
+    pub fn next_stop(&mut self) {
+        if let Some(session) = &mut self.snippet {
+            session.current = (session.current + 1).min(session.stops.len() - 1);
+        }
+        self.enter_stop();
+    }
+
+    pub fn prev_stop(&mut self) {
+        if let Some(session) = &mut self.snippet {
+            session.current = session.current.saturating_sub(1);
+        }
+        self.enter_stop();
+    }
+
+    // a cursor at the end of every range of the current stop, the session is over at the last
+    fn enter_stop(&mut self) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+
+        let ranges = session.stop().ranges.clone();
+        session.fresh = ranges.iter().any(|x| !x.is_empty());
+
+        if session.is_last() {
+            self.snippet = None;
+        }
+
+        let ends: Vec<usize> = ranges.iter().map(|x| x.end).collect();
+        self.place_cursors(&ends);
+        self.history.coalesce = false;
+    }
+
+    // replaces the text of every range of the current stop
+    pub fn fill_stop(&mut self, text: &str) {
+        let Some(session) = &mut self.snippet else {
+            return;
+        };
+
+        session.fresh = false;
+
+        let ranges = session.stop().ranges.clone();
+        let ends = self.replace_all(&ranges, text);
+        self.place_cursors(&ends);
+    }
+
+    pub fn end_snippet(&mut self) {
+        self.snippet = None;
+        self.cursors.clear();
+    }
+
+    // keeps the cursor line inside a viewport of `lines` rows, a cursor that ended up in a
+    // folded region opens it, only once the cursor or the text changed so the view can be
+    // scrolled away from the cursor
+    pub fn scroll_to_cursor(&mut self, lines: usize) {
+        let lines = lines.max(1);
+
+        if self.followed == Some((self.cursor.idx, self.version)) {
+            return;
+        }
+
+        self.followed = Some((self.cursor.idx, self.version));
+
+        self.folds.reveal(self.cursor.cursor_y);
+        self.scroll = self.folds.visible_line(self.scroll);
+
+        if self.cursor.cursor_y < self.scroll {
+            self.scroll = self.cursor.cursor_y;
+        } else if self
+            .folds
+            .row(self.cursor.cursor_y, self.scroll)
+            .is_some_and(|x| x >= lines)
+        {
+            self.scroll = self.folds.back(self.cursor.cursor_y, lines - 1);
+        }
+    }
+
+    // the first line shown after scrolling by `rows`, folded regions count as one row
+    pub fn scrolled(&self, scroll: usize, rows: isize) -> usize {
+        let last = self.content.matches('\n').count();
+
+        if rows < 0 {
+            self.folds.back(scroll, rows.unsigned_abs())
+        } else {
+            self.folds.forward(scroll, rows as usize, last)
+        }
+    }
+
+    pub fn refresh_folds(&mut self) {
+        self.folds
+            .refresh(&self.content, self.path.as_deref(), self.version);
+    }
+
+    pub fn refresh_indentation(&mut self) {
+        self.indentation.refresh(&self.content, self.version);
+    }
+
+    // folds the innermost open region around the cursor
+    pub fn fold(&mut self) {
+        let line = self.cursor.cursor_y;
+
+        let region = self
+            .folds
+            .regions
+            .iter()
+            .filter(|x| x.start <= line && line <= x.end && !self.folds.folded.contains(x))
+            .max_by_key(|x| x.start)
+            .copied();
+
+        if let Some(region) = region {
+            self.folds.fold(region);
+            self.leave_folds();
+        }
+    }
+
+    // opens the fold the cursor line is folded into
+    pub fn unfold(&mut self) {
+        let line = self.cursor.cursor_y;
+        self.folds
+            .folded
+            .retain(|x| !(x.start <= line && line <= x.end));
+    }
+
+    // folds or opens the region starting at the line
+    pub fn toggle_fold(&mut self, line: usize) {
+        if let Some(fold) = self.folds.folded_at(line) {
+            self.folds.folded.retain(|x| *x != fold);
+        } else if let Some(region) = self.folds.region_at(line) {
+            self.folds.fold(region);
+            self.leave_folds();
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folds.folded = self.folds.regions.clone();
+        self.leave_folds();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.folded.clear();
+    }
+
+    // a cursor on a line that just got folded away moves to the line it's folded into
+    fn leave_folds(&mut self) {
+        let line = self.folds.visible_line(self.cursor.cursor_y);
+
+        if line != self.cursor.cursor_y {
+            self.end_snippet();
+            self.cursor.move_to_line(&self.content, line);
+        }
+    }
+
+    // content starting at the given line
+    pub fn content_from(&self, line: usize) -> &str {
+        if line == 0 {
+            return &self.content;
+        }
+
+        match self.content.match_indices('\n').nth(line - 1) {
+            Some((idx, _)) => &self.content[idx + 1..],
+            None => "",
+        }
+    }
+
+    // text of a single line, without its line break
+    pub fn line(&self, line: usize) -> &str {
+        let content = self.content_from(line);
+
+        &content[..content.find('\n').unwrap_or(content.len())]
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::temp::TempDir;
+
+    fn type_text(buffer: &mut Buffer, text: &str) {
+        text.chars().for_each(|x| buffer.insert(x));
+    }
+
+    #[test]
+    fn typing_a_word_is_undone_in_one_step() {
+        let mut buffer = Buffer::new("a", String::new());
+
+        type_text(&mut buffer, "hello world");
+        buffer.undo();
+        assert_eq!(buffer.content, "hello");
+
+        buffer.undo();
+        assert_eq!(buffer.content, "");
+
+        buffer.redo();
+        buffer.redo();
+        assert_eq!(buffer.content, "hello world");
+        assert_eq!(buffer.cursor.idx, 11);
+    }
+
+    #[test]
+    fn a_new_edit_drops_what_could_be_redone() {
+        let mut buffer = Buffer::new("a", String::new());
+
+        type_text(&mut buffer, "ab");
+        buffer.undo();
+        type_text(&mut buffer, "c");
+        buffer.redo();
+
+        assert_eq!(buffer.content, "c");
+    }
+
+    #[test]
+    fn undoing_back_to_the_save_is_clean() {
+        let dir = TempDir::new("buffer");
+        let path = dir.write("a.txt", "");
+
+        let mut buffer = Buffer::open(&path).unwrap();
+        assert!(!buffer.is_dirty());
+
+        type_text(&mut buffer, "a");
+        assert!(buffer.is_dirty());
+
+        buffer.undo();
+        assert!(!buffer.is_dirty());
+
+        buffer.redo();
+        buffer.save().unwrap();
+        buffer.undo();
+        assert!(buffer.is_dirty());
+
+        // the saved text can't be redone once something else is typed
+        type_text(&mut buffer, " ");
+        buffer.undo();
+        assert_eq!(buffer.content, "");
+        assert!(buffer.is_dirty());
+    }
+
+    #[test]
+    fn folding_keeps_the_display_column() {
+        let mut buffer = Buffer::new("a", "世界\n  abc\nx".to_string());
+
+        buffer.refresh_folds();
+        buffer.set_cursor(9);
+        buffer.fold_all();
+
+        // two columns in is after the first wide char on the line it folds into
+        assert_eq!(buffer.cursor.idx, 3);
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+
+use winit::keyboard::{Key, ModifiersState, NamedKey, SmolStr};
+
+use crate::editor::Editor;
+
+const MAX_RECENT: usize = 8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keybinding {
+    pub modifiers: ModifiersState,
+    pub key: Key, // key without modifiers applied, characters lowercase
+}
+
+impl Keybinding {
+    // parses bindings like "ctrl+shift+p" or "ctrl+tab"
+    pub fn parse(binding: &str) -> Option<Self> {
+        let mut modifiers = ModifiersState::empty();
+        let mut key = None;
+
+        for part in binding.split('+') {
+            match part.to_lowercase().as_str() {
+                "ctrl" => modifiers |= ModifiersState::CONTROL,
+                "shift" => modifiers |= ModifiersState::SHIFT,
+                "alt" => modifiers |= ModifiersState::ALT,
+                "tab" => key = Some(Key::Named(NamedKey::Tab)),
+                "enter" => key = Some(Key::Named(NamedKey::Enter)),
+                "escape" => key = Some(Key::Named(NamedKey::Escape)),
+                "space" => key = Some(Key::Named(NamedKey::Space)),
+                "left" => key = Some(Key::Named(NamedKey::ArrowLeft)),
+                "right" => key = Some(Key::Named(NamedKey::ArrowRight)),
+                "up" => key = Some(Key::Named(NamedKey::ArrowUp)),
+                "down" => key = Some(Key::Named(NamedKey::ArrowDown)),
+                "f1" => key = Some(Key::Named(NamedKey::F1)),
+                "f2" => key = Some(Key::Named(NamedKey::F2)),
+                "f3" => key = Some(Key::Named(NamedKey::F3)),
+                "f4" => key = Some(Key::Named(NamedKey::F4)),
+                "f5" => key = Some(Key::Named(NamedKey::F5)),
+                "f6" => key = Some(Key::Named(NamedKey::F6)),
+                "f7" => key = Some(Key::Named(NamedKey::F7)),
+                "f8" => key = Some(Key::Named(NamedKey::F8)),
+                "f9" => key = Some(Key::Named(NamedKey::F9)),
+                "f10" => key = Some(Key::Named(NamedKey::F10)),
+                "f11" => key = Some(Key::Named(NamedKey::F11)),
+                "f12" => key = Some(Key::Named(NamedKey::F12)),
+                // "ctrl++" splits into an empty last part
+                "" => key = Some(Key::Character(SmolStr::new("+"))),
+                other if other.chars().count() == 1 => {
+                    key = Some(Key::Character(SmolStr::new(other)))
+                }
+                _ => return None,
+            }
+        }
+
+        Some(Keybinding {
+            modifiers,
+            key: key?,
+        })
+    }
+
+    pub fn matches(&self, modifiers: ModifiersState, key: &Key) -> bool {
+        let key = match key {
+            Key::Character(char) => Key::Character(SmolStr::new(char.to_lowercase())),
+            key => key.clone(),
+        };
+
+        // only the modifiers the editor binds are compared
+        let modifiers =
+            modifiers & (ModifiersState::CONTROL | ModifiersState::SHIFT | ModifiersState::ALT);
+
+        self.modifiers == modifiers && self.key == key
+    }
+}
+
+impl fmt::Display for Keybinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.control_key() {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.shift_key() {
+            write!(f, "Shift+")?;
+        }
+        if self.modifiers.alt_key() {
+            write!(f, "Alt+")?;
+        }
+
+        match &self.key {
+            Key::Character(char) => write!(f, "{}", char.to_uppercase()),
+            Key::Named(NamedKey::ArrowLeft) => write!(f, "Left"),
+            Key::Named(NamedKey::ArrowRight) => write!(f, "Right"),
+            Key::Named(NamedKey::ArrowUp) => write!(f, "Up"),
+            Key::Named(NamedKey::ArrowDown) => write!(f, "Down"),
+            Key::Named(named) => write!(f, "{:?}", named),
+            key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+pub struct Command {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub keybindings: Vec<Keybinding>,
+    pub action: fn(&mut Editor),
+}
+
+#[derive(Default)]
+pub struct Commands {
+    pub commands: Vec<Command>,
+    pub recent: Vec<&'static str>, // most recently used first
+}
+
+impl Commands {
+    pub fn register(
+        &mut self,
+        id: &'static str,
+        name: &'static str,
+        keybindings: &[&str],
+        action: fn(&mut Editor),
+    ) {
+        let keybindings = keybindings
+            .iter()
+            .map(|binding| {
+                Keybinding::parse(binding)
+                    .unwrap_or_else(|| panic!("invalid keybinding {} for {}", binding, id))
+            })
+            .collect();
+
+        self.commands.push(Command {
+            id,
+            name,
+            keybindings,
+            action,
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.id == id)
+    }
+
+    pub fn lookup(&self, modifiers: ModifiersState, key: &Key) -> Option<&Command> {
+        self.commands.iter().find(|command| {
+            command
+                .keybindings
+                .iter()
+                .any(|binding| binding.matches(modifiers, key))
+        })
+    }
+
+    pub fn record(&mut self, id: &'static str) {
+        self.recent.retain(|recent| *recent != id);
+        self.recent.insert(0, id);
+        self.recent.truncate(MAX_RECENT);
+    }
+
+    // recently used commands first, then the rest in registration order
+    pub fn ordered(&self) -> Vec<&Command> {
+        let recent = self.recent.iter().filter_map(|id| self.get(id));
+        let rest = self
+            .commands
+            .iter()
+            .filter(|command| !self.recent.contains(&command.id));
+
+        recent.chain(rest).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picker::{Picker, PickerItem};
+
+    fn commands(ids: &[&'static str]) -> Commands {
+        let mut commands = Commands::default();
+
+        for id in ids {
+            commands.register(id, id, &[], |_| {});
+        }
+
+        commands
+    }
+
+    fn ids(commands: &Commands) -> Vec<&'static str> {
+        commands.ordered().iter().map(|x| x.id).collect()
+    }
+
+    #[test]
+    fn keybindings_match_ignoring_case_and_other_modifiers() {
+        let binding = Keybinding::parse("ctrl+shift+p").unwrap();
+        let key = Key::Character(SmolStr::new("P"));
+
+        assert!(binding.matches(ModifiersState::CONTROL | ModifiersState::SHIFT, &key));
+        assert!(binding.matches(
+            ModifiersState::CONTROL | ModifiersState::SHIFT | ModifiersState::SUPER,
+            &key
+        ));
+        assert!(!binding.matches(ModifiersState::CONTROL, &key));
+        assert_eq!(binding.to_string(), "Ctrl+Shift+P");
+
+        assert_eq!(Keybinding::parse("ctrl++").unwrap().to_string(), "Ctrl++");
+        assert!(Keybinding::parse("ctrl+pageup").is_none());
+    }
+
+    #[test]
+    fn recent_commands_come_first() {
+        let mut commands = commands(&["a", "b", "c", "d"]);
+        assert_eq!(ids(&commands), ["a", "b", "c", "d"]);
+
+        commands.record("c");
+        commands.record("b");
+        commands.record("c");
+
+        assert_eq!(ids(&commands), ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn only_the_most_recent_are_kept() {
+        let all: Vec<&'static str> = vec!["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"];
+        let mut commands = commands(&all);
+
+        all.iter().for_each(|x| commands.record(x));
+
+        assert_eq!(commands.recent.len(), MAX_RECENT);
+        assert_eq!(ids(&commands)[..3], ["j", "i", "h"]);
+        assert_eq!(ids(&commands)[MAX_RECENT..], ["a", "b"]);
+    }
+
+    #[test]
+    fn palette_keeps_recent_first_among_equal_matches() {
+        let mut commands = commands(&["Save", "Save as"]);
+        commands.register("save_to", "Save to", &[], |_| {});
+        commands.record("save_to");
+
+        let items = commands
+            .ordered()
+            .into_iter()
+            .map(|x| PickerItem {
+                label: x.name.to_string(),
+                detail: String::new(),
+                value: x.id,
+            })
+            .collect();
+        let mut picker = Picker::new("Command palette", items);
+
+        assert_eq!(picker.selected_item(), Some(&"save_to"));
+
+        // once something is typed shorter labels win, equal ones keep the recent first
+        picker.set_query("save");
+        let order: Vec<_> = picker
+            .matches
+            .iter()
+            .map(|x| picker.items[x.0].value)
+            .collect();
+
+        assert_eq!(order, ["Save", "save_to", "Save as"]);
+    }
+}
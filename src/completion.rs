@@ -0,0 +1,472 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    picker::{Picker, PickerItem},
+    snippet::{self, Definition},
+};
+
+pub const ROWS: usize = 8;
+
+#[derive(Clone, Debug)]
+pub struct Completion {
+    pub label: String,
+    pub detail: String,
+    pub documentation: Option<String>,
+    pub text: String,         // replaces the typed prefix
+    pub snippet: bool,        // text uses snippet syntax
+    pub start: Option<usize>, // start of the replaced text if it isn't the prefix
+    pub source: &'static str,
+}
+
+impl Completion {
+    // the text accepting it replaces, the prefix typed from `start` unless the item says otherwise
+    pub fn replaces(&self, start: usize, cursor: usize) -> Range<usize> {
+        self.start.unwrap_or(start).min(cursor)..cursor
+    }
+}
+
+// what the completion sources get to look at
+pub struct Context<'a> {
+    pub content: &'a str,
+    pub cursor: usize,
+    pub start: usize, // where the typed prefix starts
+    pub path: Option<&'a Path>,
+    pub root: &'a Path,
+    pub path_mode: bool, // the cursor is in something that looks like a file path
+}
+
+impl<'a> Context<'a> {
+    pub fn new(content: &'a str, cursor: usize, path: Option<&'a Path>, root: &'a Path) -> Self {
+        let token_start = content[..cursor]
+            .char_indices()
+            .rev()
+            .take_while(|(_, x)| !is_path_delimiter(*x))
+            .last()
+            .map_or(cursor, |(idx, _)| idx);
+
+        let path_mode = content[token_start..cursor].contains('/');
+
+        Context {
+            content,
+            cursor,
+            start: prefix_start(content, cursor, path_mode),
+            path,
+            root,
+            path_mode,
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.content[self.start..self.cursor]
+    }
+}
+
+fn is_path_delimiter(char: char) -> bool {
+    char.is_whitespace() || "\"'`()[]{}<>=,;".contains(char)
+}
+
+pub fn is_word_char(char: char, path_mode: bool) -> bool {
+    char.is_alphanumeric() || char == '_' || (path_mode && (char == '.' || char == '-'))
+}
+
+fn prefix_start(content: &str, cursor: usize, path_mode: bool) -> usize {
+    content[..cursor]
+        .char_indices()
+        .rev()
+        .take_while(|(_, x)| is_word_char(*x, path_mode))
+        .last()
+        .map_or(cursor, |(idx, _)| idx)
+}
+
+pub trait Source {
+    fn complete(&self, context: &Context) -> Vec<Completion>;
+}
+
+// words used elsewhere in the buffer, the closest to the cursor first
+pub struct BufferWords;
+
+impl Source for BufferWords {
+    fn complete(&self, context: &Context) -> Vec<Completion> {
+        if context.path_mode {
+            return Vec::new();
+        }
+
+        let content = context.content;
+        let mut distances: HashMap<&str, usize> = HashMap::new();
+        let mut start = None;
+
+        for (idx, char) in content.char_indices().chain([(content.len(), ' ')]) {
+            match (is_word_char(char, false), start) {
+                (true, None) => start = Some(idx),
+                (false, Some(word_start)) => {
+                    start = None;
+
+                    // the word being typed doesn't complete itself
+                    if word_start == context.start {
+                        continue;
+                    }
+
+                    let word = &content[word_start..idx];
+
+                    if word.chars().count() < 3 || word.starts_with(|x: char| x.is_ascii_digit()) {
+                        continue;
+                    }
+
+                    let distance = word_start.abs_diff(context.cursor);
+                    let entry = distances.entry(word).or_insert(distance);
+                    *entry = (*entry).min(distance);
+                }
+                _ => (),
+            }
+        }
+
+        let mut words: Vec<(&str, usize)> = distances.into_iter().collect();
+        words.sort_by_key(|(word, distance)| (*distance, *word));
+
+        words
+            .into_iter()
+            .map(|(word, _)| Completion {
+                label: word.to_string(),
+                detail: "word".to_string(),
+                documentation: None,
+                text: word.to_string(),
+                snippet: false,
+                start: None,
+                source: "words",
+            })
+            .collect()
+    }
+}
+
+// entries of the directory typed so far, relative to the file's directory
+pub struct FilePaths;
+
+impl Source for FilePaths {
+    fn complete(&self, context: &Context) -> Vec<Completion> {
+        if !context.path_mode {
+            return Vec::new();
+        }
+
+        let token_start = context.content[..context.start]
+            .char_indices()
+            .rev()
+            .take_while(|(_, x)| !is_path_delimiter(*x))
+            .last()
+            .map_or(context.start, |(idx, _)| idx);
+        let dir = &context.content[token_start..context.start];
+
+        let base = context.path.and_then(Path::parent).unwrap_or(context.root);
+
+        let dir = if let Some(home) = dir.strip_prefix("~/") {
+            std::env::var_os("HOME").map_or(base.join(dir), |x| PathBuf::from(x).join(home))
+        } else {
+            base.join(dir)
+        };
+
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+
+        let hidden = context.prefix().starts_with('.');
+
+        let mut entries: Vec<(String, bool)> = read_dir
+            .filter_map(|entry| entry.ok())
+            .map(|entry| {
+                (
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path().is_dir(),
+                )
+            })
+            .filter(|(name, _)| hidden || !name.starts_with('.'))
+            .collect();
+
+        entries.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+
+        entries
+            .into_iter()
+            .map(|(name, is_dir)| {
+                let text = if is_dir { format!("{}/", name) } else { name };
+
+                Completion {
+                    label: text.clone(),
+                    detail: if is_dir { "directory" } else { "file" }.to_string(),
+                    documentation: None,
+                    text,
+                    snippet: false,
+                    start: None,
+                    source: "paths",
+                }
+            })
+            .collect()
+    }
+}
+
+// snippets for the file type, offered by their prefix
+pub struct Snippets {
+    pub definitions: Vec<Definition>,
+}
+
+impl Source for Snippets {
+    fn complete(&self, context: &Context) -> Vec<Completion> {
+        if context.path_mode {
+            return Vec::new();
+        }
+
+        self.definitions
+            .iter()
+            .filter(|x| x.applies(context.path))
+            .map(|definition| Completion {
+                label: definition.prefix.clone(),
+                detail: definition.description.clone(),
+                documentation: Some(snippet::plain(&definition.body)),
+                text: definition.body.clone(),
+                snippet: true,
+                start: None,
+                source: "snippets",
+            })
+            .collect()
+    }
+}
+
+pub fn sources(snippets: Vec<Definition>) -> Vec<Box<dyn Source>> {
+    vec![
+        Box::new(Snippets {
+            definitions: snippets,
+        }),
+        Box::new(FilePaths),
+        Box::new(BufferWords),
+    ]
+}
+
+// the list of completions shown at the cursor, filtered by the prefix typed since it opened
+pub struct Popup {
+    pub picker: Picker<Completion>,
+    pub start: usize,
+    pub path_mode: bool,
+    pub path: Option<PathBuf>, // file of the buffer it was opened in
+}
+
+impl Popup {
+    pub fn new(context: &Context, items: Vec<Completion>) -> Self {
+        let mut popup = Popup {
+            picker: Picker::new("", Vec::new()),
+            start: context.start,
+            path_mode: context.path_mode,
+            path: context.path.map(Path::to_path_buf),
+        };
+
+        popup.extend(items);
+        popup.picker.set_query(context.prefix());
+        popup
+    }
+
+    // later results replace plain words with the same label
+    pub fn extend(&mut self, items: Vec<Completion>) {
+        let labels: HashSet<&str> = items.iter().map(|x| x.label.as_str()).collect();
+
+        self.picker
+            .items
+            .retain(|x| x.value.source != "words" || !labels.contains(x.label.as_str()));
+
+        let items: Vec<PickerItem<Completion>> = items
+            .into_iter()
+            .map(|item| PickerItem {
+                label: item.label.clone(),
+                detail: item.detail.clone(),
+                value: item,
+            })
+            .collect();
+
+        self.picker.extend(items);
+    }
+
+    // follows the prefix as it's typed, false once the cursor left it
+    pub fn update(&mut self, content: &str, cursor: usize) -> bool {
+        if cursor < self.start || !content.is_char_boundary(self.start) {
+            return false;
+        }
+
+        let prefix = &content[self.start..cursor];
+
+        if !prefix.chars().all(|x| is_word_char(x, self.path_mode)) {
+            return false;
+        }
+
+        if prefix != self.picker.query {
+            self.picker.set_query(prefix);
+        }
+
+        true
+    }
+
+    // the choices of a snippet stop, offered over its placeholder
+    pub fn choices(start: usize, path: Option<PathBuf>, choices: &[String]) -> Self {
+        let items = choices
+            .iter()
+            .map(|choice| Completion {
+                label: choice.clone(),
+                detail: "choice".to_string(),
+                documentation: None,
+                text: choice.clone(),
+                snippet: false,
+                start: None,
+                source: "choices",
+            })
+            .collect();
+
+        let mut popup = Popup {
+            picker: Picker::new("", Vec::new()),
+            start,
+            path_mode: false,
+            path,
+        };
+
+        popup.extend(items);
+        popup
+    }
+
+    pub fn selected(&self) -> Option<&Completion> {
+        self.picker.selected_item()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.picker.matches.is_empty()
+    }
+}
+
+// breaks text into lines of at most `columns` chars, at spaces where possible
+pub fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let columns = columns.max(1);
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+
+        for word in paragraph.split(' ') {
+            let len = line.chars().count();
+
+            if len > 0 && len + 1 + word.chars().count() > columns {
+                lines.push(std::mem::take(&mut line));
+            } else if len > 0 {
+                line.push(' ');
+            }
+
+            line.push_str(word);
+
+            // words longer than a line are cut
+            while line.chars().count() > columns {
+                let rest: String = line.chars().skip(columns).collect();
+                lines.push(line.chars().take(columns).collect());
+                line = rest;
+            }
+        }
+
+        lines.push(line);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(label: &str, source: &'static str) -> Completion {
+        Completion {
+            label: label.to_string(),
+            detail: String::new(),
+            documentation: None,
+            text: label.to_string(),
+            snippet: false,
+            start: None,
+            source,
+        }
+    }
+
+    fn labels(popup: &Popup) -> Vec<(&str, &str)> {
+        popup
+            .picker
+            .matches
+            .iter()
+            .map(|(idx, _)| {
+                let item = &popup.picker.items[*idx].value;
+                (item.label.as_str(), item.source)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wrapping_breaks_at_spaces_and_cuts_long_words() {
+        assert_eq!(wrap("one two three", 7), ["one two", "three"]);
+        assert_eq!(wrap("abcdefghij x", 4), ["abcd", "efgh", "ij x"]);
+        assert_eq!(wrap("äö üß\n\nnext", 5), ["äö üß", "", "next"]);
+    }
+
+    #[test]
+    fn the_prefix_is_the_word_before_the_cursor() {
+        let root = Path::new("/");
+        let content = "let value = some_val";
+
+        let context = Context::new(content, content.len(), None, root);
+        assert_eq!(context.prefix(), "some_val");
+        assert!(!context.path_mode);
+
+        // in a path the prefix stops at the last slash, dots and dashes are part of it
+        let content = "open(src/my-file.r";
+        let context = Context::new(content, content.len(), None, root);
+        assert_eq!(context.prefix(), "my-file.r");
+        assert!(context.path_mode);
+    }
+
+    #[test]
+    fn the_popup_follows_the_prefix_until_the_cursor_leaves_it() {
+        let content = "foo fo";
+        let context = Context::new(content, content.len(), None, Path::new("/"));
+        let mut popup = Popup::new(&context, vec![word("foo", "words"), word("for", "words")]);
+
+        assert_eq!(popup.start, 4);
+        assert_eq!(labels(&popup).len(), 2);
+
+        assert!(popup.update("foo foo", 7));
+        assert_eq!(labels(&popup), [("foo", "words")]);
+
+        assert!(!popup.update("foo foo.", 8));
+        assert!(!popup.update("foo f", 3));
+    }
+
+    #[test]
+    fn accepting_replaces_the_typed_prefix() {
+        let mut content = "let x = so".to_string();
+        let context = Context::new(&content, content.len(), None, Path::new("/"));
+        let item = word("some_value", "words");
+
+        content.replace_range(item.replaces(context.start, context.cursor), &item.text);
+        assert_eq!(content, "let x = some_value");
+
+        // an item can replace more than the prefix, never past the cursor
+        let item = Completion {
+            start: Some(4),
+            ..word("y = 1", "lsp")
+        };
+        assert_eq!(item.replaces(8, 10), 4..10);
+        assert_eq!(item.replaces(2, 3), 3..3);
+    }
+
+    #[test]
+    fn later_sources_replace_words_with_the_same_label() {
+        let content = "fo";
+        let context = Context::new(content, content.len(), None, Path::new("/"));
+        let mut popup = Popup::new(&context, vec![word("foo", "words"), word("fold", "words")]);
+
+        popup.extend(vec![word("foo", "lsp")]);
+
+        let mut labels = labels(&popup);
+        labels.sort();
+        assert_eq!(labels, [("fold", "words"), ("foo", "lsp")]);
+    }
+}
@@ -1,9 +1,59 @@
-#[derive(Debug)]
+use unicode_width::UnicodeWidthChar;
+
+use crate::fold::Folds;
+
+// columns a char takes up, east asian wide chars and emoji take two
+pub fn char_width(char: char) -> usize {
+    char.width().unwrap_or(1)
+}
+
+// display column of a byte offset into a line
+pub fn column(line: &str, offset: usize) -> usize {
+    line[..offset].chars().map(char_width).sum()
+}
+
+// byte offset of a display column on a line, a column inside a wide char goes before it
+pub fn offset(line: &str, column: usize) -> usize {
+    let mut width = 0;
+
+    for (idx, char) in line.char_indices() {
+        width += char_width(char);
+
+        if width > column {
+            return idx;
+        }
+    }
+
+    line.len()
+}
+
+// how a cursor is drawn in the cell of the char it's on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorStyle {
+    Block,     // fills the cell, the char shows inverted
+    Bar,       // a thin line before the char
+    Underline, // a thin line under the char
+    Hollow,    // the outline of the cell
+}
+
+impl CursorStyle {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "block" => Some(CursorStyle::Block),
+            "bar" => Some(CursorStyle::Bar),
+            "underline" => Some(CursorStyle::Underline),
+            "hollow" => Some(CursorStyle::Hollow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Cursor {
     pub idx: usize,          // cursor idx in string
     pub cursor_y: usize,     // cursor y offset
-    pub cursor_x: usize,     // cursor x offset
-    desired_cursor_x: usize, // desired cursor x offset
+    pub cursor_x: usize,     // cursor x offset in bytes
+    desired_cursor_x: usize, // display column kept when moving between lines
 }
 
 impl Cursor {
@@ -16,72 +66,113 @@ impl Cursor {
         }
     }
 
-    pub fn move_left(&mut self, content: &String) {
-        if self.idx > 0 {
-            self.idx -= 1;
-            if self.cursor_x == 0 {
-                let prev_line = content.lines().nth(self.cursor_y - 1).unwrap();
-                self.cursor_x = prev_line.len();
-                self.desired_cursor_x = self.cursor_x;
-            } else {
-                self.cursor_x -= 1;
-                self.desired_cursor_x = self.cursor_x;
-            }
-            if content.chars().nth(self.idx).unwrap() == '\n' {
-                self.cursor_y -= 1;
-            }
-        }
-    }
+    pub fn move_left(&mut self, content: &str) {
+        let Some(char) = content[..self.idx].chars().next_back() else {
+            return;
+        };
+
+        self.idx -= char.len_utf8();
 
-    pub fn move_right(&mut self, content: &String) {
-        if self.idx < content.len() {
-            self.idx += 1;
-            self.cursor_x += 1;
-            self.desired_cursor_x = self.cursor_x;
-            if content.chars().nth(self.idx - 1).unwrap() == '\n' {
-                self.cursor_y += 1;
-                self.cursor_x = 0;
-                self.desired_cursor_x = self.cursor_x;
-            }
+        if char == '\n' {
+            self.cursor_y -= 1;
+            self.cursor_x = self.idx - content[..self.idx].rfind('\n').map_or(0, |x| x + 1);
+        } else {
+            self.cursor_x -= char.len_utf8();
         }
+
+        self.desired_cursor_x = column(&content[self.idx - self.cursor_x..], self.cursor_x);
     }
 
-    pub fn move_down(&mut self, content: &String) {
-        if self.cursor_y > content.split('\n').count() - 1 {
+    pub fn move_right(&mut self, content: &str) {
+        let Some(char) = content[self.idx..].chars().next() else {
             return;
+        };
+
+        self.idx += char.len_utf8();
+
+        if char == '\n' {
+            self.cursor_y += 1;
+            self.cursor_x = 0;
+        } else {
+            self.cursor_x += char.len_utf8();
         }
 
-        if self.cursor_y == content.split('\n').count() - 1 {
-            self.cursor_y = content.split('\n').count() - 1;
+        self.desired_cursor_x = column(&content[self.idx - self.cursor_x..], self.cursor_x);
+    }
+
+    pub fn move_down(&mut self, content: &str, folds: &Folds) {
+        let lines = content.split('\n').count();
+
+        // a folded region is stepped over, there's nothing to go to if it reaches the end
+        let next = folds
+            .folded_at(self.cursor_y)
+            .map_or(self.cursor_y, |x| x.end)
+            + 1;
+
+        if next < lines {
+            self.move_to_line(content, next);
+        } else if folds.folded_at(self.cursor_y).is_none() {
+            // the last line moves to its end
             self.idx = content.len();
-            self.cursor_x = content.split('\n').last().unwrap_or_else(|| "").len();
-            return;
+            self.cursor_x = content.split('\n').next_back().unwrap_or_default().len();
         }
+    }
 
-        let curr_line = content.lines().nth(self.cursor_y).unwrap();
-        let next_line = content.lines().nth(self.cursor_y + 1).unwrap_or_else(|| "");
+    // keeps the display column the cursor had, as far as the line allows
+    pub fn move_to_line(&mut self, content: &str, line: usize) {
+        let desired = self.desired_cursor_x;
+        let text = content.split('\n').nth(line).unwrap_or_default();
 
-        self.cursor_y += 1;
-        self.idx += curr_line
-            .len()
-            .min(curr_line.len() - self.cursor_x + next_line.len())
-            + 1;
-        self.cursor_x = self.cursor_x.min(next_line.len());
+        self.place(content, line, offset(text, desired));
+        self.desired_cursor_x = desired;
     }
 
-    pub fn move_up(&mut self, content: &String) {
+    // moves before the `char_column`th char of a line, or to its end if the line is shorter
+    pub fn move_to(&mut self, content: &str, line: usize, char_column: usize) {
+        let lines = content.split('\n').count();
+        let text = content
+            .split('\n')
+            .nth(line.min(lines - 1))
+            .unwrap_or_default();
+        let offset = text
+            .char_indices()
+            .nth(char_column)
+            .map_or(text.len(), |(idx, _)| idx);
+
+        self.place(content, line, offset);
+    }
+
+    // moves to a byte offset into a line, which has to be on a char boundary
+    fn place(&mut self, content: &str, line: usize, offset: usize) {
+        let lines: Vec<&str> = content.split('\n').collect();
+
+        self.cursor_y = line.min(lines.len() - 1);
+        self.cursor_x = offset.min(lines[self.cursor_y].len());
+        self.desired_cursor_x = column(lines[self.cursor_y], self.cursor_x);
+        self.idx = lines[..self.cursor_y]
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            + self.cursor_x;
+    }
+
+    pub fn set_idx(&mut self, content: &str, idx: usize) {
+        let before = &content[..idx];
+
+        self.idx = idx;
+        self.cursor_y = before.matches('\n').count();
+        self.cursor_x = before.len() - before.rfind('\n').map_or(0, |x| x + 1);
+        self.desired_cursor_x = column(&before[idx - self.cursor_x..], self.cursor_x);
+    }
+
+    pub fn move_up(&mut self, content: &str, folds: &Folds) {
         if self.cursor_y == 0 {
             self.cursor_x = 0;
             self.idx = 0;
             return;
         }
 
-        let mut lines = content.lines().skip(self.cursor_y - 1);
-
-        let above_line = lines.next().unwrap();
-
-        self.cursor_y -= 1;
-        self.idx -= (above_line.len()).max(self.cursor_x) + 1;
-        self.cursor_x = self.cursor_x.min(above_line.len());
+        // lands on the line a folded region above is folded into
+        self.move_to_line(content, folds.visible_line(self.cursor_y - 1));
     }
 }
@@ -0,0 +1,34 @@
+use std::time::Instant;
+
+// what needs drawing and when, so the event loop can sleep until there's something new to show
+#[derive(Default)]
+pub struct Damage {
+    invalid: bool,             // the next frame has to be drawn
+    deadline: Option<Instant>, // earliest time something asked to be drawn again
+}
+
+impl Damage {
+    // the screen is out of date, like after an edit or a resize
+    pub fn invalidate(&mut self) {
+        self.invalid = true;
+    }
+
+    // asks for a frame at a later time, like the next step of an animation or a cursor blink
+    pub fn schedule(&mut self, at: Instant) {
+        self.deadline = Some(self.deadline.map_or(at, |x| x.min(at)));
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    // true if a frame is due by `now`, the request is used up either way
+    pub fn take(&mut self, now: Instant) -> bool {
+        if self.deadline.is_some_and(|x| x <= now) {
+            self.deadline = None;
+            self.invalid = true;
+        }
+
+        std::mem::take(&mut self.invalid)
+    }
+}
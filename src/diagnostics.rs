@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use regex::Regex;
+
+use crate::{
+    lsp::{Position, Range},
+    vertex::ColorVertex,
+    waker::Waker,
+    Rectangle,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl Severity {
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            Severity::Error => [0.9, 0.25, 0.25],
+            Severity::Warning => [0.9, 0.7, 0.2],
+            Severity::Information => [0.3, 0.6, 0.9],
+            Severity::Hint => [0.5, 0.5, 0.5],
+        }
+    }
+
+    // gutter marker inside a square: a diamond for errors, a triangle for warnings,
+    // a square for information and a dot sized square for hints
+    pub fn icon(self, rect: Rectangle) -> Vec<ColorVertex> {
+        let color = self.color();
+        let vertex = |x: f32, y: f32| ColorVertex {
+            position: [rect.left + x * rect.width, rect.bottom + y * rect.height],
+            color,
+        };
+
+        match self {
+            Severity::Error => vec![
+                vertex(0.5, 1.0),
+                vertex(1.0, 0.5),
+                vertex(0.5, 0.0),
+                vertex(0.0, 0.5),
+            ],
+            // a quad with its last corner repeated collapses into a triangle
+            Severity::Warning => vec![
+                vertex(0.5, 1.0),
+                vertex(1.0, 0.0),
+                vertex(0.0, 0.0),
+                vertex(0.0, 0.0),
+            ],
+            Severity::Information => ColorVertex::from(rect, color),
+            Severity::Hint => ColorVertex::from(
+                Rectangle {
+                    bottom: rect.bottom + rect.height / 3.0,
+                    left: rect.left + rect.width / 3.0,
+                    height: rect.height / 3.0,
+                    width: rect.width / 3.0,
+                },
+                color,
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    // start and end as (line, char column), empty ranges cover one character
+    pub fn columns(&self, content: &str) -> ((usize, usize), (usize, usize)) {
+        let start = (self.range.start.line, self.range.start.column(content));
+        let end = (self.range.end.line, self.range.end.column(content));
+
+        if end <= start {
+            return (start, (start.0, start.1 + 1));
+        }
+
+        (start, end)
+    }
+
+    pub fn contains(&self, content: &str, line: usize, column: usize) -> bool {
+        let (start, end) = self.columns(content);
+
+        start <= (line, column) && (line, column) < end
+    }
+}
+
+// something that produces diagnostics in the background, checked once per frame
+pub trait Provider {
+    // returns false once the provider is done and can be dropped
+    fn poll(&mut self, diagnostics: &mut Diagnostics) -> bool;
+}
+
+// diagnostics for every file, kept separately per source so each can replace its own
+#[derive(Default)]
+pub struct Diagnostics {
+    files: HashMap<PathBuf, HashMap<String, Vec<Diagnostic>>>,
+    providers: Vec<Box<dyn Provider>>,
+    changed: bool, // something was published or cleared since the last poll
+}
+
+impl Diagnostics {
+    pub fn register(&mut self, provider: Box<dyn Provider>) {
+        self.providers.push(provider);
+    }
+
+    // replaces what `source` reported for the file
+    pub fn publish(&mut self, source: &str, path: PathBuf, diagnostics: Vec<Diagnostic>) {
+        let sources = self.files.entry(path.clone()).or_default();
+
+        if diagnostics.is_empty() {
+            sources.remove(source);
+        } else {
+            sources.insert(source.to_string(), diagnostics);
+        }
+
+        if sources.is_empty() {
+            self.files.remove(&path);
+        }
+
+        self.changed = true;
+    }
+
+    // forgets everything `source` reported
+    pub fn clear(&mut self, source: &str) {
+        for sources in self.files.values_mut() {
+            sources.remove(source);
+        }
+
+        self.files.retain(|_, sources| !sources.is_empty());
+        self.changed = true;
+    }
+
+    // runs the providers, returns true if any diagnostics changed since the last call
+    pub fn poll(&mut self) -> bool {
+        let mut providers = std::mem::take(&mut self.providers);
+
+        providers.retain_mut(|provider| provider.poll(self));
+
+        // providers registered while polling come after the existing ones
+        providers.append(&mut self.providers);
+        self.providers = providers;
+
+        std::mem::take(&mut self.changed)
+    }
+
+    pub fn for_file(&self, path: &Path) -> Vec<&Diagnostic> {
+        let mut diagnostics: Vec<&Diagnostic> = self
+            .files
+            .get(path)
+            .into_iter()
+            .flat_map(|sources| sources.values().flatten())
+            .collect();
+
+        diagnostics.sort_by_key(|x| (x.range.start, x.severity));
+        diagnostics
+    }
+
+    // every diagnostic by file, then position
+    pub fn all(&self) -> Vec<(&Path, &Diagnostic)> {
+        let mut paths: Vec<&PathBuf> = self.files.keys().collect();
+        paths.sort();
+
+        paths
+            .into_iter()
+            .flat_map(|path| {
+                self.for_file(path)
+                    .into_iter()
+                    .map(move |x| (path.as_path(), x))
+            })
+            .collect()
+    }
+
+    // the most severe diagnostic touching the position
+    pub fn at(
+        &self,
+        path: &Path,
+        content: &str,
+        line: usize,
+        column: usize,
+    ) -> Option<&Diagnostic> {
+        self.for_file(path)
+            .into_iter()
+            .filter(|x| x.contains(content, line, column))
+            .min_by_key(|x| x.severity)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.files
+            .values()
+            .flat_map(|sources| sources.values().flatten())
+            .filter(|x| x.severity == severity)
+            .count()
+    }
+}
+
+// parses compiler style "path:line:column: severity: message" lines
+pub fn parse_output(root: &Path, output: &str) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let pattern =
+        Regex::new(r"^(.+?):(\d+):(\d+):\s+(error|warning|note|help|info)(\[\w+\])?:\s*(.*)$")
+            .unwrap();
+
+    let mut files: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+
+    for line in output.lines() {
+        let Some(captures) = pattern.captures(line.trim()) else {
+            continue;
+        };
+
+        let position = Position {
+            line: captures[2].parse::<usize>().unwrap_or(1).saturating_sub(1),
+            character: captures[3].parse::<usize>().unwrap_or(1).saturating_sub(1),
+        };
+
+        files
+            .entry(root.join(&captures[1]))
+            .or_default()
+            .push(Diagnostic {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                severity: match &captures[4] {
+                    "error" => Severity::Error,
+                    "warning" => Severity::Warning,
+                    "note" | "info" => Severity::Information,
+                    _ => Severity::Hint,
+                },
+                message: captures[6].to_string(),
+                source: Some("build".to_string()),
+            });
+    }
+
+    files
+}
+
+// runs a build command and reports the problems in its output once it exits
+pub struct BuildProvider {
+    receiver: Receiver<HashMap<PathBuf, Vec<Diagnostic>>>,
+}
+
+impl BuildProvider {
+    pub fn start(root: &Path, program: &str, args: &[&str], waker: Waker) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        let mut command = Command::new(program);
+        command.args(args).current_dir(root);
+
+        let root = root.to_path_buf();
+
+        thread::spawn(move || {
+            let output = match command.output() {
+                Ok(output) => format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+                Err(_) => String::new(),
+            };
+
+            let _ = sender.send(parse_output(&root, &output));
+            waker.wake();
+        });
+
+        BuildProvider { receiver }
+    }
+
+    // the build command for the project, if there's one we know how to run
+    pub fn for_project(root: &Path, waker: Waker) -> Option<Self> {
+        if root.join("Cargo.toml").is_file() {
+            return Some(BuildProvider::start(
+                root,
+                "cargo",
+                &["check", "--message-format=short"],
+                waker,
+            ));
+        }
+
+        if root.join("Makefile").is_file() {
+            return Some(BuildProvider::start(root, "make", &[], waker));
+        }
+
+        None
+    }
+}
+
+impl Provider for BuildProvider {
+    fn poll(&mut self, diagnostics: &mut Diagnostics) -> bool {
+        let Ok(files) = self.receiver.try_recv() else {
+            return true;
+        };
+
+        diagnostics.clear("build");
+
+        for (path, list) in files {
+            diagnostics.publish("build", path, list);
+        }
+
+        false
+    }
+}
@@ -0,0 +1,1472 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Instant,
+};
+
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton},
+    keyboard::{Key, ModifiersState, NamedKey},
+    platform::modifier_supplement::KeyEventExtModifierSupplement,
+};
+
+use crate::{
+    animation::Motion,
+    buffer::{Buffer, BufferKind},
+    command::Commands,
+    completion::{self, Completion, Context, Popup, Source},
+    cursor::Cursor,
+    damage::Damage,
+    diagnostics::{BuildProvider, Diagnostics},
+    explorer::{self, Explorer},
+    finder,
+    layout::Layout,
+    lsp::{self, Client, Lsp, Position},
+    pane::{Border, Direction},
+    picker::{Picker, PickerEvent, PickerItem},
+    problems::{self, Problems},
+    search::Search,
+    settings::{self, Settings},
+    snippet::{self, Definition},
+    status::{self, StatusBar},
+    text::PADDING,
+    waker::Waker,
+    workspace::Workspace,
+    Rectangle,
+};
+
+// distance from a pane border that still grabs it
+const BORDER_GRAB: f32 = 4.0;
+
+#[derive(Debug, PartialEq)]
+pub enum Mode {
+    Normal,
+    Search,
+    SaveAs { close: bool },
+    ConfirmClose(usize),
+    NewFile,
+    Rename(PathBuf),
+    ConfirmDelete(PathBuf),
+    RenameSymbol,
+}
+
+// what choosing an entry in the picker does
+pub enum Pick {
+    File(PathBuf),
+    Command(&'static str),
+    Location(lsp::Location),
+}
+
+pub struct Editor {
+    pub root: PathBuf,
+    pub workspace: Workspace,
+    pub search: Search,
+    pub explorer: Explorer,
+    pub lsp: Lsp,
+    pub diagnostics: Diagnostics,
+    pub problems: Problems,
+    pub picker: Option<Picker<Pick>>,
+    pub completion: Option<Popup>,
+    completion_sources: Vec<Box<dyn Source>>,
+    snippets: Vec<Definition>,
+    pub commands: Commands,
+    pub status: StatusBar,
+    file_index: Option<Receiver<Vec<PathBuf>>>,
+    pub modifiers: ModifiersState,
+    pub mode: Mode,
+    pub input: String,           // text typed into the current prompt
+    pub message: Option<String>, // shown in the status line until the next key press
+    pub area: Rectangle,         // window area the panes are laid out in
+    pub sidebar: Rectangle,      // window area of the explorer, if visible
+    pub panel: Rectangle,        // window area of the problems panel, if visible
+    pub mouse: (f32, f32),       // pointer position, from the bottom left of the window
+    pub fold_markers: Vec<(Rectangle, usize, usize)>, // gutter markers drawn as (area, pane, line)
+    pub scrolled: HashMap<usize, f32>, // how far each pane's text was moved up when last drawn
+    pub layout: Rc<Layout>,
+    pub subpixel: bool, // text is antialiased per color channel instead of per pixel
+    pub damage: Damage,
+    pub waker: Waker, // given to background work, which wakes the event loop when it has results
+    pub motion: Motion,
+    pub settings: Settings,
+    pub typed: Instant, // last key press or click, the cursor stays on while typing
+    pub show_stats: bool, // frame timings are drawn over the top right corner
+    drag: Option<Border>,
+    selecting: bool, // the button went down on text and hasn't been released
+    wheel: f32,      // scrolled rows not applied yet
+}
+
+impl Editor {
+    pub fn new(workspace: Workspace, root: PathBuf, layout: Rc<Layout>) -> Self {
+        let snippets = snippet::load(&root);
+        let settings = settings::load(&root);
+        let waker = Waker::default();
+
+        Editor {
+            search: Search::new(root.clone()),
+            explorer: Explorer::new(root.clone(), waker.clone()),
+            lsp: Lsp::new(root.clone(), waker.clone()),
+            diagnostics: Diagnostics::default(),
+            problems: Problems::new(),
+            picker: None,
+            completion: None,
+            completion_sources: completion::sources(snippets.clone()),
+            snippets,
+            commands: default_commands(),
+            status: status::default_segments(),
+            file_index: None,
+            root,
+            workspace,
+            modifiers: ModifiersState::empty(),
+            mode: Mode::Normal,
+            input: String::new(),
+            message: None,
+            area: Rectangle {
+                bottom: 0.0,
+                left: 0.0,
+                height: 0.0,
+                width: 0.0,
+            },
+            sidebar: Rectangle {
+                bottom: 0.0,
+                left: 0.0,
+                height: 0.0,
+                width: 0.0,
+            },
+            panel: Rectangle {
+                bottom: 0.0,
+                left: 0.0,
+                height: 0.0,
+                width: 0.0,
+            },
+            mouse: (0.0, 0.0),
+            fold_markers: Vec::new(),
+            scrolled: HashMap::new(),
+            layout,
+            subpixel: false,
+            damage: Damage::default(),
+            waker,
+            motion: Motion::new(settings.reduced_motion),
+            settings,
+            typed: Instant::now(),
+            show_stats: false,
+            drag: None,
+            selecting: false,
+            wheel: 0.0,
+        }
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) {
+        self.message = None;
+        self.typed = Instant::now();
+        self.damage.invalidate();
+
+        if self.picker.is_some() {
+            self.handle_picker_key(event);
+            return;
+        }
+
+        match self.mode {
+            Mode::Normal => self.handle_normal_key(event),
+            Mode::Search
+            | Mode::SaveAs { .. }
+            | Mode::NewFile
+            | Mode::Rename(_)
+            | Mode::RenameSymbol => self.handle_input_key(event),
+            Mode::ConfirmClose(idx) => self.handle_confirm_close_key(event, idx),
+            Mode::ConfirmDelete(_) => self.handle_confirm_delete_key(event),
+        }
+    }
+
+    fn handle_normal_key(&mut self, event: &KeyEvent) {
+        let key = event.key_without_modifiers();
+
+        if let Some(command) = self.commands.lookup(self.modifiers, &key) {
+            self.execute(command.id);
+            return;
+        }
+
+        // unbound shortcuts don't type anything
+        if self.modifiers.control_key() {
+            return;
+        }
+
+        if self.explorer.visible && self.explorer.focused {
+            self.handle_explorer_key(event);
+            return;
+        }
+
+        if self.problems.visible && self.problems.focused {
+            self.handle_problems_key(event);
+            return;
+        }
+
+        if self.completion.is_some() && self.handle_completion_key(&key) {
+            return;
+        }
+
+        if self.handle_snippet_key(&key) {
+            return;
+        }
+
+        let read_only = self.workspace.active().is_read_only();
+        let mut typed = None;
+
+        match key.as_ref() {
+            Key::Named(NamedKey::Enter) if read_only => self.open_search_result(),
+            Key::Named(NamedKey::Escape) if read_only => {
+                self.workspace.close(self.workspace.active)
+            }
+            Key::Named(key) => {
+                let buffer = self.workspace.active_mut();
+
+                match key {
+                    NamedKey::Backspace => buffer.backspace(),
+                    NamedKey::ArrowRight => buffer.move_right(),
+                    NamedKey::ArrowLeft => buffer.move_left(),
+                    NamedKey::ArrowDown => buffer.move_down(),
+                    NamedKey::ArrowUp => buffer.move_up(),
+                    NamedKey::Space => buffer.insert(' '),
+                    NamedKey::Enter => buffer.insert('\n'),
+                    _ => (),
+                }
+            }
+            Key::Character(characters) => {
+                let buffer = self.workspace.active_mut();
+
+                for char in characters.chars() {
+                    buffer.insert(char);
+                }
+
+                typed = characters.chars().last();
+            }
+            _ => (),
+        }
+
+        self.update_completion(typed);
+    }
+
+    // keys the open completion popup takes for itself
+    fn handle_completion_key(&mut self, key: &Key) -> bool {
+        let Some(popup) = &mut self.completion else {
+            return false;
+        };
+
+        // an empty popup isn't shown, so it doesn't take keys either
+        if popup.is_empty() {
+            return false;
+        }
+
+        match key.as_ref() {
+            Key::Named(NamedKey::ArrowUp) => popup.picker.move_up(),
+            Key::Named(NamedKey::ArrowDown) => popup.picker.move_down(),
+            Key::Named(NamedKey::Enter | NamedKey::Tab) => {
+                let popup = self.completion.take().unwrap();
+                let start = popup.start;
+
+                if let Some(item) = popup.picker.into_selected() {
+                    self.accept_completion(start, item);
+                }
+            }
+            Key::Named(NamedKey::Escape) => self.completion = None,
+            _ => return false,
+        }
+
+        true
+    }
+
+    // tab moves between the stops of a snippet, or expands the trigger word before the cursor
+    fn handle_snippet_key(&mut self, key: &Key) -> bool {
+        let shift = self.modifiers.shift_key();
+        let buffer = self.workspace.active_mut();
+
+        match key.as_ref() {
+            Key::Named(NamedKey::Tab) if buffer.snippet.is_some() => {
+                if shift {
+                    buffer.prev_stop();
+                } else {
+                    buffer.next_stop();
+                }
+
+                self.offer_choices();
+            }
+            Key::Named(NamedKey::Escape)
+                if buffer.snippet.is_some() || !buffer.cursors.is_empty() =>
+            {
+                buffer.end_snippet()
+            }
+            Key::Named(NamedKey::Tab) if !shift && !buffer.is_read_only() => {
+                let context = Context::new(
+                    &buffer.content,
+                    buffer.cursor.idx,
+                    buffer.path.as_deref(),
+                    &self.root,
+                );
+
+                let Some(definition) =
+                    snippet::find(&self.snippets, context.prefix(), buffer.path.as_deref())
+                else {
+                    return false;
+                };
+
+                let (start, body) = (context.start, definition.body.clone());
+                self.insert_snippet(start, &body);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    // expands a snippet over the text from `start` to the cursor
+    fn insert_snippet(&mut self, start: usize, body: &str) {
+        let buffer = self.workspace.active_mut();
+        let cursor = buffer.cursor.idx;
+
+        let line_start = buffer.content[..start].rfind('\n').map_or(0, |x| x + 1);
+        let indent: String = buffer.content[line_start..]
+            .chars()
+            .take_while(|x| *x == ' ' || *x == '\t')
+            .collect();
+
+        let variables = snippet::variables(buffer.path.as_deref(), &buffer.content, cursor);
+        let expansion = snippet::expand(body, &variables, &indent);
+
+        buffer.insert_snippet(start..cursor, expansion);
+        self.offer_choices();
+    }
+
+    // a stop with choices lists them over its placeholder
+    fn offer_choices(&mut self) {
+        let buffer = self.workspace.active();
+
+        let Some(stop) = buffer.snippet.as_ref().map(|x| x.stop()) else {
+            return;
+        };
+
+        if !stop.choices.is_empty() {
+            self.completion = Some(Popup::choices(
+                stop.ranges[0].start,
+                buffer.path.clone(),
+                &stop.choices,
+            ));
+        }
+    }
+
+    // keeps the popup in step with the typed prefix, or opens it when a word gets going
+    fn update_completion(&mut self, typed: Option<char>) {
+        let buffer = self.workspace.active();
+
+        if let Some(popup) = &mut self.completion {
+            if popup.path != buffer.path || !popup.update(&buffer.content, buffer.cursor.idx) {
+                self.completion = None;
+            }
+            return;
+        }
+
+        let Some(char) = typed else {
+            return;
+        };
+
+        if buffer.is_read_only() {
+            return;
+        }
+
+        let context = Context::new(
+            &buffer.content,
+            buffer.cursor.idx,
+            buffer.path.as_deref(),
+            &self.root,
+        );
+        let prefix = context.prefix().chars().count();
+
+        let trigger = if context.path_mode {
+            char == '/' || prefix == 1
+        } else {
+            completion::is_word_char(char, false) && prefix == 2
+        };
+
+        if trigger {
+            self.open_completion();
+        }
+    }
+
+    fn open_completion(&mut self) {
+        let buffer = self.workspace.active();
+
+        if buffer.is_read_only() {
+            return;
+        }
+
+        let context = Context::new(
+            &buffer.content,
+            buffer.cursor.idx,
+            buffer.path.as_deref(),
+            &self.root,
+        );
+
+        let items = self
+            .completion_sources
+            .iter()
+            .flat_map(|source| source.complete(&context))
+            .collect();
+
+        let popup = Popup::new(&context, items);
+        let path_mode = popup.path_mode;
+
+        self.completion = Some(popup);
+
+        // language server results are added when they arrive
+        if !path_mode {
+            self.lsp.sync(&mut self.workspace.buffers);
+
+            let buffer = self.workspace.active();
+
+            if let Some(path) = buffer.path.clone() {
+                let position = Position::from_offset(&buffer.content, buffer.cursor.idx);
+
+                if let Some(client) = self.lsp.client(&path) {
+                    client.completion(&path, position);
+                }
+            }
+        }
+    }
+
+    // replaces the typed prefix with the completion
+    fn accept_completion(&mut self, start: usize, item: Completion) {
+        let buffer = self.workspace.active_mut();
+        let cursor = buffer.cursor.idx;
+        let range = item.replaces(start, cursor);
+
+        if item.snippet {
+            self.insert_snippet(range.start, &item.text);
+            return;
+        }
+
+        // a choice fills in every range of its stop
+        if item.source == "choices" {
+            buffer.fill_stop(&item.text);
+            return;
+        }
+
+        buffer.replace(range, &item.text);
+
+        // a completed directory goes straight on to its entries
+        if item.text.ends_with('/') {
+            self.update_completion(Some('/'));
+        }
+    }
+
+    fn handle_picker_key(&mut self, event: &KeyEvent) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+
+        match picker.handle_key(event) {
+            PickerEvent::Accept => {
+                let picker = self.picker.take().unwrap();
+                self.file_index = None;
+
+                if let Some(pick) = picker.into_selected() {
+                    self.pick(pick);
+                }
+            }
+            PickerEvent::Cancel => {
+                self.picker = None;
+                self.file_index = None;
+            }
+            PickerEvent::None => (),
+        }
+    }
+
+    fn pick(&mut self, pick: Pick) {
+        match pick {
+            Pick::File(path) => match self.workspace.open(&path) {
+                Ok(_) => self.explorer.focused = false,
+                Err(err) => self.message = Some(err.to_string()),
+            },
+            Pick::Command(id) => {
+                self.commands.record(id);
+                self.execute(id);
+            }
+            Pick::Location(location) => self.jump_to(&location),
+        }
+    }
+
+    pub fn execute(&mut self, id: &'static str) {
+        let Some(command) = self.commands.get(id) else {
+            return;
+        };
+
+        self.completion = None;
+
+        (command.action)(self);
+    }
+
+    fn open_command_palette(&mut self) {
+        let items = self
+            .commands
+            .ordered()
+            .into_iter()
+            .map(|command| PickerItem {
+                label: command.name.to_string(),
+                detail: command
+                    .keybindings
+                    .iter()
+                    .map(|binding| binding.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                value: Pick::Command(command.id),
+            })
+            .collect();
+
+        self.picker = Some(Picker::new("Command palette", items));
+    }
+
+    fn open_file_finder(&mut self) {
+        self.picker = Some(Picker::new("Open file", Vec::new()));
+        self.file_index = Some(finder::index_files(&self.root, self.waker.clone()));
+    }
+
+    fn handle_explorer_key(&mut self, event: &KeyEvent) {
+        match event.key_without_modifiers().as_ref() {
+            Key::Named(NamedKey::ArrowUp) => self.explorer.move_up(),
+            Key::Named(NamedKey::ArrowDown) => self.explorer.move_down(),
+            Key::Named(NamedKey::ArrowLeft) => self.explorer.collapse(),
+            Key::Named(NamedKey::ArrowRight) => self.explorer.expand(),
+            Key::Named(NamedKey::Enter) => self.activate_explorer_entry(),
+            Key::Named(NamedKey::Escape) => self.explorer.focused = false,
+            Key::Character("a") => {
+                self.input.clear();
+                self.mode = Mode::NewFile;
+            }
+            Key::Character("r") => {
+                if let Some(entry) = self.explorer.selected_entry() {
+                    self.input = entry.name.clone();
+                    self.mode = Mode::Rename(entry.path.clone());
+                }
+            }
+            Key::Character("d") | Key::Named(NamedKey::Delete) => {
+                if let Some(entry) = self.explorer.selected_entry() {
+                    self.mode = Mode::ConfirmDelete(entry.path.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_problems_key(&mut self, event: &KeyEvent) {
+        let len = self.diagnostics.all().len();
+
+        match event.key_without_modifiers().as_ref() {
+            Key::Named(NamedKey::ArrowUp) => self.problems.selection.move_up(),
+            Key::Named(NamedKey::ArrowDown) => self.problems.selection.move_down(len),
+            Key::Named(NamedKey::Enter) => self.open_problem(self.problems.selection.selected),
+            Key::Named(NamedKey::Escape) => self.problems.focused = false,
+            _ => (),
+        }
+    }
+
+    fn open_problem(&mut self, idx: usize) {
+        let Some((path, diagnostic)) = self.diagnostics.all().get(idx).cloned() else {
+            return;
+        };
+
+        let path = path.to_path_buf();
+        let range = diagnostic.range;
+
+        self.problems.selection.selected = idx;
+        self.problems.focused = false;
+
+        self.jump_to(&lsp::Location { path, range });
+    }
+
+    // the problem after the cursor, wrapping around to the first one
+    fn next_problem(&mut self) {
+        let all = self.diagnostics.all();
+
+        if all.is_empty() {
+            self.message = Some("No problems".to_string());
+            return;
+        }
+
+        let buffer = self.workspace.active();
+        let cursor = Position::from_offset(&buffer.content, buffer.cursor.idx);
+
+        let next = all
+            .iter()
+            .position(|(path, diagnostic)| {
+                let current = buffer.path.as_deref().map(|x| (x, cursor));
+                Some((*path, diagnostic.range.start)) > current
+            })
+            .unwrap_or(0);
+
+        self.open_problem(next);
+    }
+
+    fn activate_explorer_entry(&mut self) {
+        let Some(path) = self.explorer.activate() else {
+            return;
+        };
+
+        match self.workspace.open(&path) {
+            Ok(_) => self.explorer.focused = false,
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    fn handle_input_key(&mut self, event: &KeyEvent) {
+        match event.logical_key.as_ref() {
+            Key::Named(NamedKey::Enter) => {
+                let input = std::mem::take(&mut self.input);
+
+                match std::mem::replace(&mut self.mode, Mode::Normal) {
+                    Mode::Search => {
+                        self.search.query = input;
+                        self.start_search();
+                    }
+                    Mode::SaveAs { close } => {
+                        let path = self.root.join(input);
+
+                        match self.workspace.active_mut().save_as(&path) {
+                            Ok(()) if close => self.workspace.close(self.workspace.active),
+                            Ok(()) => (),
+                            Err(err) => self.message = Some(err.to_string()),
+                        }
+                    }
+                    Mode::NewFile => match self.explorer.create(&input) {
+                        Ok(path) if path.is_file() => {
+                            if let Err(err) = self.workspace.open(&path) {
+                                self.message = Some(err.to_string());
+                            }
+                        }
+                        Ok(_) => (),
+                        Err(err) => self.message = Some(err.to_string()),
+                    },
+                    Mode::Rename(from) => match self.explorer.rename(&from, &input) {
+                        Ok(to) => self.retarget_buffers(&from, &to),
+                        Err(err) => self.message = Some(err.to_string()),
+                    },
+                    Mode::RenameSymbol if !input.is_empty() => {
+                        self.with_client(|client, path, position| {
+                            client.rename(path, position, &input)
+                        })
+                    }
+                    _ => (),
+                }
+            }
+            Key::Named(NamedKey::Escape) => {
+                self.input.clear();
+                self.mode = Mode::Normal;
+            }
+            Key::Named(NamedKey::Backspace) => {
+                self.input.pop();
+            }
+            _ => {
+                if let Some(text) = &event.text {
+                    self.input.push_str(text);
+                }
+            }
+        }
+    }
+
+    fn handle_confirm_close_key(&mut self, event: &KeyEvent, idx: usize) {
+        match event.key_without_modifiers().as_ref() {
+            Key::Character("y") => {
+                self.mode = Mode::Normal;
+                self.workspace.active = idx;
+                self.save(true);
+            }
+            Key::Character("n") => {
+                self.mode = Mode::Normal;
+                self.workspace.close(idx);
+            }
+            Key::Named(NamedKey::Escape) => self.mode = Mode::Normal,
+            _ => (),
+        }
+    }
+
+    fn handle_confirm_delete_key(&mut self, event: &KeyEvent) {
+        match event.key_without_modifiers().as_ref() {
+            Key::Character("y") => {
+                if let Mode::ConfirmDelete(path) = std::mem::replace(&mut self.mode, Mode::Normal) {
+                    if let Err(err) = self.explorer.delete(&path) {
+                        self.message = Some(err.to_string());
+                    }
+                }
+            }
+            Key::Character("n") | Key::Named(NamedKey::Escape) => self.mode = Mode::Normal,
+            _ => (),
+        }
+    }
+
+    // keeps open buffers pointing at files that were renamed or moved with their directory
+    fn retarget_buffers(&mut self, from: &Path, to: &Path) {
+        for buffer in self.workspace.buffers.iter_mut() {
+            let Some(rest) = buffer
+                .path
+                .as_ref()
+                .and_then(|path| path.strip_prefix(from).ok())
+            else {
+                continue;
+            };
+
+            let path = to.join(rest);
+
+            buffer.name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            buffer.path = Some(path);
+        }
+    }
+
+    fn save(&mut self, close: bool) {
+        let buffer = self.workspace.active_mut();
+
+        if buffer.is_read_only() {
+            return;
+        }
+
+        if buffer.path.is_none() {
+            self.input.clear();
+            self.mode = Mode::SaveAs { close };
+            return;
+        }
+
+        let path = buffer.path.clone().unwrap();
+
+        match buffer.save() {
+            Ok(()) => {
+                self.lsp.did_save(&path);
+
+                if close {
+                    self.workspace.close(self.workspace.active);
+                }
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    // runs a request for the cursor position against the active buffer's language server
+    fn with_client(&mut self, request: impl FnOnce(&mut Client, &Path, Position)) {
+        // the server has to see the latest edits before asking about them
+        self.lsp.sync(&mut self.workspace.buffers);
+
+        let buffer = self.workspace.active();
+
+        let Some(path) = buffer.path.clone() else {
+            return;
+        };
+
+        let position = Position::from_offset(&buffer.content, buffer.cursor.idx);
+
+        match self.lsp.client(&path) {
+            Some(client) => request(client, &path, position),
+            None => self.message = Some("No language server for this file".to_string()),
+        }
+    }
+
+    fn handle_lsp_event(&mut self, event: lsp::Event) {
+        match event {
+            lsp::Event::Diagnostics => (),
+            lsp::Event::Hover(text) => {
+                self.message = Some(
+                    text.and_then(|x| x.lines().find(|x| !x.trim().is_empty()).map(String::from))
+                        .unwrap_or_else(|| "No hover information".to_string()),
+                )
+            }
+            lsp::Event::Definition(locations) if locations.len() == 1 => {
+                self.jump_to(&locations[0])
+            }
+            lsp::Event::Definition(locations) => self.pick_location("Definitions", locations),
+            lsp::Event::References(locations) => self.pick_location("References", locations),
+            lsp::Event::Rename(files) => {
+                for (path, edits) in &files {
+                    match self.workspace.load(path) {
+                        Ok(idx) => self.workspace.buffers[idx].apply_edits(edits),
+                        Err(err) => self.message = Some(err.to_string()),
+                    }
+                }
+
+                if self.message.is_none() {
+                    self.message = Some(format!("Renamed in {} files", files.len()));
+                }
+            }
+            lsp::Event::Completion(path, items) => {
+                let buffer = self.workspace.active();
+
+                // the popup was closed or belongs to another file by now
+                let Some(popup) = &mut self.completion else {
+                    return;
+                };
+
+                if popup.path.as_ref() != Some(&path) || buffer.path.as_ref() != Some(&path) {
+                    return;
+                }
+
+                let items = items
+                    .into_iter()
+                    .map(|item| Completion {
+                        detail: item.detail.unwrap_or_default(),
+                        documentation: item.documentation,
+                        start: item
+                            .edit
+                            .as_ref()
+                            .map(|edit| edit.range.start.to_offset(&buffer.content)),
+                        text: item.edit.map_or(item.insert_text, |edit| edit.new_text),
+                        snippet: item.snippet,
+                        label: item.label,
+                        source: "lsp",
+                    })
+                    .collect();
+
+                popup.extend(items);
+            }
+            lsp::Event::Error(error) => self.message = Some(error),
+        }
+    }
+
+    fn pick_location(&mut self, title: &str, locations: Vec<lsp::Location>) {
+        if locations.is_empty() {
+            self.message = Some(format!("No {} found", title.to_lowercase()));
+            return;
+        }
+
+        // files that aren't open are read once for the line previews
+        let mut files: HashMap<PathBuf, String> = HashMap::new();
+
+        let items = locations
+            .into_iter()
+            .map(|location| {
+                let content = match self.workspace.find(&location.path) {
+                    Some(idx) => self.workspace.buffers[idx].content.clone(),
+                    None => files
+                        .entry(location.path.clone())
+                        .or_insert_with(|| fs::read_to_string(&location.path).unwrap_or_default())
+                        .clone(),
+                };
+
+                let line = content
+                    .lines()
+                    .nth(location.range.start.line)
+                    .unwrap_or_default()
+                    .trim();
+
+                PickerItem {
+                    label: format!(
+                        "{}:{}",
+                        location
+                            .path
+                            .strip_prefix(&self.root)
+                            .unwrap_or(&location.path)
+                            .display(),
+                        location.range.start.line + 1
+                    ),
+                    detail: line.to_string(),
+                    value: Pick::Location(location),
+                }
+            })
+            .collect();
+
+        self.picker = Some(Picker::new(title, items));
+    }
+
+    fn jump_to(&mut self, location: &lsp::Location) {
+        match self.workspace.open(&location.path) {
+            Ok(_) => {
+                let buffer = self.workspace.active_mut();
+                let column = location.range.start.column(&buffer.content);
+                buffer
+                    .cursor
+                    .move_to(&buffer.content, location.range.start.line, column);
+                self.explorer.focused = false;
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    fn request_close(&mut self, idx: usize) {
+        if self.workspace.buffers[idx].is_dirty() {
+            self.mode = Mode::ConfirmClose(idx);
+        } else {
+            self.workspace.close(idx);
+        }
+    }
+
+    fn start_search(&mut self) {
+        self.search.start(self.waker.clone());
+
+        if let Some(error) = &self.search.error {
+            self.message = Some(format!("Search error: {}", error));
+            return;
+        }
+
+        let existing = self
+            .workspace
+            .buffers
+            .iter()
+            .position(|buffer| matches!(buffer.kind, BufferKind::SearchResults(_)));
+
+        match existing {
+            Some(idx) => self.workspace.active = idx,
+            None => {
+                let mut buffer = Buffer::new("search", String::new());
+                buffer.kind = BufferKind::SearchResults(Vec::new());
+                self.workspace.add(buffer);
+            }
+        }
+
+        let buffer = self.workspace.active_mut();
+        buffer.cursor = Cursor::new();
+        buffer.scroll = 0;
+
+        self.update_search_results();
+    }
+
+    fn update_search_results(&mut self) {
+        let (text, locations) = self.search.results_buffer();
+
+        let results = self
+            .workspace
+            .buffers
+            .iter_mut()
+            .find(|buffer| matches!(buffer.kind, BufferKind::SearchResults(_)));
+
+        if let Some(buffer) = results {
+            buffer.content = text;
+            buffer.kind = BufferKind::SearchResults(locations);
+        }
+    }
+
+    fn open_search_result(&mut self) {
+        let buffer = self.workspace.active();
+
+        let BufferKind::SearchResults(locations) = &buffer.kind else {
+            return;
+        };
+
+        let Some(Some(location)) = locations.get(buffer.cursor.cursor_y).cloned() else {
+            return;
+        };
+
+        match self.workspace.open(&location.path) {
+            Ok(_) => {
+                let buffer = self.workspace.active_mut();
+                buffer
+                    .cursor
+                    .move_to(&buffer.content, location.line, location.column);
+            }
+            Err(err) => self.message = Some(err.to_string()),
+        }
+    }
+
+    pub fn handle_mouse_move(&mut self, x: f32, y: f32) {
+        self.mouse = (x, y);
+        // tooltips follow the mouse
+        self.damage.invalidate();
+
+        // dragging over text moves the cursor end of the selection
+        if self.selecting {
+            let focused = self
+                .workspace
+                .layout
+                .viewports(self.area)
+                .into_iter()
+                .find(|(id, _)| *id == self.workspace.focused);
+
+            if let Some((_, rect)) = focused {
+                let idx = self.position_at(rect, x, y);
+                self.workspace.active_mut().set_cursor(idx);
+            }
+            return;
+        }
+
+        let Some(border) = self.drag else {
+            return;
+        };
+
+        let area = border.area;
+
+        let ratio = match border.direction {
+            Direction::Vertical => (x - area.left) / area.width,
+            Direction::Horizontal => (area.bottom + area.height - y) / area.height,
+        };
+
+        self.workspace.layout.set_ratio(border.split, ratio);
+    }
+
+    pub fn handle_mouse_input(&mut self, state: ElementState, button: MouseButton) {
+        if button != MouseButton::Left {
+            return;
+        }
+
+        self.typed = Instant::now();
+
+        self.damage.invalidate();
+
+        if state == ElementState::Released {
+            self.drag = None;
+
+            // a click without dragging leaves nothing selected
+            if std::mem::take(&mut self.selecting) {
+                let buffer = self.workspace.active_mut();
+
+                if buffer.selection().is_none() {
+                    buffer.anchor = None;
+                }
+            }
+            return;
+        }
+
+        self.completion = None;
+        self.workspace.active_mut().end_snippet();
+
+        let (x, y) = self.mouse;
+
+        let inside = |rect: &Rectangle, margin: f32| {
+            x >= rect.left - margin
+                && x <= rect.left + rect.width + margin
+                && y >= rect.bottom - margin
+                && y <= rect.bottom + rect.height + margin
+        };
+
+        if self.problems.visible && inside(&self.panel, 0.0) {
+            let top = self.panel.bottom + self.panel.height;
+            // the first row is the header
+            let row = ((top - y) / problems::ROW_HEIGHT) as usize;
+
+            if row > 0 && row - 1 + self.problems.selection.scroll < self.diagnostics.all().len() {
+                self.open_problem(row - 1 + self.problems.selection.scroll);
+            } else {
+                self.problems.focused = true;
+                self.explorer.focused = false;
+            }
+            return;
+        }
+
+        self.problems.focused = false;
+
+        if self.explorer.visible && inside(&self.sidebar, 0.0) {
+            let top = self.sidebar.bottom + self.sidebar.height;
+            let row = ((top - y) / explorer::ROW_HEIGHT) as usize + self.explorer.selection.scroll;
+
+            if row < self.explorer.entries.len() {
+                self.explorer.focused = true;
+                self.explorer.selection.selected = row;
+                self.activate_explorer_entry();
+            }
+            return;
+        }
+
+        self.explorer.focused = false;
+
+        let marker = self
+            .fold_markers
+            .iter()
+            .find(|(rect, _, _)| inside(rect, 2.0))
+            .map(|(_, pane, line)| (*pane, *line));
+
+        if let Some((pane, line)) = marker {
+            self.workspace.focus(pane);
+            self.workspace.active_mut().toggle_fold(line);
+            return;
+        }
+
+        self.drag = self
+            .workspace
+            .layout
+            .borders(self.area)
+            .into_iter()
+            .find(|border| inside(&border.rect, BORDER_GRAB));
+
+        if self.drag.is_some() {
+            return;
+        }
+
+        let clicked = self
+            .workspace
+            .layout
+            .viewports(self.area)
+            .into_iter()
+            .find(|(_, rect)| inside(rect, 0.0));
+
+        if let Some((id, rect)) = clicked {
+            self.workspace.focus(id);
+
+            let idx = self.position_at(rect, x, y);
+            let shift = self.modifiers.shift_key();
+            let buffer = self.workspace.active_mut();
+
+            // shift extends the selection from where the cursor was
+            if !shift || buffer.anchor.is_none() {
+                buffer.anchor = Some(if shift { buffer.cursor.idx } else { idx });
+            }
+
+            buffer.set_cursor(idx);
+            self.selecting = true;
+        }
+    }
+
+    // buffer position under a point of the focused pane, the inverse of how its text is laid
+    // out: rows from the top of the text, then the boundary between placed glyphs
+    fn position_at(&self, rect: Rectangle, x: f32, y: f32) -> usize {
+        let buffer = self.workspace.active();
+        let line_height = self.layout.line_height();
+
+        // the text is where the last frame drew it, which can still be easing towards the
+        // scrolled to line
+        let scrolled = self
+            .scrolled
+            .get(&self.workspace.focused)
+            .copied()
+            .unwrap_or_else(|| {
+                let top = buffer.folds.visible_line(buffer.scroll);
+                buffer.folds.row(top, 0).unwrap_or_default() as f32 * line_height
+            });
+
+        let text_top = rect.bottom + rect.height - PADDING;
+        let row = ((text_top - y + scrolled).max(0.0) / line_height) as usize;
+        let line = buffer.folds.line_at(row, 0);
+
+        let line_start = match line {
+            0 => Some(0),
+            line => buffer
+                .content
+                .match_indices('\n')
+                .nth(line - 1)
+                .map(|(idx, _)| idx + 1),
+        };
+
+        // below the last line is the end of the text
+        let Some(line_start) = line_start else {
+            return buffer.content.len();
+        };
+
+        let text = buffer.content[line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default();
+        line_start + self.layout.line(text).offset_at(x - rect.left - PADDING)
+    }
+
+    // scrolls the pane under the pointer by `rows`, fractions add up over events
+    pub fn handle_mouse_wheel(&mut self, rows: f32) {
+        self.wheel += rows;
+        self.damage.invalidate();
+
+        let rows = self.wheel.trunc();
+        self.wheel -= rows;
+
+        if rows == 0.0 {
+            return;
+        }
+
+        let (x, y) = self.mouse;
+
+        let hovered = self
+            .workspace
+            .layout
+            .viewports(self.area)
+            .into_iter()
+            .find(|(_, rect)| {
+                x >= rect.left
+                    && x <= rect.left + rect.width
+                    && y >= rect.bottom
+                    && y <= rect.bottom + rect.height
+            });
+
+        let Some((id, _)) = hovered else {
+            return;
+        };
+
+        if id == self.workspace.focused {
+            let buffer = self.workspace.active_mut();
+            buffer.scroll = buffer.scrolled(buffer.scroll, rows as isize);
+        } else if let Some(pane) = self.workspace.layout.pane_mut(id) {
+            let buffer = &self.workspace.buffers[pane.buffer];
+            pane.scroll = buffer.scrolled(pane.scroll, rows as isize);
+        }
+    }
+
+    // called once per event loop iteration to pick up background work, anything new that
+    // shows on screen invalidates it
+    pub fn poll(&mut self) {
+        let mut changed = false;
+
+        if self.search.poll() {
+            self.update_search_results();
+            changed = true;
+        }
+
+        changed |= self.explorer.poll();
+
+        changed |= self.diagnostics.poll();
+        self.problems.selection.clamp(self.diagnostics.all().len());
+
+        self.lsp.sync(&mut self.workspace.buffers);
+
+        for buffer in self.workspace.buffers.iter_mut() {
+            buffer.refresh_folds();
+            buffer.refresh_indentation();
+        }
+
+        for event in self.lsp.poll(&mut self.diagnostics) {
+            self.handle_lsp_event(event);
+            changed = true;
+        }
+
+        if let (Some(picker), Some(file_index)) = (&mut self.picker, &self.file_index) {
+            loop {
+                match file_index.try_recv() {
+                    Ok(batch) => {
+                        picker.extend(batch.into_iter().map(|path| {
+                            PickerItem {
+                                label: path
+                                    .strip_prefix(&self.root)
+                                    .unwrap_or(&path)
+                                    .display()
+                                    .to_string(),
+                                detail: String::new(),
+                                value: Pick::File(path),
+                            }
+                        }));
+                        changed = true;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    // every file has been found
+                    Err(TryRecvError::Disconnected) => {
+                        self.file_index = None;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if changed {
+            self.damage.invalidate();
+        }
+    }
+
+    // what the status bar says in place of its left segments, like a prompt or a message
+    pub fn status_message(&self) -> Option<String> {
+        match &self.mode {
+            Mode::Search => return Some(format!("Search: {}", self.input)),
+            Mode::SaveAs { .. } => return Some(format!("Save as: {}", self.input)),
+            Mode::ConfirmClose(idx) => {
+                return Some(format!(
+                    "{} has unsaved changes. Save? (y)es (n)o (esc) cancel",
+                    self.workspace.buffers[*idx].name
+                ))
+            }
+            Mode::NewFile => {
+                return Some(format!(
+                    "New file in {} (end with / for a directory): {}",
+                    self.explorer.target_dir().display(),
+                    self.input
+                ))
+            }
+            Mode::Rename(_) => return Some(format!("Rename to: {}", self.input)),
+            Mode::RenameSymbol => return Some(format!("Rename symbol to: {}", self.input)),
+            Mode::ConfirmDelete(path) => {
+                return Some(format!("Delete {}? (y)es (n)o", path.display()));
+            }
+            Mode::Normal => (),
+        }
+
+        self.message.clone()
+    }
+}
+
+// commands bound to keys in normal mode and listed in the command palette
+fn default_commands() -> Commands {
+    let mut commands = Commands::default();
+
+    commands.register(
+        "palette.open",
+        "Show all commands",
+        &["ctrl+shift+p"],
+        |editor| editor.open_command_palette(),
+    );
+    commands.register("file.open", "Go to file", &["ctrl+p"], |editor| {
+        editor.open_file_finder()
+    });
+    commands.register("file.new", "New file", &["ctrl+n"], |editor| {
+        editor.workspace.add(Buffer::new("untitled", String::new()));
+    });
+    commands.register("file.save", "Save", &["ctrl+s"], |editor| {
+        editor.save(false)
+    });
+    commands.register("buffer.close", "Close buffer", &["ctrl+w"], |editor| {
+        editor.request_close(editor.workspace.active)
+    });
+    commands.register("buffer.next", "Next buffer", &["ctrl+tab"], |editor| {
+        editor.workspace.next()
+    });
+    commands.register(
+        "buffer.prev",
+        "Previous buffer",
+        &["ctrl+shift+tab"],
+        |editor| editor.workspace.prev(),
+    );
+    commands.register("edit.undo", "Undo", &["ctrl+z"], |editor| {
+        editor.workspace.active_mut().undo()
+    });
+    commands.register("edit.redo", "Redo", &["ctrl+shift+z", "ctrl+y"], |editor| {
+        editor.workspace.active_mut().redo()
+    });
+    commands.register(
+        "search.project",
+        "Search in project",
+        &["ctrl+shift+f"],
+        |editor| {
+            editor.input = editor.search.query.clone();
+            editor.mode = Mode::Search;
+        },
+    );
+    commands.register(
+        "explorer.toggle",
+        "Toggle file explorer",
+        &["ctrl+b"],
+        |editor| {
+            editor.explorer.visible = !editor.explorer.visible;
+            editor.explorer.focused = editor.explorer.visible;
+            editor.problems.focused = false;
+        },
+    );
+    commands.register(
+        "explorer.focus",
+        "Focus file explorer",
+        &["ctrl+shift+e"],
+        |editor| {
+            editor.explorer.visible = true;
+            editor.explorer.focused = true;
+            editor.problems.focused = false;
+        },
+    );
+    commands.register(
+        "pane.split_vertical",
+        "Split pane right",
+        &["ctrl+\\"],
+        |editor| editor.workspace.split(Direction::Vertical),
+    );
+    commands.register(
+        "pane.split_horizontal",
+        "Split pane down",
+        &["ctrl+shift+\\"],
+        |editor| editor.workspace.split(Direction::Horizontal),
+    );
+    commands.register("pane.close", "Close pane", &["ctrl+shift+w"], |editor| {
+        editor.workspace.close_pane()
+    });
+    commands.register(
+        "pane.focus_left",
+        "Focus pane left",
+        &["ctrl+alt+left"],
+        |editor| editor.workspace.focus_towards(editor.area, -1.0, 0.0),
+    );
+    commands.register(
+        "pane.focus_right",
+        "Focus pane right",
+        &["ctrl+alt+right"],
+        |editor| editor.workspace.focus_towards(editor.area, 1.0, 0.0),
+    );
+    commands.register(
+        "pane.focus_up",
+        "Focus pane above",
+        &["ctrl+alt+up"],
+        |editor| editor.workspace.focus_towards(editor.area, 0.0, 1.0),
+    );
+    commands.register(
+        "pane.focus_down",
+        "Focus pane below",
+        &["ctrl+alt+down"],
+        |editor| editor.workspace.focus_towards(editor.area, 0.0, -1.0),
+    );
+    commands.register("lsp.hover", "Show hover", &["ctrl+k"], |editor| {
+        editor.with_client(|client, path, position| client.hover(path, position))
+    });
+    commands.register("lsp.definition", "Go to definition", &["f12"], |editor| {
+        editor.with_client(|client, path, position| client.definition(path, position))
+    });
+    commands.register(
+        "lsp.references",
+        "Find references",
+        &["shift+f12"],
+        |editor| editor.with_client(|client, path, position| client.references(path, position)),
+    );
+    commands.register("lsp.rename", "Rename symbol", &["f2"], |editor| {
+        if editor.workspace.active().path.is_some() {
+            editor.input.clear();
+            editor.mode = Mode::RenameSymbol;
+        }
+    });
+    commands.register(
+        "completion.trigger",
+        "Trigger completion",
+        &["ctrl+space"],
+        |editor| editor.open_completion(),
+    );
+    commands.register(
+        "problems.toggle",
+        "Toggle problems panel",
+        &["ctrl+shift+m"],
+        |editor| {
+            editor.problems.visible = !editor.problems.visible;
+            editor.problems.focused = editor.problems.visible;
+            editor.explorer.focused = false;
+        },
+    );
+    commands.register("problems.next", "Go to next problem", &["f8"], |editor| {
+        editor.next_problem()
+    });
+    commands.register("build.run", "Run build", &["ctrl+shift+b"], |editor| {
+        match BuildProvider::for_project(&editor.root, editor.waker.clone()) {
+            Some(build) => {
+                editor.diagnostics.register(Box::new(build));
+                editor.message = Some("Building...".to_string());
+            }
+            None => editor.message = Some("No build command for this project".to_string()),
+        }
+    });
+    commands.register("view.toggle_ligatures", "Toggle ligatures", &[], |editor| {
+        editor.layout.set_ligatures(!editor.layout.ligatures())
+    });
+    commands.register(
+        "view.toggle_subpixel",
+        "Toggle subpixel antialiasing",
+        &[],
+        |editor| editor.subpixel = !editor.subpixel,
+    );
+    commands.register(
+        "view.toggle_frame_stats",
+        "Toggle frame stats",
+        &[],
+        |editor| editor.show_stats = !editor.show_stats,
+    );
+    commands.register("fold.fold", "Fold region", &["ctrl+shift+["], |editor| {
+        editor.workspace.active_mut().fold()
+    });
+    commands.register(
+        "fold.unfold",
+        "Unfold region",
+        &["ctrl+shift+]"],
+        |editor| editor.workspace.active_mut().unfold(),
+    );
+    commands.register(
+        "fold.fold_all",
+        "Fold all regions",
+        &["ctrl+alt+["],
+        |editor| editor.workspace.active_mut().fold_all(),
+    );
+    commands.register(
+        "fold.unfold_all",
+        "Unfold all regions",
+        &["ctrl+alt+]"],
+        |editor| editor.workspace.active_mut().unfold_all(),
+    );
+
+    commands
+}
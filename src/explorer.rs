@@ -0,0 +1,346 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+};
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{selection::Selection, waker::Waker};
+
+pub const ROW_HEIGHT: f32 = 24.0;
+
+// names the tree leaves out
+fn hidden(name: &OsStr) -> bool {
+    name == ".git"
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+pub struct Explorer {
+    pub root: PathBuf,
+    pub visible: bool,
+    pub focused: bool,
+    pub entries: Vec<Entry>, // visible rows of the tree, in display order
+    pub selection: Selection,
+    expanded: HashSet<PathBuf>,
+    events: Receiver<notify::Result<notify::Event>>,
+    // dropping the watcher stops the notifications
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl Explorer {
+    pub fn new(root: PathBuf, waker: Waker) -> Self {
+        let (sender, events) = mpsc::channel();
+
+        let watcher = notify::recommended_watcher(move |event| {
+            if sender.send(event).is_ok() {
+                waker.wake();
+            }
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        })
+        .ok();
+
+        let mut explorer = Explorer {
+            root,
+            visible: false,
+            focused: false,
+            entries: Vec::new(),
+            selection: Selection::default(),
+            expanded: HashSet::new(),
+            events,
+            _watcher: watcher,
+        };
+
+        explorer.refresh();
+        explorer
+    }
+
+    // rebuilds the rows from disk, keeping the selection on the same path
+    pub fn refresh(&mut self) {
+        let selected = self.selected_entry().map(|entry| entry.path.clone());
+
+        self.entries.clear();
+
+        let root = self.root.clone();
+        self.read_dir(&root, 0);
+
+        // forget directories that no longer exist
+        self.expanded.retain(|path| path.is_dir());
+
+        if let Some(selected) = selected {
+            if let Some(idx) = self.entries.iter().position(|x| x.path == selected) {
+                self.selection.selected = idx;
+            }
+        }
+
+        self.selection.clamp(self.entries.len());
+    }
+
+    fn read_dir(&mut self, dir: &Path, depth: usize) {
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+
+        let mut children: Vec<(PathBuf, bool)> = read_dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !hidden(&entry.file_name()))
+            .map(|entry| (entry.path(), entry.path().is_dir()))
+            .collect();
+
+        // directories first, then alphabetically
+        children.sort_by(|(a, a_dir), (b, b_dir)| b_dir.cmp(a_dir).then_with(|| a.cmp(b)));
+
+        for (path, is_dir) in children {
+            let expanded = is_dir && self.expanded.contains(&path);
+
+            self.entries.push(Entry {
+                name: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: path.clone(),
+                depth,
+                is_dir,
+                expanded,
+            });
+
+            if expanded {
+                self.read_dir(&path, depth + 1);
+            }
+        }
+    }
+
+    // picks up changes made outside the editor, returns true if the tree changed. everything
+    // that came in since the last poll is taken in one refresh
+    pub fn poll(&mut self) -> bool {
+        let events: Vec<_> = self.events.try_iter().collect();
+        let changed = events
+            .iter()
+            .any(|event| event.as_ref().is_ok_and(|x| self.shows_change(x)));
+
+        if changed {
+            self.refresh();
+        }
+
+        changed
+    }
+
+    // the watch covers the whole root, only changes to a directory the tree lists matter, not to
+    // collapsed ones like target/ or to anything under a hidden one like .git/
+    fn shows_change(&self, event: &notify::Event) -> bool {
+        event.paths.iter().any(|path| {
+            let Some(parent) = path.parent() else {
+                return false;
+            };
+            let Ok(relative) = path.strip_prefix(&self.root) else {
+                return false;
+            };
+
+            !relative.iter().any(hidden) && (parent == self.root || self.expanded.contains(parent))
+        })
+    }
+
+    pub fn selected_entry(&self) -> Option<&Entry> {
+        self.entries.get(self.selection.selected)
+    }
+
+    pub fn move_up(&mut self) {
+        self.selection.move_up();
+    }
+
+    pub fn move_down(&mut self) {
+        self.selection.move_down(self.entries.len());
+    }
+
+    pub fn expand(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+
+        if entry.is_dir && !entry.expanded {
+            let path = entry.path.clone();
+            self.expanded.insert(path);
+            self.refresh();
+        }
+    }
+
+    // collapses the selected directory, or jumps to the parent of the selection
+    pub fn collapse(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            return;
+        };
+
+        if entry.is_dir && entry.expanded {
+            let path = entry.path.clone();
+            self.expanded.remove(&path);
+            self.refresh();
+            return;
+        }
+
+        let parent = entry.path.parent().map(Path::to_path_buf);
+
+        if let Some(idx) = self
+            .entries
+            .iter()
+            .position(|x| Some(&x.path) == parent.as_ref())
+        {
+            self.selection.selected = idx;
+        }
+    }
+
+    // expands or collapses a directory, returns the path if the selection is a file
+    pub fn activate(&mut self) -> Option<PathBuf> {
+        let entry = self.selected_entry()?;
+
+        if !entry.is_dir {
+            return Some(entry.path.clone());
+        }
+
+        if entry.expanded {
+            self.collapse();
+        } else {
+            self.expand();
+        }
+
+        None
+    }
+
+    // directory new entries are created in, next to the selection
+    pub fn target_dir(&self) -> PathBuf {
+        match self.selected_entry() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry
+                .path
+                .parent()
+                .map_or(self.root.clone(), Path::to_path_buf),
+            None => self.root.clone(),
+        }
+    }
+
+    // a trailing slash creates a directory
+    pub fn create(&mut self, name: &str) -> io::Result<PathBuf> {
+        let dir = self.target_dir();
+        let path = dir.join(name.trim_end_matches('/'));
+
+        if name.ends_with('/') {
+            fs::create_dir_all(&path)?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+        }
+
+        self.expanded.insert(dir);
+        self.refresh();
+        self.select(&path);
+
+        Ok(path)
+    }
+
+    pub fn rename(&mut self, from: &Path, name: &str) -> io::Result<PathBuf> {
+        let to = from.with_file_name(name);
+
+        fs::rename(from, &to)?;
+
+        if self.expanded.remove(from) {
+            self.expanded.insert(to.clone());
+        }
+
+        self.refresh();
+        self.select(&to);
+
+        Ok(to)
+    }
+
+    pub fn delete(&mut self, path: &Path) -> io::Result<()> {
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+
+        self.refresh();
+
+        Ok(())
+    }
+
+    fn select(&mut self, path: &Path) {
+        if let Some(idx) = self.entries.iter().position(|x| x.path == path) {
+            self.selection.selected = idx;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use notify::{event::CreateKind, Event, EventKind};
+
+    use super::*;
+    use crate::utils::temp::TempDir;
+
+    fn names(explorer: &Explorer) -> Vec<(usize, &str)> {
+        explorer
+            .entries
+            .iter()
+            .map(|x| (x.depth, x.name.as_str()))
+            .collect()
+    }
+
+    fn created(path: PathBuf) -> Event {
+        Event::new(EventKind::Create(CreateKind::Any)).add_path(path)
+    }
+
+    #[test]
+    fn directories_come_first_and_open_in_place() {
+        let dir = TempDir::new("explorer");
+        dir.write("b.txt", "");
+        dir.write("a/c.txt", "");
+        dir.write(".git/HEAD", "");
+
+        let mut explorer = Explorer::new(dir.path().to_path_buf(), Waker::default());
+        assert_eq!(names(&explorer), [(0, "a"), (0, "b.txt")]);
+
+        explorer.expand();
+        assert_eq!(names(&explorer), [(0, "a"), (1, "c.txt"), (0, "b.txt")]);
+
+        // collapsing from inside goes to the directory first
+        explorer.move_down();
+        explorer.collapse();
+        explorer.collapse();
+        assert_eq!(names(&explorer), [(0, "a"), (0, "b.txt")]);
+    }
+
+    #[test]
+    fn only_changes_to_listed_directories_refresh() {
+        let dir = TempDir::new("explorer");
+        dir.write("a/b/c.txt", "");
+        dir.write("target/debug/rite", "");
+
+        let mut explorer = Explorer::new(dir.path().to_path_buf(), Waker::default());
+        explorer.expand();
+
+        let root = dir.path();
+        assert!(explorer.shows_change(&created(root.join("d.txt"))));
+        assert!(explorer.shows_change(&created(root.join("a/d.txt"))));
+        assert!(!explorer.shows_change(&created(root.join("a/b/d.txt"))));
+        assert!(!explorer.shows_change(&created(root.join("target/debug/d"))));
+        assert!(!explorer.shows_change(&created(root.join(".git/index"))));
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use ignore::WalkBuilder;
+
+use crate::waker::Waker;
+
+const BATCH_SIZE: usize = 512;
+
+// walks the project in the background, honoring .gitignore, and streams the files found in batches
+pub fn index_files(root: &Path, waker: Waker) -> Receiver<Vec<PathBuf>> {
+    let (sender, receiver) = mpsc::channel();
+    let root = root.to_path_buf();
+
+    thread::spawn(move || {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+        for entry in WalkBuilder::new(&root).build().flatten() {
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                continue;
+            }
+
+            batch.push(entry.into_path());
+
+            if batch.len() == BATCH_SIZE {
+                if sender.send(std::mem::take(&mut batch)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+        }
+
+        let _ = sender.send(batch);
+        // the last batch and the closed channel are picked up together
+        drop(sender);
+        waker.wake();
+    });
+
+    receiver
+}
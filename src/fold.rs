@@ -0,0 +1,435 @@
+use std::{iter::Peekable, ops::Range, path::Path, str::Chars};
+
+// languages whose structure follows their brackets, the rest fold by indentation
+const BRACKET_LANGUAGES: &[&str] = &[
+    "rs", "js", "jsx", "mjs", "ts", "tsx", "c", "h", "cpp", "hpp", "cc", "java", "go", "cs",
+    "json", "css", "scss", "swift", "kt", "php", "zig",
+];
+
+// languages where `'` starts a char literal, or a lifetime in rust, instead of a string
+const CHAR_LITERAL_LANGUAGES: &[&str] = &[
+    "rs", "c", "h", "cpp", "hpp", "cc", "java", "go", "cs", "kt", "zig",
+];
+
+// a region that can be folded, `start` stays visible and the lines after it up to `end` are
+// hidden behind a placeholder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Fold {
+    // what's shown at the end of the start line while folded
+    pub fn placeholder(&self) -> String {
+        let lines = self.end - self.start;
+
+        if lines == 1 {
+            " ... 1 line".to_string()
+        } else {
+            format!(" ... {} lines", lines)
+        }
+    }
+
+    fn hides(&self, line: usize) -> bool {
+        self.start < line && line <= self.end
+    }
+}
+
+// moves past the closing quote of a string, counting the lines it spans
+fn skip_string(chars: &mut Peekable<Chars>, quote: char, line: &mut usize) {
+    while let Some(char) = chars.next() {
+        match char {
+            // escaped line breaks still end a line
+            '\\' => *line += usize::from(chars.next() == Some('\n')),
+            '\n' => *line += 1,
+            char if char == quote => break,
+            _ => (),
+        }
+    }
+}
+
+// blocks between brackets spanning lines, the line with the closing bracket stays visible
+pub fn brackets(content: &str, char_literals: bool) -> Vec<Fold> {
+    let mut regions: Vec<Fold> = Vec::new();
+    let mut stack: Vec<(char, usize)> = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 0;
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\n' => line += 1,
+            // a char literal is one char or an escape, anything else like `'a` is a lifetime
+            '\'' if char_literals => {
+                let mut ahead = chars.clone();
+                let literal = match ahead.next() {
+                    Some('\\') => true,
+                    Some(_) => ahead.next() == Some('\''),
+                    None => false,
+                };
+
+                if literal {
+                    skip_string(&mut chars, '\'', &mut line);
+                }
+            }
+            // brackets in strings and comments don't count
+            '"' | '\'' | '`' => skip_string(&mut chars, char, &mut line),
+            '/' if chars.peek() == Some(&'/') => {
+                for char in chars.by_ref() {
+                    if char == '\n' {
+                        line += 1;
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+
+                for char in chars.by_ref() {
+                    if char == '\n' {
+                        line += 1;
+                    }
+                    if last == '*' && char == '/' {
+                        break;
+                    }
+                    last = char;
+                }
+            }
+            '{' | '[' | '(' => stack.push((char, line)),
+            '}' | ']' | ')' => {
+                let open = match char {
+                    '}' => '{',
+                    ']' => '[',
+                    _ => '(',
+                };
+
+                if let Some(idx) = stack.iter().rposition(|(x, _)| *x == open) {
+                    let (_, start) = stack[idx];
+                    stack.truncate(idx);
+
+                    if line > start + 1 {
+                        regions.push(Fold {
+                            start,
+                            end: line - 1,
+                        });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    dedup(regions)
+}
+
+// lines followed by more indented ones, blank lines in between belong to the block
+pub fn indentation(content: &str) -> Vec<Fold> {
+    let mut regions = Vec::new();
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+
+    for (idx, line) in content.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let indent = line
+            .chars()
+            .take_while(|x| x.is_whitespace())
+            .map(|x| if x == '\t' { 4 } else { 1 })
+            .sum::<usize>();
+
+        while let Some(&(top, start)) = stack.last() {
+            if top < indent {
+                break;
+            }
+
+            stack.pop();
+
+            if last > start {
+                regions.push(Fold { start, end: last });
+            }
+        }
+
+        stack.push((indent, idx));
+        last = idx;
+    }
+
+    for (_, start) in stack {
+        if last > start {
+            regions.push(Fold { start, end: last });
+        }
+    }
+
+    dedup(regions)
+}
+
+// one region per start line, the largest
+fn dedup(mut regions: Vec<Fold>) -> Vec<Fold> {
+    regions.sort_by_key(|x| (x.start, std::cmp::Reverse(x.end)));
+    regions.dedup_by_key(|x| x.start);
+    regions
+}
+
+pub fn regions(content: &str, path: Option<&Path>) -> Vec<Fold> {
+    let extension = path
+        .and_then(|x| x.extension())
+        .and_then(|x| x.to_str())
+        .unwrap_or_default();
+
+    if BRACKET_LANGUAGES.contains(&extension) {
+        brackets(content, CHAR_LITERAL_LANGUAGES.contains(&extension))
+    } else {
+        indentation(content)
+    }
+}
+
+// the foldable regions of a buffer and which of them are folded
+#[derive(Debug, Default)]
+pub struct Folds {
+    pub regions: Vec<Fold>, // sorted by start line
+    pub folded: Vec<Fold>,  // sorted by start line, folds inside folds are kept
+    version: Option<i32>,   // buffer version the regions were found in
+}
+
+impl Folds {
+    pub fn refresh(&mut self, content: &str, path: Option<&Path>, version: i32) {
+        if self.version == Some(version) {
+            return;
+        }
+
+        self.regions = regions(content, path);
+        self.version = Some(version);
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.folded.iter().any(|x| x.hides(line))
+    }
+
+    // the outermost fold starting at the line, if the line itself is visible
+    pub fn folded_at(&self, line: usize) -> Option<Fold> {
+        if self.is_hidden(line) {
+            return None;
+        }
+
+        self.folded
+            .iter()
+            .filter(|x| x.start == line)
+            .max_by_key(|x| x.end)
+            .copied()
+    }
+
+    pub fn region_at(&self, line: usize) -> Option<Fold> {
+        self.regions.iter().find(|x| x.start == line).copied()
+    }
+
+    // hidden lines as sorted ranges that don't overlap
+    fn hidden(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+
+        for fold in &self.folded {
+            match ranges.last_mut() {
+                Some(last) if fold.start < last.end => last.end = last.end.max(fold.end + 1),
+                _ => ranges.push(fold.start + 1..fold.end + 1),
+            }
+        }
+
+        ranges
+    }
+
+    // the line itself, or the start of the outermost fold hiding it
+    pub fn visible_line(&self, line: usize) -> usize {
+        self.folded
+            .iter()
+            .filter(|x| x.hides(line))
+            .map(|x| x.start)
+            .min()
+            .unwrap_or(line)
+    }
+
+    // row the line is drawn on when `from` is the first line shown, none if hidden or above
+    pub fn row(&self, line: usize, from: usize) -> Option<usize> {
+        if line < from || self.is_hidden(line) {
+            return None;
+        }
+
+        let hidden: usize = self
+            .hidden()
+            .iter()
+            .map(|x| x.end.min(line).saturating_sub(x.start.max(from)))
+            .sum();
+
+        Some(line - from - hidden)
+    }
+
+    // line drawn on a row, the inverse of `row`
+    pub fn line_at(&self, row: usize, from: usize) -> usize {
+        let mut line = from;
+        let mut row = row;
+
+        for range in self.hidden() {
+            if range.end <= line {
+                continue;
+            }
+
+            let visible = range.start.saturating_sub(line);
+
+            if row < visible {
+                break;
+            }
+
+            row -= visible;
+            line = range.end;
+        }
+
+        line + row
+    }
+
+    // the first line shown `rows` rows above the line
+    pub fn back(&self, line: usize, rows: usize) -> usize {
+        let mut line = self.visible_line(line);
+
+        for _ in 0..rows {
+            if line == 0 {
+                break;
+            }
+            line = self.visible_line(line - 1);
+        }
+
+        line
+    }
+
+    // the line shown `rows` rows below the line, no further than `last`
+    pub fn forward(&self, line: usize, rows: usize, last: usize) -> usize {
+        let mut line = self.visible_line(line);
+
+        for _ in 0..rows {
+            let next = self.folded_at(line).map_or(line + 1, |x| x.end + 1);
+
+            if next > last {
+                break;
+            }
+
+            line = next;
+        }
+
+        line
+    }
+
+    pub fn fold(&mut self, fold: Fold) {
+        if !self.folded.contains(&fold) {
+            self.folded.push(fold);
+            self.folded.sort_by_key(|x| (x.start, x.end));
+        }
+    }
+
+    // opens the folds hiding the line
+    pub fn reveal(&mut self, line: usize) {
+        self.folded.retain(|x| !x.hides(line));
+    }
+
+    // keeps folded lines in place as lines `start..=end` are replaced by `end - start + 1 + delta`
+    pub fn edit(&mut self, start: usize, end: usize, delta: isize) {
+        self.folded.retain_mut(|fold| {
+            if fold.end < start {
+                return true;
+            }
+
+            if fold.start > end {
+                fold.start = fold.start.saturating_add_signed(delta);
+                fold.end = fold.end.saturating_add_signed(delta);
+                return true;
+            }
+
+            // typing on the start line keeps it folded, anything else opens it
+            start == end && start == fold.start && delta == 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(start: usize, end: usize) -> Fold {
+        Fold { start, end }
+    }
+
+    // with every fold in `folded` folded
+    fn folds(folded: &[Fold]) -> Folds {
+        let mut folds = Folds::default();
+        folded.iter().for_each(|x| folds.fold(*x));
+        folds
+    }
+
+    #[test]
+    fn brackets_in_strings_comments_and_char_literals_dont_count() {
+        let rust = "fn f<'a>(x: &'a str) -> char {\n    let s = \"}\";\n    // }\n    '{'\n}\n";
+
+        assert_eq!(brackets(rust, true), [fold(0, 3)]);
+
+        let js = "let a = {\n  b: '}',\n  c: `\n}`,\n  /* } */\n};\n";
+
+        assert_eq!(brackets(js, false), [fold(0, 4)]);
+    }
+
+    #[test]
+    fn nested_brackets_fold_separately() {
+        let content = "a(\n  [\n    1,\n  ],\n  {\n  }\n)\n";
+
+        assert_eq!(brackets(content, false), [fold(0, 5), fold(1, 2)]);
+    }
+
+    #[test]
+    fn indentation_folds_include_blank_lines_inside() {
+        let content = "a:\n  b:\n    c\n\n    d\n  e\nf\n";
+
+        assert_eq!(indentation(content), [fold(0, 5), fold(1, 4)]);
+    }
+
+    #[test]
+    fn rows_and_lines_round_trip() {
+        let folds = folds(&[fold(2, 4), fold(3, 4), fold(8, 9)]);
+        let visible = [0, 1, 2, 5, 6, 7, 8, 10, 11];
+
+        for (row, line) in visible.iter().enumerate() {
+            assert_eq!(folds.row(*line, 0), Some(row));
+            assert_eq!(folds.line_at(row, 0), *line);
+        }
+
+        assert_eq!(folds.row(3, 0), None);
+        assert_eq!(folds.row(1, 2), None);
+        assert_eq!(folds.row(6, 2), Some(2));
+        assert_eq!(folds.line_at(2, 2), 6);
+    }
+
+    #[test]
+    fn back_and_forward_step_over_folds() {
+        let folds = folds(&[fold(2, 4)]);
+
+        assert_eq!(folds.forward(0, 3, 10), 5);
+        assert_eq!(folds.forward(3, 1, 10), 5);
+        assert_eq!(folds.forward(8, 5, 10), 10);
+        assert_eq!(folds.back(6, 2), 2);
+        assert_eq!(folds.back(4, 1), 1);
+        assert_eq!(folds.back(1, 5), 0);
+    }
+
+    #[test]
+    fn edits_move_or_open_folds() {
+        let mut folds = folds(&[fold(2, 4), fold(8, 9)]);
+
+        // two lines inserted above move both folds down
+        folds.edit(0, 0, 2);
+        assert_eq!(folds.folded, [fold(4, 6), fold(10, 11)]);
+
+        // typing on the start line keeps it folded
+        folds.edit(4, 4, 0);
+        assert_eq!(folds.folded, [fold(4, 6), fold(10, 11)]);
+
+        // a line break on it opens it
+        folds.edit(4, 4, 1);
+        assert_eq!(folds.folded, [fold(11, 12)]);
+    }
+}
@@ -1,114 +1,423 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
 
-use ab_glyph::{point, Font, FontRef, ScaleFont};
-use glium::{glutin::surface::WindowSurface, Display};
-use image::Rgba;
+use ab_glyph::{point, Font, FontRef, GlyphId, PxScale, ScaleFont};
+use image::{imageops::FilterType, GenericImage, Rgba, RgbaImage};
+use rustybuzz::ttf_parser::{self, colr, RasterImageFormat, RgbaColor};
+
+pub const FONT: &[u8] = include_bytes!("./assets/FiraCode-Regular.ttf");
+
+// glyphs are drawn onto a page of this size as they're first used
+const GLYPH_PAGE_SIZE: u32 = 1024;
+
+// glyphs are rasterized at this many horizontal offsets within a pixel
+pub const SUBPIXEL_STEPS: u8 = 4;
+
+// spreads each subpixel's coverage over its neighbours so colored fringes stay faint
+const LCD_FILTER: [f32; 5] = [1.0 / 9.0, 2.0 / 9.0, 3.0 / 9.0, 2.0 / 9.0, 1.0 / 9.0];
+
+// emoji are drawn on their own page as they're first used
+const EMOJI_PAGE_SIZE: u32 = 1024;
+
+// where color emoji fonts are usually installed, RITE_EMOJI_FONT overrides them
+const EMOJI_FONTS: &[&str] = &[
+    "/usr/share/fonts/truetype/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto/NotoColorEmoji.ttf",
+    "/usr/share/fonts/google-noto-emoji/NotoColorEmoji.ttf",
+    "/usr/share/fonts/noto-emoji/NotoColorEmoji.ttf",
+    "/System/Library/Fonts/Apple Color Emoji.ttc",
+    "C:\\Windows\\Fonts\\seguiemj.ttf",
+];
 
 #[derive(Copy, Clone, Debug)]
 pub struct Character {
-    pub id: u32,
+    pub tex_coords: [f32; 4], // left, top, right and bottom on its page, in fractions of the page
     pub width: f32,
     pub height: f32,
-    pub advance: f32,
     pub offset_top: f32,
     pub offset_left: f32,
 }
 
+// glyph images packed into an atlas, filled in as shaping asks for them. the backends upload it to
+// wherever they draw from
 #[derive(Debug)]
 pub struct BitmapFont {
-    pub texture: glium::Texture2d,
-    pub char: HashMap<char, Character>,
+    pub image: Mutex<RgbaImage>,
+    pub version: AtomicUsize, // bumped whenever a glyph is added, so backends know to upload
+    pub emoji: Option<EmojiPage>,
     pub ascent: f32,
     pub descent: f32,
+    font: FontRef<'static>,
+    scale: f32,
+    // by glyph id and subpixel step, ligatures have no char of their own
+    glyphs: Mutex<HashMap<(u16, u8), Option<Character>>>,
+    next: Mutex<(u32, u32, u32)>, // where the next glyph goes and the height of its row
 }
 
 impl BitmapFont {
-    pub fn new(display: &Display<WindowSurface>) -> Self {
+    // `emoji` is the color emoji font, from `emoji_font` or none to draw without one
+    pub fn new(emoji: Option<&'static [u8]>) -> Self {
         let scale = 24.0;
 
-        let font =
-            FontRef::try_from_slice(include_bytes!("./assets/FiraCode-Regular.ttf")).unwrap();
+        let font = FontRef::try_from_slice(FONT).unwrap();
+        let ascent = font.as_scaled(scale).ascent();
+        let descent = font.as_scaled(scale).descent();
 
-        let characters = "!\"#$%&'()*+,-./(){};+,0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+        Self {
+            image: Mutex::new(RgbaImage::new(GLYPH_PAGE_SIZE, GLYPH_PAGE_SIZE)),
+            version: AtomicUsize::new(0),
+            emoji: emoji.map(|data| EmojiPage::new(data, ascent, descent)),
+            ascent,
+            descent,
+            font,
+            scale,
+            glyphs: Mutex::default(),
+            next: Mutex::new((0, 0, 0)),
+        }
+    }
 
-        let width: u32 = characters.chars().fold(0, |acc, c| {
-            acc + font
-                .outline_glyph(font.glyph_id(c).with_scale(scale))
-                .unwrap()
-                .px_bounds()
-                .width() as u32
-        });
+    // the glyph drawn `step` subpixel offsets right of the pen, none if it has no outline or the
+    // page is full
+    pub fn glyph(&self, id: u16, step: u8) -> Option<Character> {
+        if let Some(char) = self.glyphs.lock().unwrap().get(&(id, step)) {
+            return *char;
+        }
 
-        let height: u32 = characters.chars().fold(0, |acc, c| {
-            acc.max(
-                font.outline_glyph(font.glyph_id(c).with_scale(scale))
-                    .unwrap()
-                    .px_bounds()
-                    .height() as u32,
-            )
-        });
+        let char = rasterize(&self.font, self.scale, id, step).and_then(|tile| self.place(tile));
+        self.glyphs.lock().unwrap().insert((id, step), char);
 
-        let mut image = image::RgbaImage::new(width, height);
-
-        let mut x_offset = 0;
-
-        let mut map: HashMap<char, Character> = HashMap::new();
-
-        for char in characters.chars() {
-            let glyph = font
-                .glyph_id(char)
-                .with_scale_and_position(scale, point(0.0, 0.0));
-
-            if let Some(outline) = font.outline_glyph(glyph.clone()) {
-                let outline_bounds = outline.px_bounds();
-
-                outline.draw(|x: u32, y, c: f32| {
-                    let clamp = c.max(0.0).min(1.0);
-
-                    let new_x: u32 = x;
-                    let new_y: u32 = y;
-
-                    if new_x > height || new_y > height {
-                        return;
-                    }
-
-                    image.put_pixel(
-                        new_x + x_offset,
-                        new_y,
-                        Rgba::from([
-                            (clamp * 255.0) as u8,
-                            (clamp * 255.0) as u8,
-                            (clamp * 255.0) as u8,
-                            (clamp * 255.0) as u8,
-                        ]),
-                    );
-                });
-
-                map.insert(
-                    char,
-                    Character {
-                        id: x_offset,
-                        width: outline_bounds.width(),
-                        height: outline_bounds.height(),
-                        advance: font.as_scaled(scale).h_advance(glyph.id),
-                        offset_top: outline_bounds.max.y,
-                        offset_left: outline_bounds.min.x,
-                    },
-                );
-
-                x_offset += outline_bounds.width() as u32;
-            }
+        char
+    }
+
+    // rows of glyphs, the next row starts below the tallest glyph once one is full
+    fn place(&self, tile: Tile) -> Option<Character> {
+        let mut next = self.next.lock().unwrap();
+        let (mut x, mut y, mut row_height) = *next;
+        let (width, height) = tile.image.dimensions();
+
+        if x + width > GLYPH_PAGE_SIZE {
+            x = 0;
+            y += row_height + 1;
+            row_height = 0;
         }
 
-        let image_dimensions = image.dimensions();
+        if y + height > GLYPH_PAGE_SIZE {
+            return None;
+        }
 
-        let image = glium::texture::RawImage2d::from_raw_rgba(image.into_raw(), image_dimensions);
+        let mut page = self.image.lock().unwrap();
+        page.copy_from(&tile.image, x, y).unwrap();
+        self.version.fetch_add(1, Ordering::Relaxed);
 
-        Self {
-            texture: glium::texture::Texture2d::new(display, image).unwrap(),
-            char: map,
-            ascent: font.as_scaled(scale).ascent(),
-            descent: font.as_scaled(scale).descent(),
+        *next = (x + width + 1, y, row_height.max(height));
+
+        Some(Character {
+            tex_coords: tex_coords(&page, x, y, &tile.image),
+            width: width as f32,
+            height: height as f32,
+            offset_top: tile.offset_top,
+            offset_left: tile.offset_left,
+        })
+    }
+}
+
+// where `tile` placed at (x, y) is on `page`, in the fractions of the page texture coordinates use
+fn tex_coords(page: &RgbaImage, x: u32, y: u32, tile: &RgbaImage) -> [f32; 4] {
+    let (width, height) = (page.width() as f32, page.height() as f32);
+
+    [
+        x as f32 / width,
+        y as f32 / height,
+        (x + tile.width()) as f32 / width,
+        (y + tile.height()) as f32 / height,
+    ]
+}
+
+// a rasterized glyph and where it goes relative to the pen on the baseline
+struct Tile {
+    image: RgbaImage,
+    offset_top: f32,
+    offset_left: f32,
+}
+
+// a glyph drawn `step / SUBPIXEL_STEPS` of a pixel right of the pen. it's rasterized at three
+// times the horizontal resolution, red, green and blue get the filtered coverage of their third
+// of each pixel for subpixel antialiasing and alpha the whole pixel's coverage for grayscale
+fn rasterize(font: &FontRef, scale: f32, id: u16, step: u8) -> Option<Tile> {
+    let offset = step as f32 / SUBPIXEL_STEPS as f32;
+
+    let outline = font.outline_glyph(GlyphId(id).with_scale_and_position(
+        PxScale {
+            x: scale * 3.0,
+            y: scale,
+        },
+        point(offset * 3.0, 0.0),
+    ))?;
+
+    let bounds = outline.px_bounds();
+    let (width, height) = (bounds.width() as i32, bounds.height() as i32);
+
+    let mut coverage = vec![0.0; (width * height) as usize];
+
+    outline.draw(|x, y, c| {
+        if (x as i32) < width && (y as i32) < height {
+            coverage[(y as i32 * width + x as i32) as usize] = c.clamp(0.0, 1.0);
+        }
+    });
+
+    // coverage of a subpixel column relative to the pen, nothing outside the outline
+    let min = bounds.min.x as i32;
+    let at = |subpixel: i32, y: i32| {
+        let x = subpixel - min;
+
+        if x < 0 || x >= width {
+            return 0.0;
+        }
+
+        coverage[(y * width + x) as usize]
+    };
+
+    // whole pixels covering the outline and the filter's reach past it
+    let first = (min - 2).div_euclid(3);
+    let last = (min + width + 2).div_euclid(3);
+
+    let mut image = RgbaImage::new((last - first + 1) as u32, height as u32);
+
+    for (px, x) in (first..=last).enumerate() {
+        for y in 0..height {
+            let channel = |c: i32| {
+                let filtered: f32 = LCD_FILTER
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| weight * at(x * 3 + c + k as i32 - 2, y))
+                    .sum();
+
+                (filtered * 255.0).round() as u8
+            };
+
+            let gray = (0..3).map(|c| at(x * 3 + c, y)).sum::<f32>() / 3.0;
+
+            image.put_pixel(
+                px as u32,
+                y as u32,
+                Rgba([
+                    channel(0),
+                    channel(1),
+                    channel(2),
+                    (gray * 255.0).round() as u8,
+                ]),
+            );
         }
     }
+
+    Some(Tile {
+        image,
+        offset_top: bounds.max.y,
+        offset_left: first as f32,
+    })
+}
+
+// the color emoji font, if there's one installed
+pub fn emoji_font() -> Option<&'static [u8]> {
+    let path = std::env::var_os("RITE_EMOJI_FONT")
+        .map(PathBuf::from)
+        .or_else(|| EMOJI_FONTS.iter().map(PathBuf::from).find(|x| x.is_file()))?;
+
+    let data = fs::read(path).ok()?;
+    ttf_parser::Face::parse(&data, 0).ok()?;
+
+    // shaping and rasterizing borrow it for as long as the editor runs
+    Some(data.leak())
+}
+
+// an RGBA atlas page of emoji in their own colors, filled in as emoji get drawn
+#[derive(Debug)]
+pub struct EmojiPage {
+    pub image: Mutex<RgbaImage>,
+    pub version: AtomicUsize, // bumped whenever an emoji is added, so backends know to upload
+    pub data: &'static [u8],
+    size: u32, // emoji are squares as high as a line
+    descent: f32,
+    glyphs: Mutex<HashMap<u16, Option<Character>>>,
+    next: Mutex<(u32, u32)>, // where the next emoji goes
+}
+
+impl EmojiPage {
+    fn new(data: &'static [u8], ascent: f32, descent: f32) -> Self {
+        EmojiPage {
+            image: Mutex::new(RgbaImage::new(EMOJI_PAGE_SIZE, EMOJI_PAGE_SIZE)),
+            version: AtomicUsize::new(0),
+            data,
+            size: (ascent - descent) as u32,
+            descent,
+            glyphs: Mutex::default(),
+            next: Mutex::new((0, 0)),
+        }
+    }
+
+    // the emoji's place on the page, none if the font can't draw it or the page is full
+    pub fn get(&self, id: u16) -> Option<Character> {
+        if let Some(char) = self.glyphs.lock().unwrap().get(&id) {
+            return *char;
+        }
+
+        let char = rasterize_emoji(self.data, id, self.size).and_then(|image| self.place(image));
+        self.glyphs.lock().unwrap().insert(id, char);
+
+        char
+    }
+
+    fn place(&self, image: RgbaImage) -> Option<Character> {
+        let mut next = self.next.lock().unwrap();
+        let (mut x, mut y) = *next;
+
+        if x + self.size > EMOJI_PAGE_SIZE {
+            x = 0;
+            y += self.size + 1;
+        }
+
+        if y + self.size > EMOJI_PAGE_SIZE {
+            return None;
+        }
+
+        let (width, height) = image.dimensions();
+
+        let mut page = self.image.lock().unwrap();
+        page.copy_from(&image, x, y).unwrap();
+        self.version.fetch_add(1, Ordering::Relaxed);
+
+        *next = (x + self.size + 1, y);
+
+        Some(Character {
+            tex_coords: tex_coords(&page, x, y, &image),
+            width: width as f32,
+            height: height as f32,
+            offset_top: -self.descent,
+            offset_left: 0.0,
+        })
+    }
+}
+
+// the outline layers of a vector color glyph and their colors, gradients aren't supported
+#[derive(Default)]
+struct Layers {
+    outline: Option<ttf_parser::GlyphId>,
+    layers: Vec<(ttf_parser::GlyphId, RgbaColor)>,
+}
+
+impl<'a> colr::Painter<'a> for Layers {
+    fn outline_glyph(&mut self, glyph_id: ttf_parser::GlyphId) {
+        self.outline = Some(glyph_id);
+    }
+
+    fn paint(&mut self, paint: colr::Paint<'a>) {
+        if let (Some(outline), colr::Paint::Solid(color)) = (self.outline, paint) {
+            self.layers.push((outline, color));
+        }
+    }
+
+    fn push_clip(&mut self) {}
+    fn push_clip_box(&mut self, _: colr::ClipBox) {}
+    fn pop_clip(&mut self) {}
+    fn push_layer(&mut self, _: colr::CompositeMode) {}
+    fn pop_layer(&mut self) {}
+    fn push_transform(&mut self, _: ttf_parser::Transform) {}
+    fn pop_transform(&mut self) {}
+}
+
+// a color glyph as a square image, from a bitmap strike (CBDT, sbix) or color layers (COLR)
+pub fn rasterize_emoji(data: &[u8], id: u16, size: u32) -> Option<RgbaImage> {
+    let face = ttf_parser::Face::parse(data, 0).ok()?;
+    let glyph = ttf_parser::GlyphId(id);
+
+    if let Some(raster) = face.glyph_raster_image(glyph, size as u16) {
+        if raster.format != RasterImageFormat::PNG {
+            return None;
+        }
+
+        let image = image::load_from_memory(raster.data).ok()?.to_rgba8();
+        return Some(image::imageops::resize(
+            &image,
+            size,
+            size,
+            FilterType::Triangle,
+        ));
+    }
+
+    let mut layers = Layers::default();
+    face.paint_color_glyph(glyph, 0, RgbaColor::new(255, 255, 255, 255), &mut layers)?;
+
+    let font = FontRef::try_from_slice(data).ok()?;
+    let scale = size as f32;
+    let ascent = font.as_scaled(scale).ascent();
+
+    let mut image = RgbaImage::new(size, size);
+
+    for (layer, color) in layers.layers {
+        let Some(outline) =
+            font.outline_glyph(GlyphId(layer.0).with_scale_and_position(scale, point(0.0, ascent)))
+        else {
+            continue;
+        };
+
+        let bounds = outline.px_bounds();
+
+        // each layer goes over the ones before it
+        outline.draw(|x, y, coverage| {
+            let (x, y) = (
+                bounds.min.x as i32 + x as i32,
+                bounds.min.y as i32 + y as i32,
+            );
+
+            if x < 0 || y < 0 || x >= size as i32 || y >= size as i32 {
+                return;
+            }
+
+            let alpha = coverage.clamp(0.0, 1.0) * color.alpha as f32 / 255.0;
+            let pixel = image.get_pixel_mut(x as u32, y as u32);
+            let over =
+                |under: u8, over: u8| (over as f32 * alpha + under as f32 * (1.0 - alpha)) as u8;
+
+            *pixel = Rgba([
+                over(pixel[0], color.red),
+                over(pixel[1], color.green),
+                over(pixel[2], color.blue),
+                (alpha * 255.0 + pixel[3] as f32 * (1.0 - alpha)) as u8,
+            ]);
+        });
+    }
+
+    Some(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_drawn_onto_the_page_once() {
+        let font = BitmapFont::new(None);
+        let id = font.font.glyph_id('a').0;
+
+        assert_eq!(font.version.load(Ordering::Relaxed), 0);
+
+        let char = font.glyph(id, 1).unwrap();
+        assert_eq!(font.version.load(Ordering::Relaxed), 1);
+
+        assert_eq!(font.glyph(id, 1).unwrap().tex_coords, char.tex_coords);
+        assert_eq!(font.version.load(Ordering::Relaxed), 1);
+
+        // another offset is another glyph, a space has nothing to draw
+        assert_ne!(font.glyph(id, 2).unwrap().tex_coords, char.tex_coords);
+        assert!(font.glyph(font.font.glyph_id(' ').0, 0).is_none());
+        assert_eq!(font.version.load(Ordering::Relaxed), 2);
+    }
 }
@@ -0,0 +1,173 @@
+// scoring loosely follows fzf: every matched char scores, matches at word
+// boundaries and runs of consecutive matches score extra, gaps cost a little
+const SCORE_MATCH: i64 = 16;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_FIRST_CHAR: i64 = 8;
+const BONUS_CONSECUTIVE: i64 = 12;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP: i64 = 1;
+
+#[derive(Clone, Debug)]
+pub struct FuzzyMatch {
+    pub score: i64,
+    pub positions: Vec<usize>, // char indices of the matched characters
+}
+
+fn bonus(prev: Option<char>, char: char) -> i64 {
+    match prev {
+        None => BONUS_FIRST_CHAR + BONUS_BOUNDARY,
+        Some('/' | '\\' | '_' | '-' | '.' | ' ' | ':') => BONUS_BOUNDARY,
+        Some(prev) if prev.is_lowercase() && char.is_uppercase() => BONUS_BOUNDARY,
+        Some(_) => 0,
+    }
+}
+
+fn eq(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+// finds the best scoring way to match `pattern` as a subsequence of `candidate`, ignoring case
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern.chars().filter(|x| !x.is_whitespace()).collect();
+    let candidate: Vec<char> = candidate.chars().collect();
+
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+
+    // cheap rejection before the quadratic part
+    let mut chars = candidate.iter();
+    if !pattern.iter().all(|p| chars.any(|c| eq(*p, *c))) {
+        return None;
+    }
+
+    let n = pattern.len();
+    let m = candidate.len();
+
+    // score[i][j]: best score with pattern[i] matched at candidate[j]
+    let mut score = vec![vec![i64::MIN; m]; n];
+    // from[i][j]: where pattern[i - 1] was matched for that score
+    let mut from = vec![vec![usize::MAX; m]; n];
+
+    for i in 0..n {
+        // best score so far for pattern[i - 1] ending before j, gap penalties included
+        let mut best = i64::MIN;
+        let mut best_at = usize::MAX;
+
+        for j in 0..m {
+            if i > 0 && j > 0 {
+                if best != i64::MIN {
+                    best -= PENALTY_GAP;
+                }
+
+                let prev = score[i - 1][j - 1];
+
+                if prev != i64::MIN && prev - PENALTY_GAP_START > best {
+                    best = prev - PENALTY_GAP_START;
+                    best_at = j - 1;
+                }
+            }
+
+            if !eq(pattern[i], candidate[j]) {
+                continue;
+            }
+
+            let bonus = bonus(j.checked_sub(1).map(|x| candidate[x]), candidate[j]);
+
+            if i == 0 {
+                // leading gaps are free so that a match late in a path isn't punished
+                score[i][j] = SCORE_MATCH + bonus;
+                continue;
+            }
+
+            let consecutive = if j > 0 && score[i - 1][j - 1] != i64::MIN {
+                score[i - 1][j - 1] + BONUS_CONSECUTIVE
+            } else {
+                i64::MIN
+            };
+
+            if consecutive != i64::MIN && consecutive >= best {
+                score[i][j] = consecutive + SCORE_MATCH + bonus;
+                from[i][j] = j - 1;
+            } else if best != i64::MIN {
+                score[i][j] = best + SCORE_MATCH + bonus;
+                from[i][j] = best_at;
+            }
+        }
+    }
+
+    let (end, best) = score[n - 1]
+        .iter()
+        .enumerate()
+        .filter(|(_, score)| **score != i64::MIN)
+        .max_by_key(|(j, score)| (**score, std::cmp::Reverse(*j)))?;
+
+    let mut positions = vec![end];
+    let mut j = end;
+
+    for i in (1..n).rev() {
+        j = from[i][j];
+        positions.push(j);
+    }
+
+    positions.reverse();
+
+    Some(FuzzyMatch {
+        score: *best,
+        positions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(pattern: &str, candidate: &str) -> Vec<usize> {
+        fuzzy_match(pattern, candidate).unwrap().positions
+    }
+
+    fn score(pattern: &str, candidate: &str) -> i64 {
+        fuzzy_match(pattern, candidate).unwrap().score
+    }
+
+    #[test]
+    fn positions_are_char_indices_of_the_best_match() {
+        assert_eq!(positions("abc", "abc"), vec![0, 1, 2]);
+        assert_eq!(positions("ac", "abc"), vec![0, 2]);
+        // the later run of consecutive chars beats the scattered first ones
+        assert_eq!(positions("foo", "f_o_xfoo"), vec![5, 6, 7]);
+        assert_eq!(positions("é", "aéb"), vec![1]);
+        assert_eq!(positions("", "abc"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn word_boundaries_score_higher() {
+        assert_eq!(positions("b", "ab_b"), vec![3]);
+        assert_eq!(positions("mf", "main_file"), vec![0, 5]);
+        assert_eq!(positions("fb", "fooBar"), vec![0, 3]);
+        assert!(score("bar", "foo/bar") > score("bar", "foobar"));
+    }
+
+    #[test]
+    fn consecutive_matches_score_higher() {
+        assert!(score("abc", "abcxx") > score("abc", "axbxc"));
+        assert!(score("edit", "src/editor.rs") > score("edit", "src/e_d_i_t.rs"));
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert_eq!(positions("README", "readme.md"), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(positions("rm", "ReadMe"), vec![0, 4]);
+        assert_eq!(score("abc", "ABC"), score("abc", "abc"));
+    }
+
+    #[test]
+    fn missing_chars_dont_match() {
+        assert!(fuzzy_match("abc", "acb").is_none());
+        assert!(fuzzy_match("x", "").is_none());
+        assert!(fuzzy_match("aa", "a").is_none());
+    }
+}
@@ -0,0 +1,922 @@
+use std::{
+    mem::size_of,
+    sync::{atomic::Ordering, Arc},
+};
+
+use bytemuck::Pod;
+use image::RgbaImage;
+use wgpu::{
+    vertex_attr_array, Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent,
+    BlendFactor, BlendOperation, BlendState, BufferBindingType, BufferDescriptor, BufferUsages,
+    Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, Device, DeviceDescriptor,
+    Extent3d, Features, FragmentState, ImageCopyBuffer, ImageDataLayout, IndexFormat, LoadOp,
+    Maintain, MapMode, MultisampleState, Operations, PipelineLayout, PipelineLayoutDescriptor,
+    PresentMode, PrimitiveState, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, SamplerBindingType,
+    SamplerDescriptor, ShaderModule, ShaderStages, StoreOp, Surface, SurfaceConfiguration, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureViewDescriptor, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexState,
+    VertexStepMode,
+};
+use winit::{dpi::PhysicalSize, window::Window};
+
+use crate::{
+    font::BitmapFont,
+    render::{
+        self, projection, view, Backend, Draw, Layer, GAMMA, INITIAL_QUADS, QUAD_INDICES,
+        TEXT_COLOR,
+    },
+    vertex::{ColorVertex, SquiggleVertex, TextureVertex},
+    Rectangle,
+};
+
+const COLOR_ATTRIBUTES: [VertexAttribute; 2] = vertex_attr_array![0 => Float32x2, 1 => Float32x3];
+const TEXTURE_ATTRIBUTES: [VertexAttribute; 3] =
+    vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32];
+const SQUIGGLE_ATTRIBUTES: [VertexAttribute; 3] =
+    vertex_attr_array![0 => Float32x2, 1 => Float32x3, 2 => Float32x2];
+
+// room for the uniforms of this many draws is allocated up front, the buffer doubles from there
+const INITIAL_DRAWS: usize = 64;
+
+// a draw's view matrix and text color, padded the way the shaders lay them out
+const LOCAL_SIZE: usize = size_of::<[f32; 20]>();
+
+// text drawn with alpha, then the mask and add passes of subpixel text
+const ALPHA_PASS: usize = 0;
+const SUBPIXEL_PASSES: [usize; 2] = [1, 2];
+
+// where frames end up
+enum Target {
+    Surface {
+        surface: Surface<'static>,
+        config: SurfaceConfiguration,
+    },
+    // an image read back with `read`, for screenshots and tests
+    Texture(Texture),
+}
+
+// vertices gathered over a frame and the buffer they're uploaded into, kept across frames
+struct Batch<T: Pod> {
+    vertices: Vec<T>,
+    buffer: wgpu::Buffer,
+}
+
+impl<T: Pod> Batch<T> {
+    fn new(device: &Device) -> Self {
+        Batch {
+            vertices: Vec::new(),
+            buffer: buffer::<T>(device, INITIAL_QUADS * 4, BufferUsages::VERTEX),
+        }
+    }
+
+    fn quads(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    fn upload(&mut self, device: &Device, queue: &Queue) {
+        let bytes: &[u8] = bytemuck::cast_slice(&self.vertices);
+
+        if bytes.len() as u64 > self.buffer.size() {
+            let capacity = self.vertices.len().next_power_of_two();
+            self.buffer = buffer::<T>(device, capacity, BufferUsages::VERTEX);
+        }
+
+        if !bytes.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytes);
+        }
+    }
+}
+
+// the wgpu backend, quads are batched over a frame like with `render::Application` and drawn
+// once it's finished
+pub struct Gpu<'a> {
+    pub font: &'a BitmapFont,
+    device: Device,
+    queue: Queue,
+    target: Target,
+    globals: wgpu::Buffer, // projection, gamma and target size for the shaders
+    atlas: Texture,
+    atlas_version: usize, // version of the glyph atlas last uploaded
+    emoji: Texture,
+    emoji_version: usize, // version of the emoji page last uploaded
+    bind_group: BindGroup,
+    view: [f32; 2],
+    text_color: [f32; 3],
+    // the view matrix and text color of every draw, each at its own offset of the bound buffer
+    locals: wgpu::Buffer,
+    local_layout: BindGroupLayout,
+    local_group: BindGroup,
+    local_stride: u64,
+    color_pipeline: RenderPipeline,
+    squiggle_pipeline: RenderPipeline,
+    text_pipelines: [RenderPipeline; 3], // alpha, mask and add
+    clear: [f32; 3],
+    colors: Batch<ColorVertex>,
+    texts: Batch<TextureVertex>,
+    squiggles: Batch<SquiggleVertex>,
+    // shared by all batches since every quad is indexed the same way
+    indices: wgpu::Buffer,
+    draws: Vec<Draw>,
+}
+
+impl<'a> Gpu<'a> {
+    // draws into `window`, none if there's no adapter that can
+    pub fn windowed(window: Arc<Window>, font: &'a BitmapFont) -> Option<Self> {
+        let size = window.inner_size();
+
+        let instance = wgpu::Instance::default();
+        let surface = instance.create_surface(window).ok()?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            compatible_surface: Some(&surface),
+            ..Default::default()
+        }))?;
+
+        let (device, queue) = request_device(&adapter)?;
+
+        // colors are blended as they're stored like in the other backends, not in linear light
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|x| !x.is_srgb())
+            .or(capabilities.formats.first().copied())?;
+
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: Vec::new(),
+        };
+
+        surface.configure(&device, &config);
+
+        Some(Gpu::new(
+            device,
+            queue,
+            Target::Surface { surface, config },
+            format,
+            font,
+        ))
+    }
+
+    // draws into an image `width` by `height`, none if there's no adapter at all
+    pub fn headless(font: &'a BitmapFont, width: u32, height: u32) -> Option<Self> {
+        Gpu::offscreen(font, width, height, &RequestAdapterOptions::default())
+    }
+
+    // like `headless` on the software adapter, lavapipe or WARP, which draws the same on every
+    // machine that has one
+    #[cfg(test)]
+    pub fn fallback(font: &'a BitmapFont, width: u32, height: u32) -> Option<Self> {
+        let options = RequestAdapterOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        };
+
+        Gpu::offscreen(font, width, height, &options)
+    }
+
+    fn offscreen(
+        font: &'a BitmapFont,
+        width: u32,
+        height: u32,
+        options: &RequestAdapterOptions,
+    ) -> Option<Self> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(options))?;
+
+        let (device, queue) = request_device(&adapter)?;
+
+        let format = TextureFormat::Rgba8Unorm;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        Some(Gpu::new(
+            device,
+            queue,
+            Target::Texture(texture),
+            format,
+            font,
+        ))
+    }
+
+    fn new(
+        device: Device,
+        queue: Queue,
+        target: Target,
+        format: TextureFormat,
+        font: &'a BitmapFont,
+    ) -> Self {
+        let globals = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: size_of::<[f32; 20]>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let atlas = texture(&device, &queue, &font.image.lock().unwrap());
+
+        // without an emoji font no glyph samples it
+        let emoji = match &font.emoji {
+            Some(page) => texture(&device, &queue, &page.image.lock().unwrap()),
+            None => texture(&device, &queue, &RgbaImage::new(1, 1)),
+        };
+
+        let sampler = device.create_sampler(&SamplerDescriptor::default());
+
+        let texture_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: globals.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(
+                        &atlas.create_view(&TextureViewDescriptor::default()),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(
+                        &emoji.create_view(&TextureViewDescriptor::default()),
+                    ),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let local_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(LOCAL_SIZE as u64),
+                },
+                count: None,
+            }],
+        });
+
+        // offsets into a uniform buffer have to be aligned
+        let local_stride = (LOCAL_SIZE as u64)
+            .next_multiple_of(device.limits().min_uniform_buffer_offset_alignment as u64);
+
+        let locals = buffer::<u8>(
+            &device,
+            INITIAL_DRAWS * local_stride as usize,
+            BufferUsages::UNIFORM,
+        );
+        let local_group = local_group(&device, &local_layout, &locals);
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout, &local_layout],
+            push_constant_ranges: &[],
+        });
+
+        let color = device.create_shader_module(wgpu::include_wgsl!("./shaders/color.wgsl"));
+        let texture = device.create_shader_module(wgpu::include_wgsl!("./shaders/texture.wgsl"));
+        let squiggle = device.create_shader_module(wgpu::include_wgsl!("./shaders/boundry.wgsl"));
+
+        let pipeline = |module, entry_point, buffer, blend| {
+            pipeline(&device, &layout, module, entry_point, buffer, format, blend)
+        };
+
+        // the alpha channel is left alone, the target stays opaque
+        let keep_alpha = BlendComponent {
+            src_factor: BlendFactor::Zero,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+
+        let text_blend = |src_factor, dst_factor| BlendState {
+            color: BlendComponent {
+                src_factor,
+                dst_factor,
+                operation: BlendOperation::Add,
+            },
+            alpha: keep_alpha,
+        };
+
+        let texture_buffer = vertex_layout::<TextureVertex>(&TEXTURE_ATTRIBUTES);
+
+        let text_pipelines = [
+            pipeline(
+                &texture,
+                "fs_alpha",
+                texture_buffer.clone(),
+                Some(text_blend(
+                    BlendFactor::SrcAlpha,
+                    BlendFactor::OneMinusSrcAlpha,
+                )),
+            ),
+            pipeline(
+                &texture,
+                "fs_mask",
+                texture_buffer.clone(),
+                Some(text_blend(BlendFactor::Zero, BlendFactor::OneMinusSrc)),
+            ),
+            pipeline(
+                &texture,
+                "fs_add",
+                texture_buffer,
+                Some(text_blend(BlendFactor::One, BlendFactor::One)),
+            ),
+        ];
+
+        let color_pipeline = pipeline(
+            &color,
+            "fs_main",
+            vertex_layout::<ColorVertex>(&COLOR_ATTRIBUTES),
+            None,
+        );
+
+        let squiggle_pipeline = pipeline(
+            &squiggle,
+            "fs_main",
+            vertex_layout::<SquiggleVertex>(&SQUIGGLE_ATTRIBUTES),
+            None,
+        );
+
+        Gpu {
+            font,
+            atlas_version: font.version.load(Ordering::Relaxed),
+            atlas,
+            emoji,
+            emoji_version: 0,
+            bind_group,
+            view: [0.0, 0.0],
+            text_color: TEXT_COLOR,
+            locals,
+            local_layout,
+            local_group,
+            local_stride,
+            color_pipeline,
+            squiggle_pipeline,
+            text_pipelines,
+            clear: [0.0, 0.0, 0.0],
+            colors: Batch::new(&device),
+            texts: Batch::new(&device),
+            squiggles: Batch::new(&device),
+            indices: quad_indices(&device, &queue, INITIAL_QUADS),
+            draws: Vec::new(),
+            globals,
+            target,
+            device,
+            queue,
+        }
+    }
+
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if let Target::Surface { surface, config } = &mut self.target {
+            config.width = size.width.max(1);
+            config.height = size.height.max(1);
+            surface.configure(&self.device, config);
+        }
+    }
+
+    fn size(&self) -> (u32, u32) {
+        match &self.target {
+            Target::Surface { config, .. } => (config.width, config.height),
+            Target::Texture(texture) => (texture.width(), texture.height()),
+        }
+    }
+
+    // uploads everything added this frame and draws it, leaving the batches empty for the next
+    pub fn finish(&mut self) {
+        // glyphs drawn for the first time this frame
+        if self.font.version.load(Ordering::Relaxed) != self.atlas_version {
+            write_texture(&self.queue, &self.atlas, &self.font.image.lock().unwrap());
+            self.atlas_version = self.font.version.load(Ordering::Relaxed);
+        }
+
+        // emoji drawn for the first time this frame
+        if let Some(page) = self
+            .font
+            .emoji
+            .as_ref()
+            .filter(|x| x.version.load(Ordering::Relaxed) != self.emoji_version)
+        {
+            write_texture(&self.queue, &self.emoji, &page.image.lock().unwrap());
+            self.emoji_version = page.version.load(Ordering::Relaxed);
+        }
+
+        self.colors.upload(&self.device, &self.queue);
+        self.texts.upload(&self.device, &self.queue);
+        self.squiggles.upload(&self.device, &self.queue);
+
+        let quads = self
+            .colors
+            .quads()
+            .max(self.texts.quads())
+            .max(self.squiggles.quads());
+
+        if (quads * QUAD_INDICES.len() * size_of::<u32>()) as u64 > self.indices.size() {
+            self.indices = quad_indices(&self.device, &self.queue, quads.next_power_of_two());
+        }
+
+        let (width, height) = self.size();
+
+        let mut globals = projection(width, height).concat();
+        globals.extend([GAMMA, 0.0, width as f32, height as f32]);
+
+        self.queue
+            .write_buffer(&self.globals, 0, bytemuck::cast_slice(&globals));
+
+        let mut locals = vec![0; self.draws.len() * self.local_stride as usize];
+
+        for (draw, bytes) in self
+            .draws
+            .iter()
+            .zip(locals.chunks_exact_mut(self.local_stride as usize))
+        {
+            let mut local = view(draw.view).concat();
+            local.extend(draw.text_color);
+            local.push(0.0);
+
+            bytes[..LOCAL_SIZE].copy_from_slice(bytemuck::cast_slice(&local));
+        }
+
+        if locals.len() as u64 > self.locals.size() {
+            let capacity = self.draws.len().next_power_of_two() * self.local_stride as usize;
+            self.locals = buffer::<u8>(&self.device, capacity, BufferUsages::UNIFORM);
+            self.local_group = local_group(&self.device, &self.local_layout, &self.locals);
+        }
+
+        if !locals.is_empty() {
+            self.queue.write_buffer(&self.locals, 0, &locals);
+        }
+
+        let (frame, view) = match &self.target {
+            Target::Surface { surface, config } => match surface.get_current_texture() {
+                Ok(frame) => {
+                    let view = frame.texture.create_view(&TextureViewDescriptor::default());
+                    (Some(frame), view)
+                }
+                // lost or outdated, the next frame gets a fresh one
+                Err(_) => {
+                    surface.configure(&self.device, config);
+                    self.clear_batches();
+                    return;
+                }
+            },
+            Target::Texture(texture) => {
+                (None, texture.create_view(&TextureViewDescriptor::default()))
+            }
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+
+        {
+            let [red, green, blue] = self.clear;
+
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color {
+                            r: red as f64,
+                            g: green as f64,
+                            b: blue as f64,
+                            a: 1.0,
+                        }),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+
+            pass.set_bind_group(0, &self.bind_group, &[]);
+            pass.set_index_buffer(self.indices.slice(..), IndexFormat::Uint32);
+
+            for (idx, draw) in self.draws.iter().enumerate() {
+                pass.set_bind_group(
+                    1,
+                    &self.local_group,
+                    &[idx as u32 * self.local_stride as u32],
+                );
+
+                // the scissor counts from the top here and has to stay inside the target
+                let (left, bottom, right, top) = match draw.scissor {
+                    Some(x) => (
+                        x.left.min(width),
+                        x.bottom.min(height),
+                        (x.left + x.width).min(width),
+                        (x.bottom + x.height).min(height),
+                    ),
+                    None => (0, 0, width, height),
+                };
+
+                if right <= left || top <= bottom {
+                    continue;
+                }
+
+                pass.set_scissor_rect(left, height - top, right - left, top - bottom);
+
+                let indices = (draw.quads.start * QUAD_INDICES.len()) as u32
+                    ..(draw.quads.end * QUAD_INDICES.len()) as u32;
+
+                match draw.layer {
+                    Layer::Color => {
+                        pass.set_pipeline(&self.color_pipeline);
+                        pass.set_vertex_buffer(0, self.colors.buffer.slice(..));
+                        pass.draw_indexed(indices, 0, 0..1);
+                    }
+                    Layer::Squiggle => {
+                        pass.set_pipeline(&self.squiggle_pipeline);
+                        pass.set_vertex_buffer(0, self.squiggles.buffer.slice(..));
+                        pass.draw_indexed(indices, 0, 0..1);
+                    }
+                    Layer::Text => {
+                        let passes: &[usize] = if draw.subpixel {
+                            &SUBPIXEL_PASSES
+                        } else {
+                            &[ALPHA_PASS]
+                        };
+
+                        pass.set_vertex_buffer(0, self.texts.buffer.slice(..));
+
+                        for &x in passes {
+                            pass.set_pipeline(&self.text_pipelines[x]);
+                            pass.draw_indexed(indices.clone(), 0, 0..1);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.queue.submit([encoder.finish()]);
+
+        if let Some(frame) = frame {
+            frame.present();
+        }
+
+        self.clear_batches();
+    }
+
+    fn clear_batches(&mut self) {
+        self.colors.vertices.clear();
+        self.texts.vertices.clear();
+        self.squiggles.vertices.clear();
+        self.draws.clear();
+    }
+
+    // the last finished frame of a headless backend
+    pub fn read(&self) -> RgbaImage {
+        let Target::Texture(texture) = &self.target else {
+            panic!("only headless frames can be read back");
+        };
+
+        let (width, height) = (texture.width(), texture.height());
+
+        // rows of a copy are padded to a multiple of 256 bytes
+        let row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (row * height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor::default());
+
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        self.device.poll(Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        let pixels = data
+            .chunks_exact(row as usize)
+            .flat_map(|x| &x[..width as usize * 4])
+            .copied()
+            .collect();
+
+        RgbaImage::from_raw(width, height, pixels).unwrap()
+    }
+}
+
+impl Backend for Gpu<'_> {
+    fn clear(&mut self, color: [f32; 3]) {
+        self.clear = color;
+    }
+
+    fn view(&mut self, offset: [f32; 2]) {
+        self.view = offset;
+    }
+
+    fn text_color(&mut self, color: [f32; 3]) {
+        self.text_color = color;
+    }
+
+    fn fill(&mut self, shapes: &[ColorVertex], scissor: Option<Rectangle>) {
+        let start = self.colors.quads();
+        self.colors.vertices.extend_from_slice(shapes);
+        render::push(
+            &mut self.draws,
+            Layer::Color,
+            start..self.colors.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            false,
+        );
+    }
+
+    fn text(&mut self, shapes: &[TextureVertex], scissor: Option<Rectangle>, subpixel: bool) {
+        let start = self.texts.quads();
+        self.texts.vertices.extend_from_slice(shapes);
+        render::push(
+            &mut self.draws,
+            Layer::Text,
+            start..self.texts.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            subpixel,
+        );
+    }
+
+    fn squiggle(&mut self, shapes: &[SquiggleVertex], scissor: Option<Rectangle>) {
+        let start = self.squiggles.quads();
+        self.squiggles.vertices.extend_from_slice(shapes);
+        render::push(
+            &mut self.draws,
+            Layer::Squiggle,
+            start..self.squiggles.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            false,
+        );
+    }
+}
+
+fn local_group(device: &Device, layout: &BindGroupLayout, locals: &wgpu::Buffer) -> BindGroup {
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: locals,
+                offset: 0,
+                size: wgpu::BufferSize::new(LOCAL_SIZE as u64),
+            }),
+        }],
+    })
+}
+
+fn request_device(adapter: &Adapter) -> Option<(Device, Queue)> {
+    pollster::block_on(adapter.request_device(
+        &DeviceDescriptor {
+            label: None,
+            required_features: Features::empty(),
+            required_limits: adapter.limits(),
+        },
+        None,
+    ))
+    .ok()
+}
+
+fn buffer<T>(device: &Device, len: usize, usage: BufferUsages) -> wgpu::Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: None,
+        size: (len * size_of::<T>()) as u64,
+        usage: usage | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn quad_indices(device: &Device, queue: &Queue, quads: usize) -> wgpu::Buffer {
+    let indices: Vec<u32> = (0..quads as u32)
+        .flat_map(|quad| QUAD_INDICES.map(|x| quad * 4 + x))
+        .collect();
+
+    let buffer = buffer::<u32>(device, indices.len(), BufferUsages::INDEX);
+    queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&indices));
+    buffer
+}
+
+fn texture(device: &Device, queue: &Queue, image: &RgbaImage) -> Texture {
+    let texture = device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: image.width(),
+            height: image.height(),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    write_texture(queue, &texture, image);
+    texture
+}
+
+fn write_texture(queue: &Queue, texture: &Texture, image: &RgbaImage) {
+    queue.write_texture(
+        texture.as_image_copy(),
+        image.as_raw(),
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(image.width() * 4),
+            rows_per_image: None,
+        },
+        texture.size(),
+    );
+}
+
+fn vertex_layout<T>(attributes: &[VertexAttribute]) -> VertexBufferLayout<'_> {
+    VertexBufferLayout {
+        array_stride: size_of::<T>() as u64,
+        step_mode: VertexStepMode::Vertex,
+        attributes,
+    }
+}
+
+fn pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    module: &ShaderModule,
+    entry_point: &str,
+    buffer: VertexBufferLayout,
+    format: TextureFormat,
+    blend: Option<BlendState>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module,
+            entry_point: "vs_main",
+            buffers: &[buffer],
+        },
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        fragment: Some(FragmentState {
+            module,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format,
+                blend,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // no adapter is around to compile them otherwise
+    #[test]
+    fn shaders_are_valid() {
+        let shaders = [
+            include_str!("./shaders/color.wgsl"),
+            include_str!("./shaders/texture.wgsl"),
+            include_str!("./shaders/boundry.wgsl"),
+        ];
+
+        for source in shaders {
+            let module = naga::front::wgsl::parse_str(source).unwrap();
+
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn rectangles_are_filled_inside_the_scissor() {
+        let font = BitmapFont::new(None);
+
+        // a software adapter like lavapipe, which any machine running the tests needs
+        let mut gpu = Gpu::fallback(&font, 8, 8)
+            .expect("no fallback wgpu adapter, install lavapipe (mesa-vulkan-drivers)");
+
+        let rect = Rectangle {
+            bottom: 0.0,
+            left: 0.0,
+            height: 8.0,
+            width: 8.0,
+        };
+        let shape = ColorVertex::from(rect, [1.0, 0.0, 0.0]);
+
+        gpu.clear([0.0, 0.0, 1.0]);
+        gpu.fill(
+            &shape,
+            Some(Rectangle {
+                bottom: 0.0,
+                left: 0.0,
+                height: 4.0,
+                width: 2.0,
+            }),
+        );
+        gpu.finish();
+
+        let image = gpu.read();
+
+        // the scissor is the bottom left, rows of the image start at the top
+        assert_eq!(image.get_pixel(0, 7).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(1, 4).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(2, 7).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(0, 3).0, [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,532 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    mem,
+    ops::Range,
+    rc::Rc,
+};
+
+use rustybuzz::{ttf_parser::Tag, Direction, Face, Feature, UnicodeBuffer};
+use unicode_bidi::ParagraphBidiInfo;
+use unicode_width::UnicodeWidthStr;
+
+use crate::font::{BitmapFont, FONT};
+
+// features that turn sequences like -> or != into ligatures, off when ligatures are disabled
+const LIGATURE_FEATURES: &[&[u8; 4]] = &[b"liga", b"clig", b"calt", b"dlig"];
+
+// a shaped glyph placed on a line
+#[derive(Clone, Copy, Debug)]
+pub struct Glyph {
+    pub id: u16,
+    pub cluster: usize, // byte offset of the first char the glyph was shaped from
+    pub x: f32,         // pen position from the start of the line
+    pub dx: f32,        // drawn this far from the pen position, without moving it
+    pub dy: f32,
+    pub advance: f32,
+    pub color: bool, // from the emoji font, drawn in its own colors
+}
+
+// where a char of the line ended up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placed {
+    pub offset: usize, // byte offset into the line
+    pub left: f32,
+    pub right: f32,
+    pub rtl: bool,
+}
+
+impl Placed {
+    // the edge text before the char touches
+    fn leading(&self) -> f32 {
+        if self.rtl {
+            self.right
+        } else {
+            self.left
+        }
+    }
+
+    fn trailing(&self) -> f32 {
+        if self.rtl {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+// a line of text with its glyphs placed, in visual order left to right. the text itself stays
+// in logical order, runs of right to left text are reversed with the bidi algorithm for display.
+// cursor movement stays logical: the caret at an offset sits on the leading edge of the char
+// starting there, so it jumps across the line where the direction changes
+#[derive(Debug, Default)]
+pub struct LineLayout {
+    pub glyphs: Vec<Glyph>,
+    pub width: f32,
+    // every char in text order, chars inside a ligature split it evenly so the cursor steps
+    // through them
+    pub chars: Vec<Placed>,
+    pub len: usize, // bytes in the line
+}
+
+impl LineLayout {
+    fn new(line: &str, layout: &Layout) -> Self {
+        // the bidi algorithm has no runs to give for an empty line
+        if line.is_empty() {
+            return LineLayout::default();
+        }
+
+        let features = layout.features();
+
+        let bidi = ParagraphBidiInfo::new(line, None);
+        let (levels, runs) = bidi.visual_runs(0..line.len());
+
+        let mut glyphs: Vec<Glyph> = Vec::new();
+        let mut chars = Vec::new();
+        let mut x = 0.0;
+
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+            let first = glyphs.len();
+
+            // pieces of a right to left run are shown last to first
+            let mut segments = layout.segments(line, run.clone());
+            if rtl {
+                segments.reverse();
+            }
+
+            for (range, color) in segments {
+                let (face, scale) = match &layout.emoji {
+                    Some(emoji) if color => (emoji, layout.emoji_scale),
+                    _ => (&layout.face, layout.scale),
+                };
+
+                let mut buffer = UnicodeBuffer::new();
+                buffer.push_str(&line[range.clone()]);
+                buffer.guess_segment_properties();
+                buffer.set_direction(if rtl {
+                    Direction::RightToLeft
+                } else {
+                    Direction::LeftToRight
+                });
+
+                let shaped = rustybuzz::shape(face, &features, buffer);
+
+                for (info, position) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                    glyphs.push(Glyph {
+                        id: info.glyph_id as u16,
+                        cluster: range.start + info.cluster as usize,
+                        x: 0.0,
+                        dx: position.x_offset as f32 * scale,
+                        dy: position.y_offset as f32 * scale,
+                        advance: position.x_advance as f32 * scale,
+                        color,
+                    });
+                }
+            }
+
+            let run_glyphs = &mut glyphs[first..];
+
+            let mut starts: Vec<usize> = run_glyphs.iter().map(|x| x.cluster).collect();
+            starts.sort_unstable();
+            starts.dedup();
+
+            let end_of = |start: usize| {
+                let idx = starts.binary_search(&start).unwrap();
+                starts.get(idx + 1).copied().unwrap_or(run.end)
+            };
+
+            // wide chars take up two cells whatever their glyphs' advances, emoji take their
+            // cells exactly
+            for &start in &starts {
+                let columns = line[start..end_of(start)].width();
+                let cluster: Vec<usize> = (0..run_glyphs.len())
+                    .filter(|x| run_glyphs[*x].cluster == start)
+                    .collect();
+
+                if cluster.iter().any(|x| run_glyphs[*x].color) {
+                    for (idx, &glyph) in cluster.iter().enumerate() {
+                        let last = idx + 1 == cluster.len();
+
+                        run_glyphs[glyph].dx = 0.0;
+                        run_glyphs[glyph].advance = if last {
+                            columns.max(2) as f32 * layout.cell
+                        } else {
+                            0.0
+                        };
+                    }
+                    continue;
+                }
+
+                let width: f32 = cluster.iter().map(|x| run_glyphs[*x].advance).sum();
+                let extra = columns as f32 * layout.cell - width;
+
+                if columns > 1 && extra > 0.0 {
+                    for &glyph in &cluster {
+                        run_glyphs[glyph].dx += extra / 2.0;
+                    }
+
+                    if let Some(&glyph) = cluster.last() {
+                        run_glyphs[glyph].advance += extra;
+                    }
+                }
+            }
+
+            for glyph in run_glyphs.iter_mut() {
+                glyph.x = x;
+                x += glyph.advance;
+            }
+
+            for &start in &starts {
+                let end = end_of(start);
+
+                // the glyphs of a cluster are next to each other, whichever way the run goes
+                let cluster = run_glyphs.iter().filter(|x| x.cluster == start);
+                let left = cluster.clone().map(|x| x.x).fold(f32::MAX, f32::min);
+                let width: f32 = cluster.map(|x| x.advance).sum();
+
+                let offsets: Vec<usize> = line[start..end].char_indices().map(|x| x.0).collect();
+                let step = width / offsets.len() as f32;
+
+                for (idx, offset) in offsets.iter().enumerate() {
+                    let left = if rtl {
+                        left + width - (idx + 1) as f32 * step
+                    } else {
+                        left + idx as f32 * step
+                    };
+
+                    chars.push(Placed {
+                        offset: start + offset,
+                        left,
+                        right: left + step,
+                        rtl,
+                    });
+                }
+            }
+        }
+
+        chars.sort_by_key(|x| x.offset);
+
+        LineLayout {
+            glyphs,
+            width: x,
+            chars,
+            len: line.len(),
+        }
+    }
+
+    // x of the caret at the byte offset, the end of the line follows its last char
+    pub fn x(&self, offset: usize) -> f32 {
+        match self.chars.iter().find(|x| x.offset >= offset) {
+            Some(char) => char.leading(),
+            None => self.chars.last().map_or(0.0, Placed::trailing),
+        }
+    }
+
+    // byte offset of the caret closest to `x`, next to whichever char was hit
+    pub fn offset_at(&self, x: f32) -> usize {
+        let mut carets = Vec::new();
+
+        for (idx, char) in self.chars.iter().enumerate() {
+            let next = self.chars.get(idx + 1).map_or(self.len, |x| x.offset);
+
+            carets.push((char.offset, char.leading()));
+            carets.push((next, char.trailing()));
+        }
+
+        carets
+            .into_iter()
+            .min_by(|a, b| (a.1 - x).abs().total_cmp(&(b.1 - x).abs()))
+            .map_or(0, |x| x.0)
+    }
+
+    // byte offset of the char covering `x`, none past the end of the line
+    pub fn char_at(&self, x: f32) -> Option<usize> {
+        self.chars
+            .iter()
+            .find(|char| char.left <= x && x < char.right)
+            .map(|char| char.offset)
+    }
+
+    // left to right (left, right) spans covering the chars of a byte range, a range crossing
+    // a direction change can be split up on screen
+    pub fn spans(&self, range: Range<usize>) -> Vec<(f32, f32)> {
+        let mut placed: Vec<&Placed> = self
+            .chars
+            .iter()
+            .filter(|x| range.contains(&x.offset))
+            .collect();
+        placed.sort_by(|a, b| a.left.total_cmp(&b.left));
+
+        let mut spans: Vec<(f32, f32)> = Vec::new();
+
+        for char in placed {
+            match spans.last_mut() {
+                Some(last) if (char.left - last.1).abs() < 0.01 => last.1 = char.right,
+                _ => spans.push((char.left, char.right)),
+            }
+        }
+
+        spans
+    }
+}
+
+// shapes lines and keeps them keyed by their content, so lines that weren't edited are
+// reused and an edited line is shaped again. lines not asked for during a frame are dropped
+pub struct Layout {
+    face: Face<'static>,
+    scale: f32,
+    emoji: Option<Face<'static>>, // falls back to it for chars the font doesn't have
+    emoji_scale: f32,
+    cell: f32, // advance of a space, wide chars take two
+    line_height: f32,
+    ligatures: Cell<bool>,
+    current: RefCell<HashMap<String, Rc<LineLayout>>>,
+    previous: RefCell<HashMap<String, Rc<LineLayout>>>,
+}
+
+impl Layout {
+    pub fn new(bitmap: &BitmapFont) -> Self {
+        Layout::with_fonts(
+            bitmap.ascent - bitmap.descent,
+            bitmap.emoji.as_ref().map(|x| x.data),
+        )
+    }
+
+    // `line_height` pixels from the ascent to the descent of the font, like the bitmap font
+    pub fn with_fonts(line_height: f32, emoji: Option<&'static [u8]>) -> Self {
+        let face = Face::from_slice(FONT, 0).unwrap();
+        let scale = line_height / (face.ascender() - face.descender()) as f32;
+
+        let emoji = emoji.and_then(|x| Face::from_slice(x, 0));
+        let emoji_scale = emoji
+            .as_ref()
+            .map_or(0.0, |x| line_height / (x.ascender() - x.descender()) as f32);
+
+        let cell = face
+            .glyph_index(' ')
+            .and_then(|x| face.glyph_hor_advance(x))
+            .map_or(line_height / 2.0, |x| x as f32 * scale);
+
+        Layout {
+            face,
+            scale,
+            emoji,
+            emoji_scale,
+            cell,
+            line_height,
+            ligatures: Cell::new(true),
+            current: RefCell::default(),
+            previous: RefCell::default(),
+        }
+    }
+
+    // pieces of a run drawn with the same font, and whether it's the emoji font. joiners,
+    // variation selectors and skin tones stay with the char before them
+    fn segments(&self, line: &str, run: Range<usize>) -> Vec<(Range<usize>, bool)> {
+        let mut segments: Vec<(Range<usize>, bool)> = Vec::new();
+        let mut chars = line[run.clone()].char_indices().peekable();
+
+        while let Some((idx, char)) = chars.next() {
+            let start = run.start + idx;
+            let end = start + char.len_utf8();
+
+            let joins = matches!(char, '\u{200d}' | '\u{20e3}' | '\u{fe00}'..='\u{fe0f}' | '\u{1f3fb}'..='\u{1f3ff}');
+            let presentation = chars.peek().is_some_and(|x| x.1 == '\u{fe0f}');
+
+            let color = match &self.emoji {
+                _ if joins => segments.last().is_some_and(|x| x.1),
+                Some(emoji) => {
+                    emoji.glyph_index(char).is_some()
+                        && (presentation || self.face.glyph_index(char).is_none())
+                }
+                None => false,
+            };
+
+            match segments.last_mut() {
+                Some(last) if last.1 == color => last.0.end = end,
+                _ => segments.push((start..end, color)),
+            }
+        }
+
+        segments
+    }
+
+    // advance of a space, what a column is wide
+    pub fn cell(&self) -> f32 {
+        self.cell
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    pub fn ligatures(&self) -> bool {
+        self.ligatures.get()
+    }
+
+    // everything laid out so far was shaped the other way
+    pub fn set_ligatures(&self, ligatures: bool) {
+        self.ligatures.set(ligatures);
+        self.current.borrow_mut().clear();
+        self.previous.borrow_mut().clear();
+    }
+
+    fn features(&self) -> Vec<Feature> {
+        if self.ligatures() {
+            return Vec::new();
+        }
+
+        LIGATURE_FEATURES
+            .iter()
+            .map(|tag| Feature::new(Tag::from_bytes(tag), 0, ..))
+            .collect()
+    }
+
+    pub fn line(&self, line: &str) -> Rc<LineLayout> {
+        if let Some(layout) = self.current.borrow().get(line) {
+            return layout.clone();
+        }
+
+        let layout = self
+            .previous
+            .borrow_mut()
+            .remove(line)
+            .unwrap_or_else(|| Rc::new(LineLayout::new(line, self)));
+
+        self.current
+            .borrow_mut()
+            .insert(line.to_string(), layout.clone());
+
+        layout
+    }
+
+    // x of a byte offset into a line
+    pub fn x(&self, line: &str, offset: usize) -> f32 {
+        self.line(line).x(offset)
+    }
+
+    pub fn width(&self, string: &str) -> f32 {
+        self.line(string).width
+    }
+
+    // drops the lines that weren't used since the last call
+    pub fn next_frame(&self) {
+        let current = mem::take(&mut *self.current.borrow_mut());
+        *self.previous.borrow_mut() = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lines are laid out at the 24px the editor uses
+    fn lay_out(line: &str) -> Rc<LineLayout> {
+        Layout::with_fonts(24.0, None).line(line)
+    }
+
+    fn clusters(layout: &LineLayout) -> Vec<usize> {
+        layout.glyphs.iter().map(|x| x.cluster).collect()
+    }
+
+    #[test]
+    fn empty_lines_have_nothing_to_place() {
+        let layout = lay_out("");
+
+        assert!(layout.glyphs.is_empty());
+        assert_eq!(layout.x(0), 0.0);
+        assert_eq!(layout.offset_at(100.0), 0);
+    }
+
+    #[test]
+    fn left_to_right_keeps_order() {
+        let layout = lay_out("abc");
+
+        assert_eq!(clusters(&layout), vec![0, 1, 2]);
+        assert_eq!(layout.x(0), 0.0);
+        assert_eq!(layout.x(2), 24.0);
+        assert_eq!(layout.x(3), layout.width);
+        assert_eq!(layout.offset_at(13.0), 1);
+    }
+
+    #[test]
+    fn right_to_left_runs_are_reversed() {
+        // "abc " then four hebrew letters of two bytes each, then " def"
+        let layout = lay_out("abc שלום def");
+
+        assert_eq!(
+            clusters(&layout),
+            vec![0, 1, 2, 3, 10, 8, 6, 4, 12, 13, 14, 15]
+        );
+
+        // the first hebrew letter is drawn rightmost of its run
+        let first = layout.chars.iter().find(|x| x.offset == 4).unwrap();
+        assert_eq!((first.left, first.right), (84.0, 96.0));
+        assert!(first.rtl);
+    }
+
+    #[test]
+    fn carets_follow_logical_order() {
+        let layout = lay_out("abc שלום def");
+
+        // stepping through the text walks right, jumps to the right end of the hebrew run,
+        // walks left through it and jumps back past it
+        let carets: Vec<f32> = [3, 4, 6, 8, 10, 12, 13]
+            .iter()
+            .map(|x| layout.x(*x))
+            .collect();
+
+        assert_eq!(carets, vec![36.0, 96.0, 84.0, 72.0, 60.0, 96.0, 108.0]);
+        assert_eq!(layout.x(layout.len), layout.width);
+    }
+
+    #[test]
+    fn hit_testing_maps_back_to_logical_offsets() {
+        let layout = lay_out("abc שלום def");
+
+        // the right half of the first hebrew letter is before it, the left half after it
+        assert_eq!(layout.offset_at(93.0), 4);
+        assert_eq!(layout.offset_at(86.0), 6);
+        assert_eq!(layout.char_at(90.0), Some(4));
+        assert_eq!(layout.char_at(50.0), Some(10));
+        assert_eq!(layout.char_at(200.0), None);
+
+        for offset in [0, 4, 8, 13, 16] {
+            let char = layout.chars.iter().find(|x| x.offset == offset);
+            let x = char.map_or(layout.width, |x| (x.left + x.right) / 2.0);
+
+            assert_eq!(layout.char_at(x), char.map(|x| x.offset));
+        }
+    }
+
+    #[test]
+    fn selections_split_at_direction_changes() {
+        let layout = lay_out("abc שלום def");
+
+        // "c ש" is two pieces on screen
+        assert_eq!(layout.spans(2..6), vec![(24.0, 48.0), (84.0, 96.0)]);
+
+        // the whole hebrew word stays one piece
+        assert_eq!(layout.spans(4..12), vec![(48.0, 96.0)]);
+        assert!(layout.spans(5..5).is_empty());
+    }
+
+    #[test]
+    fn right_to_left_lines_end_on_the_left() {
+        let layout = lay_out("שלום");
+
+        assert_eq!(layout.x(0), layout.width);
+        assert_eq!(layout.x(layout.len), 0.0);
+        assert_eq!(layout.offset_at(0.0), layout.len);
+    }
+
+    #[test]
+    fn carets_step_through_ligatures() {
+        let layout = lay_out("a->b");
+
+        assert_eq!(layout.chars.len(), 4);
+        assert_eq!(layout.x(2), 24.0);
+        assert_eq!(layout.offset_at(25.0), 2);
+    }
+}
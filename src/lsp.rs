@@ -0,0 +1,1102 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    buffer::Buffer,
+    diagnostics::{Diagnostic, Diagnostics, Severity},
+    waker::Waker,
+};
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub language_id: String,
+    pub extensions: Vec<String>,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl ServerConfig {
+    fn new(language_id: &str, extensions: &[&str], command: &str, args: &[&str]) -> Self {
+        ServerConfig {
+            language_id: language_id.to_string(),
+            extensions: extensions.iter().map(|x| x.to_string()).collect(),
+            command: command.to_string(),
+            args: args.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+pub fn default_servers() -> Vec<ServerConfig> {
+    vec![
+        ServerConfig::new("rust", &["rs"], "rust-analyzer", &[]),
+        ServerConfig::new(
+            "javascript",
+            &["js", "jsx", "mjs"],
+            "typescript-language-server",
+            &["--stdio"],
+        ),
+        ServerConfig::new(
+            "typescript",
+            &["ts", "tsx"],
+            "typescript-language-server",
+            &["--stdio"],
+        ),
+        ServerConfig::new("python", &["py"], "pylsp", &[]),
+        ServerConfig::new("c", &["c", "h"], "clangd", &[]),
+        ServerConfig::new("cpp", &["cpp", "hpp", "cc"], "clangd", &[]),
+    ]
+}
+
+// servers from `.rite/lsp.json` in the project root replace the defaults per language:
+// { "rust": { "command": "rust-analyzer", "args": [], "extensions": ["rs"] } }
+pub fn load_servers(root: &Path) -> Vec<ServerConfig> {
+    let mut servers = default_servers();
+
+    let Ok(config) = fs::read_to_string(root.join(".rite").join("lsp.json")) else {
+        return servers;
+    };
+
+    let Ok(Value::Object(languages)) = serde_json::from_str::<Value>(&config) else {
+        eprintln!("invalid .rite/lsp.json");
+        return servers;
+    };
+
+    let strings = |value: &Value| -> Vec<String> {
+        value
+            .as_array()
+            .map(|x| {
+                x.iter()
+                    .filter_map(|x| x.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    for (language_id, server) in languages {
+        let Some(command) = server["command"].as_str() else {
+            continue;
+        };
+
+        let default = servers.iter().position(|x| x.language_id == language_id);
+
+        let extensions = match (&server["extensions"], default) {
+            (Value::Null, Some(idx)) => servers[idx].extensions.clone(),
+            (extensions, _) => strings(extensions),
+        };
+
+        let config = ServerConfig {
+            language_id,
+            extensions,
+            command: command.to_string(),
+            args: strings(&server["args"]),
+        };
+
+        match default {
+            Some(idx) => servers[idx] = config,
+            None => servers.push(config),
+        }
+    }
+
+    servers
+}
+
+// line and utf-16 column, the way the protocol counts them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn from_offset(content: &str, idx: usize) -> Self {
+        let before = &content[..idx];
+        let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+        Position {
+            line: before.matches('\n').count(),
+            character: before[line_start..].encode_utf16().count(),
+        }
+    }
+
+    // byte offset of the position, clamped to the end of its line
+    pub fn to_offset(self, content: &str) -> usize {
+        let line_start = if self.line == 0 {
+            0
+        } else {
+            match content.match_indices('\n').nth(self.line - 1) {
+                Some((idx, _)) => idx + 1,
+                None => return content.len(),
+            }
+        };
+
+        let mut character = 0;
+
+        for (idx, char) in content[line_start..].char_indices() {
+            if character >= self.character || char == '\n' {
+                return line_start + idx;
+            }
+            character += char.len_utf16();
+        }
+
+        content.len()
+    }
+
+    // char column in the line, as `Cursor::move_to` takes it
+    pub fn column(self, content: &str) -> usize {
+        let idx = self.to_offset(content);
+        let line_start = content[..idx].rfind('\n').map_or(0, |x| x + 1);
+
+        content[line_start..idx].chars().count()
+    }
+
+    fn to_json(self) -> Value {
+        json!({ "line": self.line, "character": self.character })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        Position {
+            line: value["line"].as_u64().unwrap_or(0) as usize,
+            character: value["character"].as_u64().unwrap_or(0) as usize,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Range {
+    fn from_json(value: &Value) -> Self {
+        Range {
+            start: Position::from_json(&value["start"]),
+            end: Position::from_json(&value["end"]),
+        }
+    }
+}
+
+// an edit queued by a buffer, positions are from before the edit was applied
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub range: Range,
+    pub text: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub range: Range,
+}
+
+impl Location {
+    fn from_json(value: &Value) -> Option<Self> {
+        // plain locations and location links both carry a uri and a range
+        let uri = value["uri"].as_str().or(value["targetUri"].as_str())?;
+        let range = match &value["targetSelectionRange"] {
+            Value::Null => &value["range"],
+            range => range,
+        };
+
+        Some(Location {
+            path: uri_to_path(uri)?,
+            range: Range::from_json(range),
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    fn from_json(value: &Value) -> Self {
+        TextEdit {
+            range: Range::from_json(&value["range"]),
+            new_text: value["newText"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CompletionItem {
+    pub label: String,
+    pub detail: Option<String>,
+    pub documentation: Option<String>,
+    pub insert_text: String,
+    pub edit: Option<TextEdit>,
+    pub snippet: bool, // insert text uses snippet syntax
+}
+
+impl CompletionItem {
+    fn from_json(value: &Value) -> Self {
+        let label = value["label"].as_str().unwrap_or_default().to_string();
+
+        let edit = match &value["textEdit"] {
+            Value::Null => None,
+            // insert/replace edits carry two ranges, insert ends at the cursor
+            edit if edit["insert"].is_object() => Some(TextEdit {
+                range: Range::from_json(&edit["insert"]),
+                new_text: edit["newText"].as_str().unwrap_or_default().to_string(),
+            }),
+            edit => Some(TextEdit::from_json(edit)),
+        };
+
+        CompletionItem {
+            insert_text: value["insertText"]
+                .as_str()
+                .map_or(label.clone(), String::from),
+            label,
+            detail: value["detail"].as_str().map(String::from),
+            documentation: markup(&value["documentation"]),
+            edit,
+            snippet: value["insertTextFormat"].as_u64() == Some(2),
+        }
+    }
+}
+
+pub enum Event {
+    Diagnostics,
+    Hover(Option<String>),
+    Definition(Vec<Location>),
+    References(Vec<Location>),
+    Rename(Vec<(PathBuf, Vec<TextEdit>)>),
+    Completion(PathBuf, Vec<CompletionItem>),
+    Error(String),
+}
+
+// requests waiting for a response, by id
+enum Pending {
+    Initialize,
+    Shutdown,
+    Hover,
+    Definition,
+    References,
+    Rename,
+    Completion(PathBuf),
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+
+    for byte in path.to_string_lossy().replace('\\', "/").bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            // windows paths start with the drive letter
+            b':' if uri.len() == "file://".len() + 2 => uri.push(':'),
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    if !uri["file://".len()..].starts_with('/') {
+        uri.insert(7, '/');
+    }
+
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut iter = path.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    let path = String::from_utf8(bytes).ok()?;
+
+    // "/C:/dir" on windows
+    if path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]));
+    }
+
+    Some(PathBuf::from(path))
+}
+
+// hover contents and documentation come as strings, markup or lists of either
+fn markup(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        Value::Object(object) => object.get("value")?.as_str()?.to_string(),
+        Value::Array(values) => values
+            .iter()
+            .filter_map(markup)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => return None,
+    };
+
+    Some(text).filter(|x| !x.trim().is_empty())
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+// reads one message, None once the stream is closed
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing content length",
+        ));
+    };
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+// one language server process spoken to over stdio
+pub struct Client {
+    pub config: ServerConfig,
+    pub capabilities: Value,
+    process: Child,
+    stdin: ChildStdin,
+    receiver: Receiver<Value>,
+    next_id: i64,
+    pending: HashMap<i64, Pending>,
+    initialized: bool,
+    queue: Vec<Value>,                // sent once the server answered initialize
+    documents: HashMap<PathBuf, i32>, // open documents and their synced version
+}
+
+impl Client {
+    pub fn start(config: ServerConfig, root: &Path, waker: Waker) -> io::Result<Self> {
+        let mut process = Command::new(&config.command)
+            .args(&config.args)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let stdin = process.stdin.take().unwrap();
+        let stdout = process.stdout.take().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+
+            // the server exited, `poll` sees the channel closed
+            drop(sender);
+            waker.wake();
+        });
+
+        let mut client = Client {
+            config,
+            capabilities: Value::Null,
+            process,
+            stdin,
+            receiver,
+            next_id: 0,
+            pending: HashMap::new(),
+            initialized: false,
+            queue: Vec::new(),
+            documents: HashMap::new(),
+        };
+
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": path_to_uri(root),
+            "workspaceFolders": [{ "uri": path_to_uri(root), "name": root.file_name().map(|x| x.to_string_lossy()) }],
+            "capabilities": {
+                "textDocument": {
+                    "synchronization": { "didSave": true },
+                    "publishDiagnostics": {},
+                    "hover": { "contentFormat": ["plaintext", "markdown"] },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "rename": {},
+                    "completion": {
+                        "completionItem": {
+                            "snippetSupport": true,
+                            "documentationFormat": ["plaintext", "markdown"],
+                        }
+                    },
+                },
+                "general": { "positionEncodings": ["utf-16"] },
+            },
+        });
+
+        // initialize goes out ahead of the queue
+        let id = client.next_id();
+        client.pending.insert(id, Pending::Initialize);
+        client.write(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": params,
+        }))?;
+
+        Ok(client)
+    }
+
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn write(&mut self, message: &Value) -> io::Result<()> {
+        write_message(&mut self.stdin, message)
+    }
+
+    fn send(&mut self, message: Value) {
+        if !self.initialized {
+            self.queue.push(message);
+            return;
+        }
+
+        // a dead server shows up as missing responses, there's nothing to do here
+        let _ = self.write(&message);
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    fn request(&mut self, method: &str, params: Value, pending: Pending) {
+        let id = self.next_id();
+        self.pending.insert(id, pending);
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+    }
+
+    pub fn handles(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| self.config.extensions.iter().any(|ext| ext == x))
+    }
+
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.documents.contains_key(path)
+    }
+
+    pub fn did_open(&mut self, path: &Path, version: i32, text: &str) {
+        self.documents.insert(path.to_path_buf(), version);
+
+        let language_id = self.config.language_id.clone();
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": path_to_uri(path),
+                    "languageId": language_id,
+                    "version": version,
+                    "text": text,
+                }
+            }),
+        );
+    }
+
+    // sends the queued edits, or the whole text if the server doesn't do incremental sync
+    pub fn did_change(&mut self, path: &Path, version: i32, changes: &[Change], text: &str) {
+        if changes.is_empty() {
+            return;
+        }
+
+        self.documents.insert(path.to_path_buf(), version);
+
+        let sync = &self.capabilities["textDocumentSync"];
+        let kind = sync.as_u64().or(sync["change"].as_u64()).unwrap_or(2);
+
+        let content_changes: Vec<Value> = if kind == 2 {
+            changes
+                .iter()
+                .map(|change| {
+                    json!({
+                        "range": {
+                            "start": change.range.start.to_json(),
+                            "end": change.range.end.to_json(),
+                        },
+                        "text": change.text,
+                    })
+                })
+                .collect()
+        } else {
+            vec![json!({ "text": text })]
+        };
+
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": path_to_uri(path), "version": version },
+                "contentChanges": content_changes,
+            }),
+        );
+    }
+
+    pub fn did_save(&mut self, path: &Path) {
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": path_to_uri(path) } }),
+        );
+    }
+
+    pub fn did_close(&mut self, path: &Path) {
+        self.documents.remove(path);
+        self.notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": path_to_uri(path) } }),
+        );
+    }
+
+    fn position_params(path: &Path, position: Position) -> Value {
+        json!({
+            "textDocument": { "uri": path_to_uri(path) },
+            "position": position.to_json(),
+        })
+    }
+
+    pub fn hover(&mut self, path: &Path, position: Position) {
+        let params = Client::position_params(path, position);
+        self.request("textDocument/hover", params, Pending::Hover);
+    }
+
+    pub fn definition(&mut self, path: &Path, position: Position) {
+        let params = Client::position_params(path, position);
+        self.request("textDocument/definition", params, Pending::Definition);
+    }
+
+    pub fn references(&mut self, path: &Path, position: Position) {
+        let mut params = Client::position_params(path, position);
+        params["context"] = json!({ "includeDeclaration": true });
+        self.request("textDocument/references", params, Pending::References);
+    }
+
+    pub fn rename(&mut self, path: &Path, position: Position, new_name: &str) {
+        let mut params = Client::position_params(path, position);
+        params["newName"] = json!(new_name);
+        self.request("textDocument/rename", params, Pending::Rename);
+    }
+
+    pub fn completion(&mut self, path: &Path, position: Position) {
+        let params = Client::position_params(path, position);
+        self.request(
+            "textDocument/completion",
+            params,
+            Pending::Completion(path.to_path_buf()),
+        );
+    }
+
+    // handles everything the server sent since the last call
+    pub fn poll(&mut self, diagnostics: &mut Diagnostics) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        loop {
+            let message = match self.receiver.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                // the server exited, nothing it was asked is going to be answered
+                Err(TryRecvError::Disconnected) => {
+                    self.pending.clear();
+                    break;
+                }
+            };
+            let id = message["id"].as_i64();
+
+            match (id, message["method"].as_str()) {
+                // a request from the server, nothing it asks for is supported
+                (Some(_), Some(_)) => {
+                    let _ = self.write(&json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "result": null,
+                    }));
+                }
+                (None, Some("textDocument/publishDiagnostics")) => {
+                    let params = &message["params"];
+                    let Some(path) = params["uri"].as_str().and_then(uri_to_path) else {
+                        continue;
+                    };
+
+                    let list = params["diagnostics"]
+                        .as_array()
+                        .map(Vec::as_slice)
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|x| Diagnostic {
+                            range: Range::from_json(&x["range"]),
+                            severity: match x["severity"].as_u64() {
+                                Some(2) => Severity::Warning,
+                                Some(3) => Severity::Information,
+                                Some(4) => Severity::Hint,
+                                _ => Severity::Error,
+                            },
+                            message: x["message"].as_str().unwrap_or_default().to_string(),
+                            source: x["source"].as_str().map(String::from),
+                        })
+                        .collect();
+
+                    diagnostics.publish(&self.config.language_id, path, list);
+                    events.push(Event::Diagnostics);
+                }
+                (None, Some(_)) => (),
+                (Some(id), None) => {
+                    let Some(pending) = self.pending.remove(&id) else {
+                        continue;
+                    };
+
+                    if let Some(error) = message["error"]["message"].as_str() {
+                        events.push(Event::Error(error.to_string()));
+                        continue;
+                    }
+
+                    if let Some(event) = self.response(pending, &message["result"]) {
+                        events.push(event);
+                    }
+                }
+                (None, None) => (),
+            }
+        }
+
+        events
+    }
+
+    fn response(&mut self, pending: Pending, result: &Value) -> Option<Event> {
+        let locations = |result: &Value| match result {
+            Value::Array(values) => values.iter().filter_map(Location::from_json).collect(),
+            Value::Null => Vec::new(),
+            value => Location::from_json(value).into_iter().collect(),
+        };
+
+        let event = match pending {
+            Pending::Initialize => {
+                self.capabilities = result["capabilities"].clone();
+                self.initialized = true;
+
+                let _ = self.write(&json!({
+                    "jsonrpc": "2.0",
+                    "method": "initialized",
+                    "params": {},
+                }));
+
+                for message in std::mem::take(&mut self.queue) {
+                    let _ = self.write(&message);
+                }
+
+                return None;
+            }
+            Pending::Shutdown => return None,
+            Pending::Hover => Event::Hover(markup(&result["contents"])),
+            Pending::Definition => Event::Definition(locations(result)),
+            Pending::References => Event::References(locations(result)),
+            Pending::Rename => {
+                let mut files: Vec<(PathBuf, Vec<TextEdit>)> = Vec::new();
+
+                if let Some(changes) = result["changes"].as_object() {
+                    for (uri, edits) in changes {
+                        let Some(path) = uri_to_path(uri) else {
+                            continue;
+                        };
+                        let edits = edits.as_array().map(Vec::as_slice).unwrap_or_default();
+                        files.push((path, edits.iter().map(TextEdit::from_json).collect()));
+                    }
+                }
+
+                for change in result["documentChanges"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default()
+                {
+                    let Some(path) = change["textDocument"]["uri"].as_str().and_then(uri_to_path)
+                    else {
+                        continue;
+                    };
+                    let edits = change["edits"]
+                        .as_array()
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    files.push((path, edits.iter().map(TextEdit::from_json).collect()));
+                }
+
+                Event::Rename(files)
+            }
+            Pending::Completion(path) => {
+                // either a list of items or a completion list holding them
+                let items = match result {
+                    Value::Array(items) => items,
+                    result => result["items"].as_array()?,
+                };
+
+                Event::Completion(path, items.iter().map(CompletionItem::from_json).collect())
+            }
+        };
+
+        Some(event)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        let id = self.next_id();
+        self.pending.insert(id, Pending::Shutdown);
+
+        let _ = self.write(&json!({ "jsonrpc": "2.0", "id": id, "method": "shutdown" }));
+        let _ = self.write(&json!({ "jsonrpc": "2.0", "method": "exit" }));
+
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+// the running servers, started on demand for the file types they're configured for
+pub struct Lsp {
+    pub root: PathBuf,
+    pub servers: Vec<ServerConfig>,
+    pub clients: Vec<Client>,
+    failed: HashSet<String>, // servers that couldn't be started aren't retried
+    waker: Waker,
+}
+
+impl Lsp {
+    pub fn new(root: PathBuf, waker: Waker) -> Self {
+        Lsp {
+            servers: load_servers(&root),
+            root,
+            clients: Vec::new(),
+            failed: HashSet::new(),
+            waker,
+        }
+    }
+
+    // language id of the server configured for the file, whether or not it's running
+    pub fn language(&self, path: &Path) -> Option<&str> {
+        let extension = path.extension()?.to_str()?;
+
+        self.servers
+            .iter()
+            .find(|x| x.extensions.iter().any(|ext| ext == extension))
+            .map(|x| x.language_id.as_str())
+    }
+
+    pub fn client(&mut self, path: &Path) -> Option<&mut Client> {
+        let idx = match self.clients.iter().position(|x| x.handles(path)) {
+            Some(idx) => idx,
+            None => {
+                let extension = path.extension()?.to_str()?;
+                let config = self
+                    .servers
+                    .iter()
+                    .find(|x| x.extensions.iter().any(|ext| ext == extension))?
+                    .clone();
+
+                if self.failed.contains(&config.language_id) {
+                    return None;
+                }
+
+                match Client::start(config.clone(), &self.root, self.waker.clone()) {
+                    Ok(client) => self.clients.push(client),
+                    Err(err) => {
+                        eprintln!("failed to start {}: {}", config.command, err);
+                        self.failed.insert(config.language_id);
+                        return None;
+                    }
+                }
+
+                self.clients.len() - 1
+            }
+        };
+
+        Some(&mut self.clients[idx])
+    }
+
+    // opens, updates and closes documents to match the buffers
+    pub fn sync(&mut self, buffers: &mut [Buffer]) {
+        for buffer in buffers.iter_mut() {
+            let changes = std::mem::take(&mut buffer.changes);
+
+            let Some(path) = buffer.path.clone() else {
+                continue;
+            };
+
+            let Some(client) = self.client(&path) else {
+                continue;
+            };
+
+            if client.is_open(&path) {
+                client.did_change(&path, buffer.version, &changes, &buffer.content);
+            } else {
+                client.did_open(&path, buffer.version, &buffer.content);
+            }
+        }
+
+        for client in &mut self.clients {
+            let closed: Vec<PathBuf> = client
+                .documents
+                .keys()
+                .filter(|path| !buffers.iter().any(|x| x.path.as_ref() == Some(*path)))
+                .cloned()
+                .collect();
+
+            for path in closed {
+                client.did_close(&path);
+            }
+        }
+    }
+
+    pub fn did_save(&mut self, path: &Path) {
+        if let Some(client) = self.clients.iter_mut().find(|x| x.is_open(path)) {
+            client.did_save(path);
+        }
+    }
+
+    pub fn poll(&mut self, diagnostics: &mut Diagnostics) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        for client in &mut self.clients {
+            events.extend(client.poll(diagnostics));
+        }
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{cursor::Cursor, utils::temp::TempDir};
+
+    // the mock server is src/bin/mock_lsp.rs, cargo builds it for the `rite` test target. the
+    // bin's own test build, which `--all-targets` still checks, doesn't know where it is
+    #[allow(clippy::option_env_unwrap)]
+    fn mock_server() -> ServerConfig {
+        let command = option_env!("CARGO_BIN_EXE_mock_lsp")
+            .expect("the lsp tests run from the `rite` test target, `cargo test --test rite`");
+
+        ServerConfig {
+            language_id: "plaintext".to_string(),
+            extensions: vec!["txt".to_string()],
+            command: command.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    fn wait_for(
+        client: &mut Client,
+        diagnostics: &mut Diagnostics,
+        matches: impl Fn(&Event) -> bool,
+    ) -> Event {
+        let start = Instant::now();
+
+        loop {
+            if let Some(event) = client.poll(diagnostics).into_iter().find(&matches) {
+                return event;
+            }
+
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "no response from the mock server"
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    // the root is the test's own, removed once the test drops it
+    fn start() -> (Client, PathBuf, TempDir) {
+        let root = TempDir::new("lsp");
+        let client = Client::start(mock_server(), root.path(), Waker::default()).unwrap();
+        let path = root.path().join("test.txt");
+
+        (client, path, root)
+    }
+
+    #[test]
+    fn positions_count_utf16() {
+        let content = "ab\nä😀x\n";
+
+        let idx = content.find('x').unwrap();
+        let position = Position::from_offset(content, idx);
+
+        assert_eq!(
+            position,
+            Position {
+                line: 1,
+                character: 3
+            }
+        );
+        assert_eq!(position.to_offset(content), idx);
+        assert_eq!(position.column(content), 2);
+
+        let mut cursor = Cursor::new();
+        cursor.move_to(content, position.line, position.column(content));
+        assert_eq!(cursor.idx, idx);
+
+        // past the end of the line clamps to the newline
+        let position = Position {
+            line: 0,
+            character: 10,
+        };
+        assert_eq!(position.to_offset(content), 2);
+    }
+
+    #[test]
+    fn uris_round_trip() {
+        let path = Path::new("/tmp/some dir/ä.rs");
+        let uri = path_to_uri(path);
+
+        assert_eq!(uri, "file:///tmp/some%20dir/%C3%A4.rs");
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+    }
+
+    #[test]
+    fn incremental_changes_reach_the_server() {
+        let (mut client, path, _root) = start();
+        let mut diagnostics = Diagnostics::default();
+
+        let mut buffer = Buffer::new("test", "fine bad\n".to_string());
+        buffer.path = Some(path.clone());
+        client.did_open(&path, buffer.version, &buffer.content);
+
+        wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Diagnostics)
+        });
+        assert_eq!(diagnostics.for_file(&path)[0].range.start.character, 5);
+
+        // typing in front of the match moves it, a multibyte char counts once
+        buffer.replace(0..0, "😀");
+        buffer.replace(4..4, " ");
+        let changes = std::mem::take(&mut buffer.changes);
+        client.did_change(&path, buffer.version, &changes, &buffer.content);
+
+        wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Diagnostics)
+        });
+        let diagnostic = diagnostics.for_file(&path)[0];
+        assert_eq!(diagnostic.range.start.character, 8);
+        assert_eq!(diagnostic.severity, Severity::Error);
+    }
+
+    #[test]
+    fn requests_get_answered() {
+        let (mut client, path, _root) = start();
+        let mut diagnostics = Diagnostics::default();
+
+        let content = "alpha beta\nalphabet alpha\n";
+        client.did_open(&path, 0, content);
+
+        let at = |line, character| Position { line, character };
+
+        client.hover(&path, at(0, 2));
+        let Event::Hover(Some(text)) = wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Hover(_))
+        }) else {
+            unreachable!()
+        };
+        assert_eq!(text, "alpha");
+
+        client.definition(&path, at(1, 10));
+        let Event::Definition(locations) = wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Definition(_))
+        }) else {
+            unreachable!()
+        };
+        assert_eq!(locations.len(), 1);
+        assert_eq!(locations[0].range.start, at(0, 0));
+        assert_eq!(locations[0].path, path);
+
+        client.references(&path, at(0, 0));
+        let Event::References(locations) = wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::References(_))
+        }) else {
+            unreachable!()
+        };
+        assert_eq!(locations.len(), 2);
+
+        client.rename(&path, at(0, 0), "gamma");
+        let Event::Rename(files) = wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Rename(_))
+        }) else {
+            unreachable!()
+        };
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].1.len(), 2);
+        assert_eq!(files[0].1[1].range.start, at(1, 9));
+        assert_eq!(files[0].1[1].new_text, "gamma");
+
+        client.completion(&path, at(1, 4));
+        let Event::Completion(_, items) = wait_for(&mut client, &mut diagnostics, |x| {
+            matches!(x, Event::Completion(..))
+        }) else {
+            unreachable!()
+        };
+        let labels: Vec<&str> = items.iter().map(|x| x.label.as_str()).collect();
+        assert_eq!(labels, ["alpha", "alphabet"]);
+    }
+
+    #[test]
+    fn requests_are_dropped_once_the_server_exits() {
+        let (mut client, path, _root) = start();
+        let mut diagnostics = Diagnostics::default();
+
+        client.hover(&path, Position::default());
+        assert!(!client.pending.is_empty());
+
+        client.process.kill().unwrap();
+        let start = Instant::now();
+
+        while !client.pending.is_empty() {
+            client.poll(&mut diagnostics);
+
+            assert!(start.elapsed() < Duration::from_secs(10), "still waiting");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
@@ -1,23 +1,63 @@
+mod animation;
+mod buffer;
+mod command;
+mod completion;
 mod cursor;
+mod damage;
+mod diagnostics;
+mod editor;
+mod explorer;
+mod finder;
+mod fold;
 mod font;
+mod fuzzy;
+mod gpu;
+mod layout;
+mod lsp;
+mod pane;
+mod picker;
+mod problems;
 mod render;
-mod scalable;
+mod search;
+mod selection;
+mod settings;
+mod snippet;
+mod software;
+mod stats;
+mod status;
 mod text;
 mod utils;
 mod vertex;
+mod waker;
+mod workspace;
 
-use cursor::Cursor;
+use animation::Overlay;
+use buffer::Buffer;
+use cursor::CursorStyle;
+use diagnostics::Severity;
+use editor::Editor;
 use font::BitmapFont;
-use glium::{uniform, Surface, VertexBuffer};
-use render::Application;
+use gpu::Gpu;
+use layout::Layout;
+use render::{Application, Backend, TEXT_COLOR};
+use software::Software;
+use stats::FrameStats;
+use status::Side;
+use std::{
+    path::PathBuf,
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use text::TextRenderer;
-use utils::interpolation::lerp;
-use vertex::ColorVertex;
+use vertex::{ColorVertex, SquiggleVertex};
 use winit::{
-    event::{ElementState, Event, WindowEvent},
-    keyboard,
-    platform::modifier_supplement::KeyEventExtModifierSupplement,
+    dpi::PhysicalSize,
+    event::{ElementState, Event, MouseScrollDelta, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Window, WindowBuilder},
 };
+use workspace::Workspace;
 
 extern crate glium;
 extern crate winit;
@@ -30,49 +70,125 @@ struct Rectangle {
     width: f32,
 }
 
+const WINDOW_SIZE: (u32, u32) = (800, 400);
+const BACKGROUND: [f32; 3] = [2.0 / 255.0, 2.0 / 255.0, 2.0 / 255.0];
+const TAB_BAR_HEIGHT: f32 = 32.0;
+const TAB_PADDING: f32 = 12.0;
+const SIDEBAR_WIDTH: f32 = 240.0;
+const PICKER_WIDTH: f32 = 640.0;
+const PICKER_ROWS: usize = 10;
+const GUTTER_ICON_SIZE: f32 = 8.0;
+const TOOLTIP_PADDING: f32 = 6.0;
+const FOLD_MARKER_SIZE: f32 = 6.0;
+const WHEEL_ROWS: f32 = 3.0; // rows scrolled per wheel notch
+const COMPLETION_WIDTH: f32 = 280.0;
+const DOCUMENTATION_WIDTH: f32 = 360.0;
+const DOCUMENTATION_LINES: usize = 12;
+const STATUS_GAP: f32 = 24.0; // between status bar segments
+const FRAME_INTERVAL: Duration = Duration::from_millis(16); // between frames of an animation
+
 fn main() {
-    let event_loop = winit::event_loop::EventLoop::new().expect("Unable to create event loop");
+    let bitmap = BitmapFont::new(font::emoji_font());
 
-    let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
-        .with_inner_size(800, 400)
-        .with_title("Rite")
-        .build(&event_loop);
+    let layout = Rc::new(Layout::new(&bitmap));
+    let renderer = TextRenderer::new(&bitmap, layout.clone());
 
-    let bitmap = BitmapFont::new(&display);
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut screenshot = None;
+    let mut api = None;
 
-    let renderer = TextRenderer::new(&bitmap);
+    // `--screenshot out.png` draws a single frame into an image instead of opening a window and
+    // `--renderer glium` or `--renderer wgpu` picks what draws it
+    let mut args = std::env::args().skip(1);
 
-    let mut content = include_str!("./samples/sample.js")
-        .to_string()
-        .chars()
-        .filter(|x| *x != '\r')
-        .collect::<String>();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--screenshot" => screenshot = args.next().map(PathBuf::from),
+            "--renderer" => api = args.next(),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
 
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    if let Some(name) = api.as_deref().filter(|x| !["glium", "wgpu"].contains(x)) {
+        eprintln!("unknown renderer {}, expected glium or wgpu", name);
+    }
 
-    let program = glium::Program::from_source(
-        &display,
-        include_str!("./shaders/texture.vert"),
-        include_str!("./shaders/texture.frag"),
-        None,
-    )
-    .unwrap();
+    let mut workspace = if paths.is_empty() {
+        let content = include_str!("./samples/sample.js")
+            .to_string()
+            .chars()
+            .filter(|x| *x != '\r')
+            .collect::<String>();
 
-    let color_program = glium::Program::from_source(
-        &display,
-        include_str!("./shaders/color.vert"),
-        include_str!("./shaders/color.frag"),
-        None,
-    )
-    .unwrap();
+        Workspace::new(Buffer::new("sample.js", content))
+    } else {
+        Workspace::new(Buffer::new("untitled", String::new()))
+    };
+
+    for path in &paths {
+        if let Err(err) = workspace.open(path) {
+            eprintln!("{}: {}", path.display(), err);
+        }
+    }
+
+    let mut editor = Editor::new(workspace, std::env::current_dir().unwrap(), layout.clone());
+
+    if let Some(path) = screenshot {
+        let (width, height) = WINDOW_SIZE;
+
+        // a still image, of everything where it ends up
+        editor.motion.reduced = true;
+
+        let mut draw = |backend: &mut dyn Backend| {
+            draw_frame(
+                &mut editor,
+                &renderer,
+                backend,
+                PhysicalSize::new(width, height),
+                &FrameStats::default(),
+                Instant::now(),
+            )
+        };
+
+        // the software backend unless wgpu is asked for and there's an adapter
+        let gpu = match api.as_deref() {
+            Some("wgpu") => Gpu::headless(&bitmap, width, height),
+            _ => None,
+        };
 
-    let padding = 16.0;
+        let image = match gpu {
+            Some(mut gpu) => {
+                draw(&mut gpu);
+                gpu.finish();
+                gpu.read()
+            }
+            None => {
+                let mut backend = Software::new(&bitmap, width, height);
+                draw(&mut backend);
+                backend.image
+            }
+        };
+
+        if let Err(err) = image.save(&path) {
+            eprintln!("{}: {}", path.display(), err);
+        }
+
+        return;
+    }
+
+    let event_loop = EventLoop::new().expect("Unable to create event loop");
+
+    let (window, mut output) = open_window(&event_loop, &bitmap, api.as_deref());
 
-    let mut curr_cursor_x = 0.0;
-    let mut curr_cursor_y = 0.0;
+    // background work ends up in `AboutToWait`, which polls it
+    let proxy = event_loop.create_proxy();
+    editor.waker.set(move || {
+        let _ = proxy.send_event(());
+    });
 
-    let mut cursor = Cursor::new();
+    let mut stats = FrameStats::default();
 
+    editor.damage.invalidate();
 
     event_loop
         .run(|ev, control_flow| match ev {
@@ -80,183 +196,1615 @@ fn main() {
                 window_id: _,
                 event,
             } => match event {
-                WindowEvent::KeyboardInput { event, .. } => {
-                    if event.state == ElementState::Pressed {
-                        match event.key_without_modifiers().as_ref() {
-                            keyboard::Key::Named(key) => match key {
-                                keyboard::NamedKey::Backspace => {
-                                    if cursor.idx > 0 {
-                                        cursor.move_left(&content);
-                                        content.remove(cursor.idx);
-                                    }
-                                }
-                                keyboard::NamedKey::ArrowRight => {
-                                    cursor.move_right(&content);
-                                }
-                                keyboard::NamedKey::ArrowLeft => {
-                                    cursor.move_left(&content);
-                                }
-                                keyboard::NamedKey::ArrowDown => {
-                                    cursor.move_down(&content);
-                                }
-                                keyboard::NamedKey::ArrowUp => {
-                                    cursor.move_up(&content);
-                                }
-                                keyboard::NamedKey::Space => {
-                                    content.insert(cursor.idx, ' ');
-                                    cursor.move_right(&content);
-                                }
-                                keyboard::NamedKey::Enter => {
-                                    content.insert(cursor.idx, '\n');
-                                    cursor.move_right(&content);
-                                }
-                                _ => return,
-                            },
-                            keyboard::Key::Character(characters) => {
-                                for char in characters.chars() {
-                                    content.insert(cursor.idx, char);
-                                    cursor.move_right(&content);
-                                }
-                            }
-                            _ => return,
+                WindowEvent::ModifiersChanged(modifiers) => {
+                    editor.modifiers = modifiers.state();
+                }
+                WindowEvent::CursorMoved { position, .. } => {
+                    editor.handle_mouse_move(
+                        position.x as f32,
+                        window.inner_size().height as f32 - position.y as f32,
+                    );
+                }
+                WindowEvent::MouseInput { state, button, .. } => {
+                    editor.handle_mouse_input(state, button);
+                }
+                WindowEvent::MouseWheel { delta, .. } => {
+                    let rows = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => -y * WHEEL_ROWS,
+                        MouseScrollDelta::PixelDelta(position) => {
+                            -position.y as f32 / editor.layout.line_height()
                         }
-                    }
+                    };
+
+                    editor.handle_mouse_wheel(rows);
+                }
+                WindowEvent::KeyboardInput { event, .. }
+                    if event.state == ElementState::Pressed =>
+                {
+                    editor.handle_key(&event);
                 }
                 WindowEvent::RedrawRequested => {
-                    let mut target = display.draw();
-
-                    target.clear_color(2.0 / 255.0, 2.0 / 255.0, 2.0 / 255.0, 1.0);
-                    // target.clear_color(1.0, 1.0, 1.0, 1.0);
-
-                    let uniforms = uniform! {
-                        matrix: [
-                            [1.0, 0.0, 0.0, 0.0],
-                            [0.0, 1.0, 0.0, 0.0],
-                            [0.0, 0.0, 1.0, 0.0],
-                            [0.0 ,0.0, 0.0, 1.0f32],
-                        ],
-                        tex: &bitmap.texture,
-                    };
+                    let started = Instant::now();
 
-                    curr_cursor_x = lerp(curr_cursor_x, cursor.cursor_x as f32, 0.1);
-                    curr_cursor_y = lerp(curr_cursor_y, cursor.cursor_y as f32, 0.1);
+                    let backend: &mut dyn Backend = match &mut output {
+                        Output::Wgpu(gpu) => gpu.as_mut(),
+                        Output::Glium(application) => application.as_mut(),
+                    };
 
-                    let shape = renderer.render(
-                        &mut content,
-                        padding,
-                        window.inner_size().height as f32 - bitmap.ascent - padding,
-                        &window,
+                    draw_frame(
+                        &mut editor,
+                        &renderer,
+                        backend,
+                        window.inner_size(),
+                        &stats,
+                        started,
                     );
 
-                    let vertex_buffer = glium::VertexBuffer::new(&display, &shape).unwrap();
-
-                    target
-                        .draw(
-                            &vertex_buffer,
-                            &indices,
-                            &program,
-                            &uniforms,
-                            &Default::default(),
-                        )
-                        .unwrap();
-
-                    let text = vec![
-                        content.len().to_string(),
-                        cursor.cursor_x.to_string(),
-                        cursor.cursor_y.to_string(),
-                    ];
-
-                    let counter_shape =
-                        renderer.render(&mut text.join(" "), padding, padding, &window);
-
-                    let counter_vertex_buffer =
-                        VertexBuffer::new(&display, &counter_shape).unwrap();
-
-                    target
-                        .draw(
-                            &counter_vertex_buffer,
-                            &indices,
-                            &program,
-                            &uniforms,
-                            &Default::default(),
-                        )
-                        .unwrap();
-
-                    let cursor_rect = Rectangle {
-                        bottom: window.inner_size().height as f32 - bitmap.ascent + bitmap.descent
-                            - padding
-                            - (curr_cursor_y * 24.0),
-                        left: padding + (curr_cursor_x * 12.0),
-                        height: 24.0,
-                        width: 2.0,
-                    };
+                    match &mut output {
+                        Output::Wgpu(gpu) => {
+                            gpu.finish();
+                            stats.record(started);
+                        }
+                        Output::Glium(application) => {
+                            let mut target = application.display.draw();
+                            application.finish(&mut target);
 
-                    let cursor_shape = ColorVertex::from(cursor_rect, [1.0, 1.0, 1.0]);
+                            // timed before the swap, which waits on the display
+                            stats.record(started);
 
-                    let ascent_rect: Rectangle = Rectangle {
-                        bottom: window.inner_size().height as f32 - bitmap.ascent + bitmap.ascent
-                            - padding,
-                        left: padding,
-                        height: 1.0,
-                        width: 200.0,
-                    };
+                            target.finish().unwrap();
+                        }
+                    }
+                }
+                WindowEvent::Resized(window_size) => {
+                    match &mut output {
+                        Output::Wgpu(gpu) => gpu.resize(window_size),
+                        Output::Glium(application) => {
+                            application.display.resize(window_size.into())
+                        }
+                    }
+                    editor.damage.invalidate();
+                }
+                WindowEvent::CloseRequested => control_flow.exit(),
+                WindowEvent::Focused(_)
+                | WindowEvent::Occluded(false)
+                | WindowEvent::ScaleFactorChanged { .. } => editor.damage.invalidate(),
+                _ => (),
+            },
+            Event::AboutToWait => {
+                editor.poll();
+
+                let now = Instant::now();
+
+                if editor.damage.take(now) {
+                    window.request_redraw();
+                }
+
+                // sleeps until the next scheduled frame, or until input or background work
+                // wakes it up
+                control_flow.set_control_flow(match editor.damage.deadline() {
+                    Some(deadline) => ControlFlow::WaitUntil(deadline),
+                    None => ControlFlow::Wait,
+                });
+            }
+            _ => (),
+        })
+        .unwrap();
+}
+
+// what draws into the window
+enum Output<'a> {
+    Wgpu(Box<Gpu<'a>>),
+    Glium(Box<Application<'a>>),
+}
+
+// a window with wgpu drawing into it, or glium if that's asked for or wgpu has no adapter
+fn open_window<'a>(
+    event_loop: &EventLoop<()>,
+    font: &'a BitmapFont,
+    api: Option<&str>,
+) -> (Arc<Window>, Output<'a>) {
+    if api != Some("glium") {
+        // hidden until it's known something can draw into it
+        let window = WindowBuilder::new()
+            .with_inner_size(PhysicalSize::new(WINDOW_SIZE.0, WINDOW_SIZE.1))
+            .with_title("Rite")
+            .with_visible(false)
+            .build(event_loop)
+            .expect("Unable to create window");
+        let window = Arc::new(window);
+
+        if let Some(gpu) = Gpu::windowed(window.clone(), font) {
+            window.set_visible(true);
+            return (window, Output::Wgpu(Box::new(gpu)));
+        }
+
+        eprintln!("no wgpu adapter, drawing with glium instead");
+    }
+
+    let (window, display) = glium::backend::glutin::SimpleWindowBuilder::new()
+        .with_inner_size(WINDOW_SIZE.0, WINDOW_SIZE.1)
+        .with_title("Rite")
+        .build(event_loop);
+
+    (
+        Arc::new(window),
+        Output::Glium(Box::new(Application::new(display, font))),
+    )
+}
+
+// a cursor drawn in `cell`
+fn cursor_shape(style: CursorStyle, cell: Rectangle, color: [f32; 3]) -> Vec<ColorVertex> {
+    let edges = match style {
+        CursorStyle::Block => vec![cell],
+        CursorStyle::Bar => vec![Rectangle { width: 2.0, ..cell }],
+        CursorStyle::Underline => vec![Rectangle {
+            height: 2.0,
+            ..cell
+        }],
+        CursorStyle::Hollow => vec![
+            Rectangle {
+                height: 1.0,
+                ..cell
+            },
+            Rectangle {
+                bottom: cell.bottom + cell.height - 1.0,
+                height: 1.0,
+                ..cell
+            },
+            Rectangle { width: 1.0, ..cell },
+            Rectangle {
+                left: cell.left + cell.width - 1.0,
+                width: 1.0,
+                ..cell
+            },
+        ],
+    };
+
+    edges
+        .into_iter()
+        .flat_map(|x| ColorVertex::from(x, color))
+        .collect()
+}
+
+// the part of `a` inside `b`, empty if they don't overlap
+fn intersect(a: Rectangle, b: Rectangle) -> Rectangle {
+    let left = a.left.max(b.left);
+    let bottom = a.bottom.max(b.bottom);
+
+    Rectangle {
+        bottom,
+        left,
+        height: ((a.bottom + a.height).min(b.bottom + b.height) - bottom).max(0.0),
+        width: ((a.left + a.width).min(b.left + b.width) - left).max(0.0),
+    }
+}
+
+// the top `shown` part of a popup, which unfolds downwards as it opens
+fn unfold(rect: Rectangle, shown: f32) -> Rectangle {
+    Rectangle {
+        bottom: rect.bottom + rect.height * (1.0 - shown),
+        height: rect.height * shown,
+        ..rect
+    }
+}
+
+// lays out and draws everything on screen into `backend` with animations where they are at
+// `started`, `stats` are shown when the editor asks for them
+fn draw_frame(
+    editor: &mut Editor,
+    renderer: &TextRenderer,
+    backend: &mut dyn Backend,
+    size: PhysicalSize<u32>,
+    stats: &FrameStats,
+    started: Instant,
+) {
+    let layout = &renderer.layout;
+    let bitmap = renderer.bitmap;
+    let padding = text::PADDING;
+
+    layout.next_frame();
+
+    backend.clear(BACKGROUND);
+    // backend.clear([1.0, 1.0, 1.0]);
+
+    let window_rect = Rectangle {
+        bottom: 0.0,
+        left: 0.0,
+        height: size.height as f32,
+        width: size.width as f32,
+    };
+
+    let line_height = layout.line_height();
+
+    let status_rect = Rectangle {
+        height: line_height + padding,
+        ..window_rect
+    };
+
+    // top of the pane area, below the tab bar
+    let top = size.height as f32 - TAB_BAR_HEIGHT;
+
+    editor.panel = Rectangle {
+        bottom: status_rect.height,
+        left: 0.0,
+        height: if editor.problems.visible {
+            ((problems::ROWS + 1) as f32 * problems::ROW_HEIGHT)
+                .min((top - status_rect.height).max(0.0))
+        } else {
+            0.0
+        },
+        width: size.width as f32,
+    };
+
+    let bottom = editor.panel.bottom + editor.panel.height;
+
+    editor.area = Rectangle {
+        bottom,
+        left: 0.0,
+        height: (top - bottom).max(0.0),
+        width: size.width as f32,
+    };
+
+    if editor.explorer.visible {
+        editor.sidebar = Rectangle {
+            width: SIDEBAR_WIDTH.min(editor.area.width),
+            ..editor.area
+        };
+        editor.area.left += editor.sidebar.width;
+        editor.area.width -= editor.sidebar.width;
+    }
+
+    let viewports = editor.workspace.layout.viewports(editor.area);
+
+    if let Some((_, rect)) = viewports
+        .iter()
+        .find(|(id, _)| *id == editor.workspace.focused)
+    {
+        let visible_lines = ((rect.height - padding * 2.0) / line_height) as usize;
+
+        editor
+            .workspace
+            .active_mut()
+            .scroll_to_cursor(visible_lines);
+    }
+
+    let mut tab_bar_shape = ColorVertex::from(
+        Rectangle {
+            bottom: top,
+            left: 0.0,
+            height: TAB_BAR_HEIGHT,
+            width: size.width as f32,
+        },
+        [0.08, 0.08, 0.08],
+    );
+
+    let mut tab_labels = Vec::new();
+    let mut tab_left = 0.0;
+
+    for (idx, tab) in editor.workspace.buffers.iter().enumerate() {
+        let label = if tab.is_dirty() {
+            format!("{} *", tab.name)
+        } else {
+            tab.name.clone()
+        };
+
+        let tab_rect = Rectangle {
+            bottom: top,
+            left: tab_left,
+            height: TAB_BAR_HEIGHT,
+            width: renderer.width(&label) + TAB_PADDING * 2.0,
+        };
+
+        if idx == editor.workspace.active {
+            tab_bar_shape.extend(ColorVertex::from(tab_rect, [0.2, 0.2, 0.2]));
+        }
+
+        tab_labels.extend(renderer.render(
+            &label,
+            tab_rect,
+            TAB_PADDING,
+            (TAB_BAR_HEIGHT - line_height) / 2.0 + bitmap.ascent,
+        ));
 
-                    let ascent_shape = ColorVertex::from(ascent_rect, [1.0, 0.0, 0.0]);
+        tab_left += tab_rect.width;
+    }
 
-                    let baseline_rect: Rectangle = Rectangle {
-                        bottom: window.inner_size().height as f32 - bitmap.ascent - padding,
-                        left: padding,
-                        height: 1.0,
-                        width: 200.0,
+    // pane borders
+    for border in editor.workspace.layout.borders(editor.area) {
+        tab_bar_shape.extend(ColorVertex::from(border.rect, [0.2, 0.2, 0.2]));
+    }
+
+    backend.fill(&tab_bar_shape, None);
+
+    backend.text(&tab_labels, None, editor.subpixel);
+
+    if editor.explorer.visible {
+        let sidebar = editor.sidebar;
+        let sidebar_top = sidebar.bottom + sidebar.height;
+
+        editor
+            .explorer
+            .selection
+            .scroll_to_selected((sidebar.height / explorer::ROW_HEIGHT) as usize);
+
+        let mut sidebar_shape = ColorVertex::from(sidebar, [0.05, 0.05, 0.05]);
+
+        sidebar_shape.extend(ColorVertex::from(
+            Rectangle {
+                left: sidebar.left + sidebar.width - 1.0,
+                width: 1.0,
+                ..sidebar
+            },
+            [0.2, 0.2, 0.2],
+        ));
+
+        let explorer = &editor.explorer;
+
+        let selection = &explorer.selection;
+
+        if selection.selected >= selection.scroll {
+            let row = (selection.selected - selection.scroll) as f32;
+
+            sidebar_shape.extend(ColorVertex::from(
+                Rectangle {
+                    bottom: sidebar_top - (row + 1.0) * explorer::ROW_HEIGHT,
+                    height: explorer::ROW_HEIGHT,
+                    ..sidebar
+                },
+                if explorer.focused {
+                    [0.15, 0.2, 0.3]
+                } else {
+                    [0.15, 0.15, 0.15]
+                },
+            ));
+        }
+
+        let clip = Some(sidebar);
+
+        backend.fill(&sidebar_shape, clip);
+
+        let mut sidebar_labels = Vec::new();
+
+        for (row, entry) in explorer.entries.iter().skip(selection.scroll).enumerate() {
+            if row as f32 * explorer::ROW_HEIGHT > sidebar.height {
+                break;
+            }
+
+            let marker = match (entry.is_dir, entry.expanded) {
+                (true, true) => "- ",
+                (true, false) => "+ ",
+                (false, _) => "  ",
+            };
+
+            sidebar_labels.extend(renderer.render(
+                &format!("{}{}", marker, entry.name),
+                sidebar,
+                8.0 + entry.depth as f32 * 16.0,
+                row as f32 * explorer::ROW_HEIGHT
+                    + (explorer::ROW_HEIGHT - line_height) / 2.0
+                    + bitmap.ascent,
+            ));
+        }
+
+        backend.text(&sidebar_labels, clip, editor.subpixel);
+    }
+
+    if editor.problems.visible {
+        let panel = editor.panel;
+        let panel_top = panel.bottom + panel.height;
+
+        editor.problems.selection.scroll_to_selected(problems::ROWS);
+
+        let problems = &editor.problems;
+        let all = editor.diagnostics.all();
+
+        let mut panel_shape = ColorVertex::from(panel, [0.05, 0.05, 0.05]);
+
+        panel_shape.extend(ColorVertex::from(
+            Rectangle {
+                bottom: panel_top - 1.0,
+                height: 1.0,
+                ..panel
+            },
+            [0.2, 0.2, 0.2],
+        ));
+
+        let mut panel_labels = renderer.render(
+            &format!(
+                "Problems  {} errors  {} warnings",
+                editor.diagnostics.count(Severity::Error),
+                editor.diagnostics.count(Severity::Warning)
+            ),
+            panel,
+            8.0,
+            (problems::ROW_HEIGHT - line_height) / 2.0 + bitmap.ascent,
+        );
+
+        for (row, (path, diagnostic)) in all
+            .iter()
+            .skip(problems.selection.scroll)
+            .take(problems::ROWS)
+            .enumerate()
+        {
+            let row_bottom = panel_top - (row + 2) as f32 * problems::ROW_HEIGHT;
+
+            if problems.selection.is_selected(row) {
+                panel_shape.extend(ColorVertex::from(
+                    Rectangle {
+                        bottom: row_bottom,
+                        height: problems::ROW_HEIGHT,
+                        ..panel
+                    },
+                    if problems.focused {
+                        [0.15, 0.2, 0.3]
+                    } else {
+                        [0.15, 0.15, 0.15]
+                    },
+                ));
+            }
+
+            panel_shape.extend(diagnostic.severity.icon(Rectangle {
+                bottom: row_bottom + (problems::ROW_HEIGHT - GUTTER_ICON_SIZE) / 2.0,
+                left: panel.left + 8.0,
+                height: GUTTER_ICON_SIZE,
+                width: GUTTER_ICON_SIZE,
+            }));
+
+            panel_labels.extend(renderer.render(
+                &format!(
+                    "{}:{}:{}  {}",
+                    path.strip_prefix(&editor.root).unwrap_or(path).display(),
+                    diagnostic.range.start.line + 1,
+                    diagnostic.range.start.character + 1,
+                    diagnostic.message.lines().next().unwrap_or_default()
+                ),
+                panel,
+                8.0 + GUTTER_ICON_SIZE + 8.0,
+                (row + 1) as f32 * problems::ROW_HEIGHT
+                    + (problems::ROW_HEIGHT - line_height) / 2.0
+                    + bitmap.ascent,
+            ));
+        }
+
+        let clip = Some(panel);
+
+        backend.fill(&panel_shape, clip);
+
+        backend.text(&panel_labels, clip, editor.subpixel);
+    }
+
+    // the cursor stays on while typing and then blinks, a blinking cursor is motion too
+    let cursor_on = match editor
+        .settings
+        .cursor_blink
+        .filter(|_| !editor.motion.reduced)
+    {
+        Some(blink) => {
+            let phase =
+                started.saturating_duration_since(editor.typed).as_nanos() / blink.as_nanos();
+
+            editor
+                .damage
+                .schedule(editor.typed + blink * (phase as u32 + 1));
+
+            phase.is_multiple_of(2)
+        }
+        None => true,
+    };
+
+    // message and top left corner of the diagnostic under the mouse
+    let mut tooltip = None;
+    // start of the completed word, x and bottom of its line
+    let mut completion_anchor = None;
+
+    editor.fold_markers.clear();
+    editor.workspace.follow_edits();
+
+    for (id, buffer, cursor, scroll) in editor.workspace.views() {
+        let Some((_, rect)) = viewports.iter().find(|(pane, _)| *pane == id) else {
+            continue;
+        };
+
+        // keeps panes from drawing over each other
+        let clip = Some(*rect);
+
+        let baseline = rect.bottom + rect.height - padding - bitmap.ascent;
+
+        // the text and what's drawn along with it are placed in the document and moved up by
+        // the view, which eases towards the scrolled to line. whole pixels, so glyphs stay on
+        // the pixel grid
+        let scrolled = editor
+            .motion
+            .scroll(id, renderer.scroll_offset(buffer, scroll), started)
+            .round();
+
+        editor.scrolled.insert(id, scrolled);
+        backend.view([0.0, scrolled]);
+
+        // drawn from the line at the top of the view, which is partly scrolled past while the
+        // view moves and shows part of one more line at the bottom
+        let scroll = buffer.folds.line_at((scrolled / line_height) as usize, 0);
+        let document_baseline = baseline - renderer.scroll_offset(buffer, scroll);
+        let visible = ((rect.height - padding) / line_height) as usize + 2;
+
+        // the selection under the text, line by line
+        if let Some(selection) = buffer
+            .selection()
+            .filter(|_| id == editor.workspace.focused)
+        {
+            let content = &buffer.content;
+            let mut selection_shapes = Vec::new();
+
+            let mut line = content[..selection.start].matches('\n').count();
+            let mut line_start = content[..selection.start].rfind('\n').map_or(0, |x| x + 1);
+
+            loop {
+                let line_end = content[line_start..]
+                    .find('\n')
+                    .map_or(content.len(), |x| line_start + x);
+
+                let row = buffer.folds.row(line, scroll);
+
+                if row.is_some_and(|x| x >= visible) {
+                    break;
+                }
+
+                if let Some(row) = row {
+                    let placed = layout.line(&content[line_start..line_end]);
+                    let from = selection.start.max(line_start) - line_start;
+                    let to = selection.end.min(line_end) - line_start;
+
+                    let mut spans = placed.spans(from..to);
+
+                    // a selected line break shows as one more cell
+                    if selection.end > line_end {
+                        let end = placed.x(placed.len);
+                        spans.push((end, end + layout.cell()));
+                    }
+
+                    for (left, right) in spans {
+                        selection_shapes.extend(ColorVertex::from(
+                            Rectangle {
+                                bottom: document_baseline + bitmap.descent
+                                    - row as f32 * line_height,
+                                left: rect.left + padding + left,
+                                height: line_height,
+                                width: right - left,
+                            },
+                            [0.2, 0.3, 0.45],
+                        ));
+                    }
+                }
+
+                if line_end >= selection.end {
+                    break;
+                }
+
+                line += 1;
+                line_start = line_end + 1;
+            }
+
+            backend.fill(&selection_shapes, clip);
+        }
+
+        // snippet stops under the text, the current one stands out
+        if let Some(session) = buffer
+            .snippet
+            .as_ref()
+            .filter(|_| id == editor.workspace.focused)
+        {
+            let mut stop_shapes = Vec::new();
+
+            for (idx, stop) in session.stops.iter().enumerate() {
+                let color = if idx == session.current {
+                    [0.2, 0.25, 0.35]
+                } else {
+                    [0.12, 0.12, 0.16]
+                };
+
+                for range in &stop.ranges {
+                    let before = &buffer.content[..range.start];
+                    let line = before.matches('\n').count();
+                    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+                    let Some(row) = buffer.folds.row(line, scroll) else {
+                        continue;
                     };
 
-                    let baseline_shape = ColorVertex::from(baseline_rect, [0.0, 1.0, 0.0]);
+                    // ranges spanning lines are marked on their first line
+                    let line_text = buffer.line(line);
+                    let placed = layout.line(line_text);
+                    let from = range.start - line_start;
+                    let to = (range.end - line_start).min(line_text.len());
+
+                    // empty stops show as a thin mark at the caret
+                    let mut spans = placed.spans(from..to);
+                    if spans.is_empty() {
+                        spans.push((placed.x(from), placed.x(from)));
+                    }
+
+                    for (left, right) in spans {
+                        stop_shapes.extend(ColorVertex::from(
+                            Rectangle {
+                                bottom: document_baseline + bitmap.descent
+                                    - row as f32 * line_height,
+                                left: rect.left + padding + left,
+                                height: line_height,
+                                width: (right - left).max(2.0),
+                            },
+                            color,
+                        ));
+                    }
+                }
+            }
+
+            backend.fill(&stop_shapes, clip);
+        }
+
+        let shape = renderer.render_buffer(
+            buffer,
+            scroll,
+            scrolled,
+            *rect,
+            padding,
+            padding + bitmap.ascent,
+        );
+
+        backend.text(&shape, clip, editor.subpixel);
+
+        // gutter markers pointing down at open regions and right at folded ones
+        let mut marker_shapes = Vec::new();
+
+        for region in &buffer.folds.regions {
+            let Some(row) = buffer
+                .folds
+                .row(region.start, scroll)
+                .filter(|x| *x < visible)
+            else {
+                continue;
+            };
+
+            let marker_rect = Rectangle {
+                bottom: document_baseline + bitmap.descent - row as f32 * line_height
+                    + (line_height - FOLD_MARKER_SIZE) / 2.0,
+                left: rect.left + padding / 2.0 + (padding / 2.0 - FOLD_MARKER_SIZE) / 2.0,
+                height: FOLD_MARKER_SIZE,
+                width: FOLD_MARKER_SIZE,
+            };
+
+            let vertex = |x: f32, y: f32| ColorVertex {
+                position: [
+                    marker_rect.left + x * marker_rect.width,
+                    marker_rect.bottom + y * marker_rect.height,
+                ],
+                color: [0.5, 0.5, 0.5],
+            };
+
+            if buffer.folds.folded_at(region.start).is_some() {
+                marker_shapes.extend([
+                    vertex(0.0, 1.0),
+                    vertex(1.0, 0.5),
+                    vertex(0.0, 0.0),
+                    vertex(0.0, 0.0),
+                ]);
+            } else {
+                marker_shapes.extend([
+                    vertex(0.0, 1.0),
+                    vertex(1.0, 1.0),
+                    vertex(0.5, 0.0),
+                    vertex(0.5, 0.0),
+                ]);
+            }
+
+            // clicked in the window
+            editor.fold_markers.push((
+                Rectangle {
+                    bottom: marker_rect.bottom + scrolled,
+                    ..marker_rect
+                },
+                id,
+                region.start,
+            ));
+        }
+
+        backend.fill(&marker_shapes, clip);
+
+        if let Some(path) = &buffer.path {
+            let diagnostics = editor.diagnostics.for_file(path);
+            let lines: Vec<&str> = buffer.content.split('\n').collect();
+
+            let mut gutter_shapes = Vec::new();
+            let mut squiggle_shapes = Vec::new();
+
+            // byte offset of a char column on a line
+            let column_offset = |line: &str, column: usize| {
+                line.char_indices().nth(column).map_or(line.len(), |x| x.0)
+            };
+
+            for diagnostic in &diagnostics {
+                let (start, end) = diagnostic.columns(&buffer.content);
+
+                for line in start.0.max(scroll)..=end.0 {
+                    let Some(text) = lines.get(line) else {
+                        break;
+                    };
 
-                    let descent_rect: Rectangle = Rectangle {
-                        bottom: window.inner_size().height as f32 - bitmap.ascent + bitmap.descent
-                            - padding,
-                        left: padding,
-                        height: 1.0,
-                        width: 200.0,
+                    let Some(row) = buffer.folds.row(line, scroll) else {
+                        continue;
                     };
 
-                    let descent_shape = ColorVertex::from(descent_rect, [0.0, 0.0, 1.0]);
+                    if row >= visible {
+                        break;
+                    }
+
+                    let from = if line == start.0 { start.1 } else { 0 };
+                    let to = if line == end.0 {
+                        end.1
+                    } else {
+                        text.chars().count()
+                    };
 
-                    let mut combined_shape = [cursor_shape].concat();
+                    let line_baseline = document_baseline - row as f32 * line_height;
+                    let placed = layout.line(text);
+                    let from = column_offset(text, from);
 
-                    let combined_shape_iter = combined_shape.iter_mut();
+                    // past the end of the line is underlined for one cell
+                    let mut spans = placed.spans(from..column_offset(text, to));
+                    if spans.is_empty() {
+                        spans.push((placed.x(from), placed.x(from)));
+                    }
 
-                    for vert in combined_shape_iter {
-                        scalable::rescale_position(
-                            vert,
-                            window.inner_size().height as f32,
-                            window.inner_size().width as f32,
-                        );
+                    for (left, right) in spans {
+                        squiggle_shapes.extend(SquiggleVertex::from(
+                            Rectangle {
+                                bottom: line_baseline + bitmap.descent,
+                                left: rect.left + padding + left,
+                                height: 4.0,
+                                width: (right - left).max(layout.cell()),
+                            },
+                            diagnostic.severity.color(),
+                        ));
                     }
+                }
+            }
+
+            // a line's icon shows its most severe diagnostic, once however many share the line
+            let mut icon_lines: Vec<usize> =
+                diagnostics.iter().map(|x| x.range.start.line).collect();
+            icon_lines.sort_unstable();
+            icon_lines.dedup();
+
+            for line in icon_lines {
+                let Some(row) = buffer.folds.row(line, scroll).filter(|x| *x < visible) else {
+                    continue;
+                };
+                let Some(severity) = diagnostics
+                    .iter()
+                    .filter(|x| x.range.start.line == line)
+                    .map(|x| x.severity)
+                    .min()
+                else {
+                    continue;
+                };
+
+                let line_bottom = document_baseline + bitmap.descent - row as f32 * line_height;
+
+                // the left half of the gutter, fold markers take the right
+                gutter_shapes.extend(severity.icon(Rectangle {
+                    bottom: line_bottom + (line_height - GUTTER_ICON_SIZE) / 2.0,
+                    left: rect.left + (padding / 2.0 - GUTTER_ICON_SIZE) / 2.0,
+                    height: GUTTER_ICON_SIZE,
+                    width: GUTTER_ICON_SIZE,
+                }));
+            }
 
-                    let vertex_buffer_cursor =
-                        glium::VertexBuffer::new(&display, &combined_shape).unwrap();
+            // the mouse over a squiggle shows its message
+            let (mouse_x, mouse_y) = editor.mouse;
+            let text_top = rect.bottom + rect.height - padding;
 
-                    target
-                        .draw(
-                            &vertex_buffer_cursor,
-                            &indices,
-                            &color_program,
-                            &glium::uniforms::EmptyUniforms,
-                            &Default::default(),
-                        )
-                        .unwrap();
+            if editor.picker.is_none()
+                && mouse_x >= rect.left + padding
+                && mouse_x <= rect.left + rect.width
+                && mouse_y >= rect.bottom
+                && mouse_y <= text_top
+            {
+                // rows from the top of the document
+                let row = ((text_top - mouse_y + scrolled) / line_height) as usize;
+                let line = buffer.folds.line_at(row, 0);
+                let text = lines.get(line).copied().unwrap_or_default();
+                let offset = layout
+                    .line(text)
+                    .char_at(mouse_x - rect.left - padding)
+                    .unwrap_or(text.len());
+                let column = text[..offset].chars().count();
 
-                    target.finish().unwrap();
+                if let Some(diagnostic) = editor.diagnostics.at(path, &buffer.content, line, column)
+                {
+                    tooltip = Some((
+                        diagnostic.message.clone(),
+                        mouse_x,
+                        text_top - (row + 1) as f32 * line_height + scrolled,
+                    ));
                 }
-                WindowEvent::Resized(window_size) => display.resize(window_size.into()),
-                WindowEvent::CloseRequested => control_flow.exit(),
-                _ => (),
+            }
+
+            backend.fill(&gutter_shapes, clip);
+
+            backend.squiggle(&squiggle_shapes, clip);
+        }
+
+        // cursors over everything else in the pane, rows counted from the top of the document
+        let mut cursor_shapes = Vec::new();
+        // block cursors and the lines they're on, their chars are drawn again inverted
+        let mut blocks = Vec::new();
+
+        // where a cursor on `line` at `offset` is drawn, as wide as the char it's on
+        let cell = |line: usize, offset: usize, x: f32, row: f32| {
+            let text = buffer.line(line);
+            // past the end of the line, or an offset that isn't on a char, is one cell wide
+            let width = match text.get(offset..).and_then(|x| x.chars().next()) {
+                Some(char) => layout.x(text, offset + char.len_utf8()) - layout.x(text, offset),
+                None => layout.cell(),
+            };
+
+            Rectangle {
+                bottom: baseline + bitmap.descent - row * line_height,
+                left: rect.left + padding + x,
+                height: line_height,
+                width,
+            }
+        };
+
+        if id == editor.workspace.focused {
+            let style = editor.settings.cursor_style(&editor.mode);
+            let cursor_x = layout.x(buffer.line(cursor.cursor_y), cursor.cursor_x);
+            let row = buffer.folds.row(cursor.cursor_y, 0).unwrap_or_default();
+            let (x, row) = editor.motion.cursor((cursor_x, row as f32), started);
+
+            let mut cursors = vec![(cursor, x, row, [1.0, 1.0, 1.0])];
+
+            // extra cursors don't animate
+            for extra in &buffer.cursors {
+                let Some(row) = buffer.folds.row(extra.cursor_y, 0) else {
+                    continue;
+                };
+
+                let x = layout.x(buffer.line(extra.cursor_y), extra.cursor_x);
+                cursors.push((extra, x, row as f32, [0.8, 0.8, 0.8]));
+            }
+
+            for (cursor, x, row, color) in cursors.into_iter().filter(|_| cursor_on) {
+                let cell = cell(cursor.cursor_y, cursor.cursor_x, x, row);
+
+                cursor_shapes.extend(cursor_shape(style, cell, color));
+
+                if style == CursorStyle::Block {
+                    blocks.push((cell, cursor.cursor_y));
+                }
+            }
+        } else if let Some(row) = buffer.folds.row(cursor.cursor_y, 0) {
+            let x = layout.x(buffer.line(cursor.cursor_y), cursor.cursor_x);
+            let cell = cell(cursor.cursor_y, cursor.cursor_x, x, row as f32);
+
+            cursor_shapes.extend(cursor_shape(CursorStyle::Bar, cell, [0.4, 0.4, 0.4]));
+        }
+
+        backend.fill(&cursor_shapes, clip);
+
+        // the line again in the background color, only where it's under a block
+        backend.text_color(BACKGROUND);
+
+        for (cell, line) in blocks {
+            let row = buffer.folds.row(line, 0).unwrap_or_default();
+            let document = Rectangle {
+                bottom: rect.bottom - scrolled,
+                ..*rect
+            };
+
+            let shape = renderer.render(
+                buffer.line(line),
+                document,
+                padding,
+                padding + bitmap.ascent + row as f32 * line_height - scrolled,
+            );
+
+            let cell = Rectangle {
+                bottom: cell.bottom + scrolled,
+                ..cell
+            };
+
+            backend.text(&shape, Some(intersect(cell, *rect)), editor.subpixel);
+        }
+
+        backend.text_color(TEXT_COLOR);
+
+        backend.view([0.0, 0.0]);
+
+        if let Some(popup) = editor
+            .completion
+            .as_ref()
+            .filter(|_| id == editor.workspace.focused)
+        {
+            let before = &buffer.content[..popup.start.min(buffer.content.len())];
+            let line = before.matches('\n').count();
+            let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+
+            if let Some(row) = buffer.folds.row(line, scroll) {
+                completion_anchor = Some((
+                    rect.left + padding + layout.x(buffer.line(line), before.len() - line_start),
+                    document_baseline + bitmap.descent - row as f32 * line_height + scrolled,
+                ));
+            }
+        }
+    }
+
+    // prompts and messages take the place of the segments on the left
+    let left = match editor.status_message() {
+        Some(message) => vec![message],
+        None => editor.status.texts(editor, Side::Left),
+    };
+
+    let mut status_labels = Vec::new();
+    let mut left_end = padding;
+
+    for text in &left {
+        status_labels.extend(renderer.render(text, status_rect, left_end, line_height));
+        left_end += renderer.width(text) + STATUS_GAP;
+    }
+
+    // from the right edge in, as many as fit next to the left ones
+    let mut right_start = status_rect.width - padding;
+
+    for text in editor.status.texts(editor, Side::Right).iter().rev() {
+        let left = right_start - renderer.width(text);
+
+        if left < left_end {
+            break;
+        }
+
+        status_labels.extend(renderer.render(text, status_rect, left, line_height));
+        right_start = left - STATUS_GAP;
+    }
+
+    backend.fill(&ColorVertex::from(status_rect, [0.08, 0.08, 0.08]), None);
+    backend.text(&status_labels, Some(status_rect), editor.subpixel);
+
+    if let Some((message, x, y)) = tooltip {
+        let lines: Vec<&str> = message.lines().collect();
+
+        let width = lines
+            .iter()
+            .map(|line| renderer.width(line))
+            .fold(0.0, f32::max)
+            + TOOLTIP_PADDING * 2.0;
+        let height = lines.len() as f32 * line_height + TOOLTIP_PADDING * 2.0;
+
+        let tooltip_rect = Rectangle {
+            // below the hovered line, or above it if there's no room
+            bottom: if y - height >= 0.0 {
+                y - height
+            } else {
+                y + line_height
             },
-            Event::AboutToWait => {
-                window.request_redraw();
+            left: x.min(size.width as f32 - width).max(0.0),
+            height,
+            width,
+        };
+
+        let mut tooltip_shape = ColorVertex::from(tooltip_rect, [0.2, 0.2, 0.2]);
+
+        tooltip_shape.extend(ColorVertex::from(
+            Rectangle {
+                left: tooltip_rect.left + 1.0,
+                bottom: tooltip_rect.bottom + 1.0,
+                width: tooltip_rect.width - 2.0,
+                height: tooltip_rect.height - 2.0,
+            },
+            [0.1, 0.1, 0.1],
+        ));
+
+        let tooltip_labels = renderer.render(
+            &message,
+            tooltip_rect,
+            TOOLTIP_PADDING,
+            TOOLTIP_PADDING + bitmap.ascent,
+        );
+
+        backend.fill(&tooltip_shape, None);
+
+        backend.text(&tooltip_labels, None, editor.subpixel);
+    }
+
+    let shown = editor.motion.overlay(
+        Overlay::Completion,
+        editor.completion.is_some() && completion_anchor.is_some(),
+        started,
+    );
+
+    if let (Some(popup), Some((anchor_x, anchor_y))) = (&mut editor.completion, completion_anchor) {
+        let rows = completion::ROWS.min(popup.picker.matches.len());
+
+        popup.picker.selection.scroll_to_selected(rows);
+
+        let picker = &popup.picker;
+        let visible: Vec<_> = picker
+            .matches
+            .iter()
+            .skip(picker.selection.scroll)
+            .take(rows)
+            .collect();
+
+        let width = visible
+            .iter()
+            .map(|(idx, _)| {
+                let item = &picker.items[*idx];
+                renderer.width(&item.label) + renderer.width(&item.detail) + TOOLTIP_PADDING * 4.0
+            })
+            .fold(COMPLETION_WIDTH, f32::max)
+            .min(size.width as f32);
+        let height = rows as f32 * line_height;
+
+        let popup_rect = Rectangle {
+            // below the line, or above it if there's no room
+            bottom: if anchor_y - height >= 0.0 {
+                anchor_y - height
+            } else {
+                anchor_y + line_height
+            },
+            left: (anchor_x - TOOLTIP_PADDING)
+                .min(size.width as f32 - width)
+                .max(0.0),
+            height,
+            width,
+        };
+        let popup_top = popup_rect.bottom + popup_rect.height;
+
+        let mut popup_shape = ColorVertex::from(popup_rect, [0.1, 0.1, 0.1]);
+        let mut popup_labels = Vec::new();
+
+        for (row, (idx, matched)) in visible.iter().enumerate() {
+            let item = &picker.items[*idx];
+            let row_bottom = popup_top - (row + 1) as f32 * line_height;
+
+            if picker.selection.is_selected(row) {
+                popup_shape.extend(ColorVertex::from(
+                    Rectangle {
+                        bottom: row_bottom,
+                        height: line_height,
+                        ..popup_rect
+                    },
+                    [0.15, 0.2, 0.3],
+                ));
             }
-            _ => (),
-        })
-        .unwrap();
+
+            for position in &matched.positions {
+                let before: String = item.label.chars().take(*position).collect();
+                let char: String = item.label.chars().skip(*position).take(1).collect();
+
+                popup_shape.extend(ColorVertex::from(
+                    Rectangle {
+                        bottom: row_bottom + 2.0,
+                        left: popup_rect.left + TOOLTIP_PADDING + renderer.width(&before),
+                        height: 2.0,
+                        width: renderer.width(&char),
+                    },
+                    [0.9, 0.7, 0.2],
+                ));
+            }
+
+            let row_top = row as f32 * line_height + bitmap.ascent;
+
+            popup_labels.extend(renderer.render(&item.label, popup_rect, TOOLTIP_PADDING, row_top));
+
+            // the detail sits at the right edge
+            popup_labels.extend(renderer.render(
+                &item.detail,
+                popup_rect,
+                popup_rect.width - TOOLTIP_PADDING - renderer.width(&item.detail),
+                row_top,
+            ));
+        }
+
+        let mut draws = vec![(popup_rect, popup_shape, popup_labels)];
+
+        if let Some(documentation) = popup.selected().and_then(|x| x.documentation.as_ref()) {
+            let columns = ((DOCUMENTATION_WIDTH - TOOLTIP_PADDING * 2.0) / layout.cell()) as usize;
+            let lines = completion::wrap(documentation, columns);
+            let lines = &lines[..lines.len().min(DOCUMENTATION_LINES)];
+
+            let height = lines.len() as f32 * line_height + TOOLTIP_PADDING * 2.0;
+
+            // next to the list, on whichever side has room
+            let left =
+                if popup_rect.left + popup_rect.width + DOCUMENTATION_WIDTH <= size.width as f32 {
+                    popup_rect.left + popup_rect.width
+                } else {
+                    (popup_rect.left - DOCUMENTATION_WIDTH).max(0.0)
+                };
+
+            let documentation_rect = Rectangle {
+                bottom: (popup_top - height).max(0.0),
+                left,
+                height,
+                width: DOCUMENTATION_WIDTH,
+            };
+
+            let documentation_labels = renderer.render(
+                &lines.join("\n"),
+                documentation_rect,
+                TOOLTIP_PADDING,
+                TOOLTIP_PADDING + bitmap.ascent,
+            );
+
+            draws.push((
+                documentation_rect,
+                ColorVertex::from(documentation_rect, [0.13, 0.13, 0.13]),
+                documentation_labels,
+            ));
+        }
+
+        for (rect, shape, labels) in draws {
+            let clip = Some(unfold(rect, shown));
+
+            backend.fill(&shape, clip);
+
+            backend.text(&labels, clip, editor.subpixel);
+        }
+    }
+
+    let shown = editor
+        .motion
+        .overlay(Overlay::Picker, editor.picker.is_some(), started);
+
+    if let Some(picker) = &mut editor.picker {
+        let width = PICKER_WIDTH.min(size.width as f32 - padding * 2.0);
+        let rows = PICKER_ROWS.min(picker.matches.len());
+
+        picker.selection.scroll_to_selected(rows);
+
+        let overlay = Rectangle {
+            bottom: top - padding - line_height * (rows + 1) as f32,
+            left: (size.width as f32 - width) / 2.0,
+            height: line_height * (rows + 1) as f32,
+            width,
+        };
+
+        let mut overlay_shape = ColorVertex::from(overlay, [0.1, 0.1, 0.1]);
+
+        overlay_shape.extend(ColorVertex::from(
+            Rectangle {
+                bottom: overlay.bottom + overlay.height - line_height,
+                height: line_height,
+                ..overlay
+            },
+            [0.16, 0.16, 0.16],
+        ));
+
+        let mut overlay_labels = renderer.render(
+            &format!("{} > {}", picker.title, picker.query),
+            overlay,
+            padding / 2.0,
+            bitmap.ascent,
+        );
+
+        let visible = picker
+            .matches
+            .iter()
+            .skip(picker.selection.scroll)
+            .take(rows);
+
+        for (row, (idx, matched)) in visible.enumerate() {
+            let item = &picker.items[*idx];
+            let row_top = line_height * (row + 1) as f32;
+            let row_bottom = overlay.bottom + overlay.height - row_top - line_height;
+
+            if picker.selection.is_selected(row) {
+                overlay_shape.extend(ColorVertex::from(
+                    Rectangle {
+                        bottom: row_bottom,
+                        height: line_height,
+                        ..overlay
+                    },
+                    [0.15, 0.2, 0.3],
+                ));
+            }
+
+            // matched characters are underlined
+            for position in &matched.positions {
+                let before: String = item.label.chars().take(*position).collect();
+                let char: String = item.label.chars().skip(*position).take(1).collect();
+
+                overlay_shape.extend(ColorVertex::from(
+                    Rectangle {
+                        bottom: row_bottom + 2.0,
+                        left: overlay.left + padding / 2.0 + renderer.width(&before),
+                        height: 2.0,
+                        width: renderer.width(&char),
+                    },
+                    [0.9, 0.7, 0.2],
+                ));
+            }
+
+            let label = if item.detail.is_empty() {
+                item.label.clone()
+            } else {
+                format!("{}  {}", item.label, item.detail)
+            };
+
+            overlay_labels.extend(renderer.render(
+                &label,
+                overlay,
+                padding / 2.0,
+                row_top + bitmap.ascent,
+            ));
+        }
+
+        let clip = Some(unfold(overlay, shown));
+
+        backend.fill(&overlay_shape, clip);
+
+        backend.text(&overlay_labels, clip, editor.subpixel);
+    }
+
+    if editor.show_stats {
+        let text = stats.text(started);
+        let width = renderer.width(&text) + TOOLTIP_PADDING * 2.0;
+
+        let stats_rect = Rectangle {
+            bottom: top - line_height - TOOLTIP_PADDING * 2.0,
+            left: (size.width as f32 - width).max(0.0),
+            height: line_height + TOOLTIP_PADDING * 2.0,
+            width,
+        };
+
+        let stats_shape = ColorVertex::from(stats_rect, [0.1, 0.1, 0.1]);
+
+        let stats_labels = renderer.render(
+            &text,
+            stats_rect,
+            TOOLTIP_PADDING,
+            TOOLTIP_PADDING + bitmap.ascent,
+        );
+
+        backend.fill(&stats_shape, None);
+        backend.text(&stats_labels, None, editor.subpixel);
+    }
+
+    // keep drawing frames until everything settles
+    if editor.motion.running(started) {
+        editor.damage.schedule(started + FRAME_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path, sync::OnceLock};
+
+    use image::RgbaImage;
+    use winit::event::MouseButton;
+
+    use super::*;
+    use crate::{
+        diagnostics::Diagnostic,
+        lsp::{Position, Range},
+        utils::temp::TempDir,
+    };
+
+    // how far a channel may be off, float rounding isn't the same everywhere
+    const TOLERANCE: u8 = 2;
+
+    // `content` opened as sample.js from an empty directory, so nothing else on the machine
+    // shows up, and the renderer to draw it with. the directory is removed when the test drops it
+    fn open(content: &str) -> (Editor, TextRenderer<'static>, TempDir) {
+        // one font for every test, its atlas fills in with what they draw
+        static BITMAP: OnceLock<BitmapFont> = OnceLock::new();
+
+        let bitmap = BITMAP.get_or_init(|| BitmapFont::new(None));
+        let layout = Rc::new(Layout::new(bitmap));
+
+        let root = TempDir::new("golden");
+        let path = root.write("sample.js", content);
+
+        let workspace = Workspace::new(Buffer::open(&path).unwrap());
+        let mut editor = Editor::new(workspace, root.path().to_path_buf(), layout.clone());
+
+        // golden images show everything where it ends up
+        editor.motion.reduced = true;
+
+        (editor, TextRenderer::new(bitmap, layout), root)
+    }
+
+    fn open_sample() -> (Editor, TextRenderer<'static>, TempDir) {
+        open(&include_str!("./samples/sample.js").replace('\r', ""))
+    }
+
+    // draws the editor headlessly as it is at `at`
+    fn draw_at(editor: &mut Editor, renderer: &TextRenderer, at: Instant) -> RgbaImage {
+        let (width, height) = WINDOW_SIZE;
+        let mut backend = Software::new(renderer.bitmap, width, height);
+
+        draw_frame(
+            editor,
+            renderer,
+            &mut backend,
+            PhysicalSize::new(width, height),
+            &FrameStats::default(),
+            at,
+        );
+
+        backend.image
+    }
+
+    // draws the editor headlessly, once to lay out the panes and once with them in place
+    fn draw(editor: &mut Editor, renderer: &TextRenderer) -> RgbaImage {
+        draw_at(editor, renderer, Instant::now());
+        draw_at(editor, renderer, Instant::now())
+    }
+
+    // compares with tests/golden/<name>.png, RITE_UPDATE_GOLDEN=1 writes it instead
+    fn assert_golden(name: &str, image: &RgbaImage) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden");
+        let path = dir.join(format!("{}.png", name));
+
+        if std::env::var_os("RITE_UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(&dir).unwrap();
+            image.save(&path).unwrap();
+            return;
+        }
+
+        let expected = image::open(&path)
+            .unwrap_or_else(|err| panic!("{}: {}, run with RITE_UPDATE_GOLDEN=1", name, err))
+            .to_rgba8();
+
+        assert_eq!(expected.dimensions(), image.dimensions());
+
+        let differing = expected
+            .pixels()
+            .zip(image.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > TOLERANCE))
+            .count();
+
+        if differing > 0 {
+            let actual = dir.join(format!("{}.actual.png", name));
+            image.save(&actual).unwrap();
+
+            panic!(
+                "{} pixels differ from {}, see {}",
+                differing,
+                path.display(),
+                actual.display()
+            );
+        }
+    }
+
+    #[test]
+    fn sample_file() {
+        let (mut editor, renderer, _root) = open_sample();
+
+        assert_golden("sample_file", &draw(&mut editor, &renderer));
+    }
+
+    #[test]
+    fn selection_across_lines() {
+        let (mut editor, renderer, _root) = open_sample();
+
+        let buffer = editor.workspace.active_mut();
+        let start = buffer.content.find("add(a").unwrap();
+        let end = buffer.content.find("a + b").unwrap();
+
+        buffer.anchor = Some(start);
+        buffer.set_cursor(end);
+
+        assert_golden("selection_across_lines", &draw(&mut editor, &renderer));
+    }
+
+    #[test]
+    fn diagnostics_and_problems_panel() {
+        let (mut editor, renderer, _root) = open_sample();
+
+        let path = editor.workspace.active().path.clone().unwrap();
+        let diagnostic = |line, from, to, severity, message: &str| Diagnostic {
+            range: Range {
+                start: Position {
+                    line,
+                    character: from,
+                },
+                end: Position {
+                    line,
+                    character: to,
+                },
+            },
+            severity,
+            message: message.to_string(),
+            source: None,
+        };
+
+        editor.diagnostics.publish(
+            "test",
+            path,
+            vec![
+                diagnostic(0, 6, 9, Severity::Error, "`num` is never reassigned"),
+                diagnostic(6, 0, 7, Severity::Warning, "unexpected console statement"),
+            ],
+        );
+        editor.problems.visible = true;
+
+        assert_golden(
+            "diagnostics_and_problems_panel",
+            &draw(&mut editor, &renderer),
+        );
+    }
+
+    // text, squiggles and the cursor of a scrolled buffer all move along
+    #[test]
+    fn scrolled_buffer() {
+        let content: Vec<String> = (0..40).map(|x| format!("let line{} = {};", x, x)).collect();
+        let (mut editor, renderer, _root) = open(&content.join("\n"));
+
+        let buffer = editor.workspace.active_mut();
+        let line = buffer.content.find("let line30").unwrap();
+        buffer.set_cursor(line);
+
+        let path = buffer.path.clone().unwrap();
+        let position = |character| Position {
+            line: 28,
+            character,
+        };
+
+        editor.diagnostics.publish(
+            "test",
+            path,
+            vec![Diagnostic {
+                range: Range {
+                    start: position(4),
+                    end: position(10),
+                },
+                severity: Severity::Warning,
+                message: "`line28` is never read".to_string(),
+                source: None,
+            }],
+        );
+
+        assert_golden("scrolled_buffer", &draw(&mut editor, &renderer));
+    }
+
+    // a scrolled view moves over a few frames and ends up where it would without animations
+    #[test]
+    fn scrolling_eases_into_place() {
+        let content: Vec<String> = (0..40).map(|x| format!("let line{} = {};", x, x)).collect();
+        let (mut editor, renderer, _root) = open(&content.join("\n"));
+        let start = Instant::now();
+
+        editor.motion.reduced = false;
+        draw_at(&mut editor, &renderer, start);
+
+        editor.workspace.active_mut().scroll = 10;
+        let first = draw_at(&mut editor, &renderer, start);
+
+        assert_eq!(editor.damage.deadline(), Some(start + FRAME_INTERVAL));
+
+        let halfway = draw_at(&mut editor, &renderer, start + Duration::from_millis(75));
+        let settled = draw_at(&mut editor, &renderer, start + Duration::from_secs(1));
+
+        assert!(!editor.motion.running(start + Duration::from_secs(1)));
+        assert!(halfway != first && halfway != settled);
+
+        editor.motion.reduced = true;
+        assert!(draw(&mut editor, &renderer) == settled);
+    }
+
+    // presses and releases the left button `column` cells into the `row`th row of the focused
+    // pane, and returns where the cursor went
+    fn click(editor: &mut Editor, row: usize, column: f32) -> usize {
+        let viewports = editor.workspace.layout.viewports(editor.area);
+        let (_, rect) = viewports
+            .iter()
+            .find(|(id, _)| *id == editor.workspace.focused)
+            .unwrap();
+        let line_height = editor.layout.line_height();
+
+        editor.mouse = (
+            rect.left + text::PADDING + column * editor.layout.cell(),
+            rect.bottom + rect.height - text::PADDING - (row as f32 + 0.5) * line_height,
+        );
+        editor.handle_mouse_input(ElementState::Pressed, MouseButton::Left);
+        editor.handle_mouse_input(ElementState::Released, MouseButton::Left);
+
+        editor.workspace.active().cursor.idx
+    }
+
+    #[test]
+    fn clicks_land_where_the_text_is_drawn() {
+        let content = "a\n{\n  b\n}\n世界x\nc\nd";
+        let (mut editor, renderer, _root) = open(content);
+        let start = Instant::now();
+        let wide = content.find('世').unwrap();
+
+        let buffer = editor.workspace.active_mut();
+        buffer.refresh_folds();
+        buffer.toggle_fold(1);
+        draw(&mut editor, &renderer);
+
+        // the folded away line takes no row, a wide char two cells
+        assert_eq!(click(&mut editor, 2, 0.2), content.find('}').unwrap());
+        assert_eq!(click(&mut editor, 3, 2.2), wide + "世".len());
+        assert_eq!(click(&mut editor, 3, 3.4), wide + "世界".len());
+
+        // while the view eases towards the scrolled to line, the text is where it's drawn
+        editor.motion.reduced = false;
+        draw_at(&mut editor, &renderer, start);
+        editor.workspace.active_mut().scroll = 4;
+        draw_at(&mut editor, &renderer, start);
+
+        assert_eq!(click(&mut editor, 0, 0.2), 0);
+
+        draw_at(&mut editor, &renderer, start + Duration::from_secs(1));
+        assert_eq!(click(&mut editor, 0, 0.2), wide);
+    }
+
+    // the GPU draws the same frames as the reference rasterizer, give or take some rounding
+    #[test]
+    fn wgpu_draws_like_the_software_backend() {
+        let (mut editor, renderer, _root) = open_sample();
+        let (width, height) = WINDOW_SIZE;
+
+        // a software adapter like lavapipe, which any machine running the tests needs
+        let mut gpu = Gpu::fallback(renderer.bitmap, width, height)
+            .expect("no fallback wgpu adapter, install lavapipe (mesa-vulkan-drivers)");
+
+        let expected = draw(&mut editor, &renderer);
+
+        draw_frame(
+            &mut editor,
+            &renderer,
+            &mut gpu,
+            PhysicalSize::new(width, height),
+            &FrameStats::default(),
+            Instant::now(),
+        );
+        gpu.finish();
+
+        let image = gpu.read();
+
+        let differing = expected
+            .pixels()
+            .zip(image.pixels())
+            .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > TOLERANCE))
+            .count();
+
+        // edges exactly on pixel centers may go either way
+        assert!(
+            differing < (width * height / 200) as usize,
+            "{} pixels differ",
+            differing
+        );
+    }
+
+    #[test]
+    fn subpixel_text() {
+        let (mut editor, renderer, _root) = open_sample();
+        editor.subpixel = true;
+
+        assert_golden("subpixel_text", &draw(&mut editor, &renderer));
+    }
+
+    // a block cursor shows the char under it inverted, a hollow one only outlines it
+    #[test]
+    fn cursor_styles() {
+        let (mut editor, renderer, _root) = open_sample();
+        editor.settings.cursor_normal = CursorStyle::Block;
+
+        let buffer = editor.workspace.active_mut();
+        let line = buffer.content.find("function").unwrap();
+        buffer.set_cursor(line + 2);
+
+        assert_golden("cursor_styles", &draw(&mut editor, &renderer));
+
+        editor.settings.cursor_normal = CursorStyle::Hollow;
+
+        assert_golden("cursor_styles_hollow", &draw(&mut editor, &renderer));
+    }
 }
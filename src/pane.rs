@@ -0,0 +1,238 @@
+use crate::{cursor::Cursor, Rectangle};
+
+const MIN_RATIO: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Vertical,   // vertical divider, panes side by side
+    Horizontal, // horizontal divider, panes stacked
+}
+
+// view state of a pane, only authoritative while the pane is not focused,
+// the focused pane lives in the workspace's active buffer
+#[derive(Debug)]
+pub struct Pane {
+    pub id: usize,
+    pub buffer: usize,
+    pub cursor: Cursor,
+    pub scroll: usize,
+}
+
+#[derive(Debug)]
+pub enum Layout {
+    Leaf(Pane),
+    Split {
+        direction: Direction,
+        ratio: f32, // share of the first child
+        first: Box<Layout>,
+        second: Box<Layout>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Border {
+    pub split: usize, // pre-order index of the split it belongs to
+    pub direction: Direction,
+    pub rect: Rectangle, // divider line
+    pub area: Rectangle, // area of the whole split
+}
+
+impl Layout {
+    fn split_rect(direction: Direction, ratio: f32, rect: Rectangle) -> (Rectangle, Rectangle) {
+        match direction {
+            Direction::Vertical => {
+                let width = (rect.width * ratio).round();
+                (
+                    Rectangle { width, ..rect },
+                    Rectangle {
+                        left: rect.left + width,
+                        width: rect.width - width,
+                        ..rect
+                    },
+                )
+            }
+            Direction::Horizontal => {
+                let height = (rect.height * ratio).round();
+                (
+                    Rectangle {
+                        bottom: rect.bottom + rect.height - height,
+                        height,
+                        ..rect
+                    },
+                    Rectangle {
+                        height: rect.height - height,
+                        ..rect
+                    },
+                )
+            }
+        }
+    }
+
+    pub fn viewports(&self, rect: Rectangle) -> Vec<(usize, Rectangle)> {
+        match self {
+            Layout::Leaf(pane) => vec![(pane.id, rect)],
+            Layout::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_rect, second_rect) = Layout::split_rect(*direction, *ratio, rect);
+
+                let mut viewports = first.viewports(first_rect);
+                viewports.extend(second.viewports(second_rect));
+                viewports
+            }
+        }
+    }
+
+    pub fn borders(&self, rect: Rectangle) -> Vec<Border> {
+        let mut borders = Vec::new();
+        self.collect_borders(rect, &mut 0, &mut borders);
+        borders
+    }
+
+    fn collect_borders(&self, rect: Rectangle, split: &mut usize, borders: &mut Vec<Border>) {
+        let Layout::Split {
+            direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return;
+        };
+
+        let (first_rect, second_rect) = Layout::split_rect(*direction, *ratio, rect);
+
+        let line = match direction {
+            Direction::Vertical => Rectangle {
+                left: second_rect.left - 1.0,
+                width: 2.0,
+                ..rect
+            },
+            Direction::Horizontal => Rectangle {
+                bottom: first_rect.bottom - 1.0,
+                height: 2.0,
+                ..rect
+            },
+        };
+
+        borders.push(Border {
+            split: *split,
+            direction: *direction,
+            rect: line,
+            area: rect,
+        });
+
+        *split += 1;
+
+        first.collect_borders(first_rect, split, borders);
+        second.collect_borders(second_rect, split, borders);
+    }
+
+    pub fn panes(&self) -> Vec<&Pane> {
+        match self {
+            Layout::Leaf(pane) => vec![pane],
+            Layout::Split { first, second, .. } => {
+                let mut panes = first.panes();
+                panes.extend(second.panes());
+                panes
+            }
+        }
+    }
+
+    pub fn panes_mut(&mut self) -> Vec<&mut Pane> {
+        match self {
+            Layout::Leaf(pane) => vec![pane],
+            Layout::Split { first, second, .. } => {
+                let mut panes = first.panes_mut();
+                panes.extend(second.panes_mut());
+                panes
+            }
+        }
+    }
+
+    pub fn pane_mut(&mut self, id: usize) -> Option<&mut Pane> {
+        self.panes_mut().into_iter().find(|pane| pane.id == id)
+    }
+
+    // replaces the pane with a split holding it and `pane`, hands `pane` back if `id` isn't found
+    pub fn split(&mut self, id: usize, direction: Direction, pane: Pane) -> Result<(), Pane> {
+        match self {
+            Layout::Leaf(leaf) if leaf.id == id => {
+                let existing = std::mem::replace(self, Layout::Leaf(Pane::empty()));
+
+                *self = Layout::Split {
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(existing),
+                    second: Box::new(Layout::Leaf(pane)),
+                };
+                Ok(())
+            }
+            Layout::Leaf(_) => Err(pane),
+            Layout::Split { first, second, .. } => first
+                .split(id, direction, pane)
+                .or_else(|pane| second.split(id, direction, pane)),
+        }
+    }
+
+    // removes the pane, its sibling takes over the space
+    pub fn remove(&mut self, id: usize) -> bool {
+        let Layout::Split { first, second, .. } = self else {
+            return false;
+        };
+
+        let is_pane = |layout: &Layout| matches!(layout, Layout::Leaf(pane) if pane.id == id);
+
+        let sibling = if is_pane(first) {
+            second
+        } else if is_pane(second) {
+            first
+        } else {
+            return first.remove(id) || second.remove(id);
+        };
+
+        let sibling = std::mem::replace(sibling.as_mut(), Layout::Leaf(Pane::empty()));
+        *self = sibling;
+        true
+    }
+
+    pub fn set_ratio(&mut self, split: usize, ratio: f32) {
+        let mut idx = 0;
+        self.set_ratio_at(split, ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO), &mut idx);
+    }
+
+    fn set_ratio_at(&mut self, split: usize, value: f32, idx: &mut usize) -> bool {
+        let Layout::Split {
+            ratio,
+            first,
+            second,
+            ..
+        } = self
+        else {
+            return false;
+        };
+
+        if *idx == split {
+            *ratio = value;
+            return true;
+        }
+
+        *idx += 1;
+
+        first.set_ratio_at(split, value, idx) || second.set_ratio_at(split, value, idx)
+    }
+}
+
+impl Pane {
+    fn empty() -> Self {
+        Pane {
+            id: usize::MAX,
+            buffer: 0,
+            cursor: Cursor::new(),
+            scroll: 0,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use winit::{
+    event::KeyEvent,
+    keyboard::{Key, NamedKey},
+};
+
+use crate::{
+    fuzzy::{self, FuzzyMatch},
+    selection::Selection,
+};
+
+pub enum PickerEvent {
+    None,
+    Accept,
+    Cancel,
+}
+
+pub struct PickerItem<T> {
+    pub label: String,
+    pub detail: String, // shown dimmed after the label, not matched against
+    pub value: T,
+}
+
+// fuzzy filtered list of items with a query line, the overlay behind quick open,
+// the command palette and other lists that pick one thing
+pub struct Picker<T> {
+    pub title: String,
+    pub query: String,
+    pub items: Vec<PickerItem<T>>,
+    pub matches: Vec<(usize, FuzzyMatch)>, // item index and match, best first
+    pub selection: Selection,
+}
+
+impl<T> Picker<T> {
+    pub fn new(title: &str, items: Vec<PickerItem<T>>) -> Self {
+        let mut picker = Picker {
+            title: title.to_string(),
+            query: String::new(),
+            items,
+            matches: Vec::new(),
+            selection: Selection::default(),
+        };
+
+        picker.filter();
+        picker
+    }
+
+    // adds items streamed in after the picker was opened
+    pub fn extend(&mut self, items: impl IntoIterator<Item = PickerItem<T>>) {
+        self.items.extend(items);
+        self.filter();
+    }
+
+    fn filter(&mut self) {
+        self.matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| Some((idx, fuzzy::fuzzy_match(&self.query, &item.label)?)))
+            .collect();
+
+        // a stable sort keeps the item order for equal scores, so sources can rank their items
+        if !self.query.is_empty() {
+            let items = &self.items;
+            self.matches.sort_by(|(a_idx, a), (b_idx, b)| {
+                b.score
+                    .cmp(&a.score)
+                    .then_with(|| items[*a_idx].label.len().cmp(&items[*b_idx].label.len()))
+            });
+        }
+
+        self.selection.clamp(self.matches.len());
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.query = query.to_string();
+        self.selection = Selection::default();
+        self.filter();
+    }
+
+    pub fn selected_item(&self) -> Option<&T> {
+        let idx = self.matches.get(self.selection.selected)?.0;
+        Some(&self.items[idx].value)
+    }
+
+    pub fn into_selected(self) -> Option<T> {
+        let idx = self.matches.get(self.selection.selected)?.0;
+        self.items.into_iter().nth(idx).map(|item| item.value)
+    }
+
+    pub fn move_up(&mut self) {
+        self.selection.move_up();
+    }
+
+    pub fn move_down(&mut self) {
+        self.selection.move_down(self.matches.len());
+    }
+
+    pub fn handle_key(&mut self, event: &KeyEvent) -> PickerEvent {
+        match event.logical_key.as_ref() {
+            Key::Named(NamedKey::Enter) => return PickerEvent::Accept,
+            Key::Named(NamedKey::Escape) => return PickerEvent::Cancel,
+            Key::Named(NamedKey::ArrowUp) => self.move_up(),
+            Key::Named(NamedKey::ArrowDown) => self.move_down(),
+            Key::Named(NamedKey::Backspace) => {
+                let mut query = self.query.clone();
+                query.pop();
+                self.set_query(&query);
+            }
+            _ => {
+                if let Some(text) = event
+                    .text
+                    .as_ref()
+                    .filter(|x| !x.chars().any(char::is_control))
+                {
+                    let query = format!("{}{}", self.query, text);
+                    self.set_query(&query);
+                }
+            }
+        }
+
+        PickerEvent::None
+    }
+}
@@ -0,0 +1,21 @@
+use crate::selection::Selection;
+
+pub const ROW_HEIGHT: f32 = 24.0;
+pub const ROWS: usize = 8; // list rows below the header
+
+// state of the problems panel, the rows themselves come from the diagnostics
+pub struct Problems {
+    pub visible: bool,
+    pub focused: bool,
+    pub selection: Selection,
+}
+
+impl Problems {
+    pub fn new() -> Self {
+        Problems {
+            visible: false,
+            focused: false,
+            selection: Selection::default(),
+        }
+    }
+}
@@ -1,122 +1,503 @@
+use std::{ops::Range, sync::atomic::Ordering};
+
 use glium::{
     backend::glutin::Display,
     glutin::surface::WindowSurface,
-    index::NoIndices,
-    uniforms::{AsUniformValue, Uniforms},
-    Frame, Program, Surface, VertexBuffer,
+    index::PrimitiveType,
+    texture::RawImage2d,
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+    Blend, BlendingFunction, DrawParameters, Frame, IndexBuffer, LinearBlendingFactor, Program,
+    Surface, Texture2d, Vertex, VertexBuffer,
 };
-use winit::window::Window;
 
 use crate::{
     font::BitmapFont,
-    vertex::{ColorVertex, TextureVertex},
+    vertex::{ColorVertex, SquiggleVertex, TextureVertex},
+    Rectangle,
 };
 
+// coverage is raised to 1 / GAMMA so antialiased edges keep their weight once blended
+pub const GAMMA: f32 = 1.8;
+
+pub const TEXT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+// room for this many quads is allocated up front, buffers double from there
+pub const INITIAL_QUADS: usize = 1024;
+
+// every quad is four vertices, top left, top right, bottom right and bottom left
+pub const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+// a column major 4x4 matrix like the shaders take
+pub type Matrix = [[f32; 4]; 4];
+
+// where the quads of a frame end up, drawn in the order they're added. vertices are in window
+// pixels from the bottom left and texture coordinates are normalized to their atlas page
+pub trait Backend {
+    fn clear(&mut self, color: [f32; 3]);
+    // moves the quads added from now on by `offset` pixels, like a scrolled document
+    fn view(&mut self, offset: [f32; 2]);
+    // colors the text added from now on, emoji keep their own colors
+    fn text_color(&mut self, color: [f32; 3]);
+    fn fill(&mut self, shapes: &[ColorVertex], scissor: Option<Rectangle>);
+    // with `subpixel` the coverage of each color channel is applied separately
+    fn text(&mut self, shapes: &[TextureVertex], scissor: Option<Rectangle>, subpixel: bool);
+    fn squiggle(&mut self, shapes: &[SquiggleVertex], scissor: Option<Rectangle>);
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layer {
+    Color,
+    Text,
+    Squiggle,
+}
+
+// a scissor rectangle in whole window pixels, from the bottom left
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scissor {
+    pub left: u32,
+    pub bottom: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Scissor {
+    pub fn from(rect: Rectangle) -> Self {
+        Scissor {
+            left: rect.left.max(0.0) as u32,
+            bottom: rect.bottom.max(0.0) as u32,
+            width: rect.width.max(0.0) as u32,
+            height: rect.height.max(0.0) as u32,
+        }
+    }
+}
+
+// window pixels to normalized device coordinates
+pub fn projection(width: u32, height: u32) -> Matrix {
+    [
+        [2.0 / width as f32, 0.0, 0.0, 0.0],
+        [0.0, 2.0 / height as f32, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, -1.0, 0.0, 1.0],
+    ]
+}
+
+// moves vertices by `offset` pixels
+pub fn view(offset: [f32; 2]) -> Matrix {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [offset[0], offset[1], 0.0, 1.0],
+    ]
+}
+
+// a run of quads from one layer sharing a scissor rectangle, view and text color
+pub struct Draw {
+    pub layer: Layer,
+    pub quads: Range<usize>,
+    pub scissor: Option<Scissor>,
+    pub view: [f32; 2],
+    pub text_color: [f32; 3],
+    pub subpixel: bool,
+}
+
+// consecutive quads of a layer under the same scissor, view and text color become a single
+// draw call
+pub fn push(
+    draws: &mut Vec<Draw>,
+    layer: Layer,
+    quads: Range<usize>,
+    scissor: Option<Rectangle>,
+    view: [f32; 2],
+    text_color: [f32; 3],
+    subpixel: bool,
+) {
+    if quads.is_empty() {
+        return;
+    }
+
+    let scissor = scissor.map(Scissor::from);
+
+    if let Some(last) = draws.last_mut() {
+        if last.layer == layer
+            && last.scissor == scissor
+            && last.view == view
+            && last.text_color == text_color
+            && last.subpixel == subpixel
+            && last.quads.end == quads.start
+        {
+            last.quads.end = quads.end;
+            return;
+        }
+    }
+
+    draws.push(Draw {
+        layer,
+        quads,
+        scissor,
+        view,
+        text_color,
+        subpixel,
+    });
+}
+
+// vertices gathered over a frame and the buffer they're uploaded into, kept across frames
+struct Batch<T: Copy + Vertex> {
+    vertices: Vec<T>,
+    buffer: VertexBuffer<T>,
+}
+
+impl<T: Copy + Vertex> Batch<T> {
+    fn new(display: &Display<WindowSurface>) -> Self {
+        Batch {
+            vertices: Vec::new(),
+            buffer: VertexBuffer::empty_dynamic(display, INITIAL_QUADS * 4).unwrap(),
+        }
+    }
+
+    fn quads(&self) -> usize {
+        self.vertices.len() / 4
+    }
+
+    fn upload(&mut self, display: &Display<WindowSurface>) {
+        if self.vertices.len() > self.buffer.len() {
+            let capacity = self.vertices.len().next_power_of_two();
+            self.buffer = VertexBuffer::empty_dynamic(display, capacity).unwrap();
+        }
+
+        if !self.vertices.is_empty() {
+            self.buffer
+                .slice(0..self.vertices.len())
+                .unwrap()
+                .write(&self.vertices);
+        }
+    }
+}
+
+// the OpenGL backend, quads are batched over a frame and drawn once it's finished
 pub struct Application<'a> {
-    pub window: &'a Window,
-    pub display: &'a Display<WindowSurface>,
-    pub indices: NoIndices,
-    pub program: &'a Program,
+    pub display: Display<WindowSurface>,
     pub font: &'a BitmapFont,
+    atlas: Texture2d,
+    atlas_version: usize, // version of the glyph atlas last uploaded
+    emoji: Texture2d,
+    emoji_version: usize, // version of the emoji page last uploaded
+    clear: [f32; 3],
+    view: [f32; 2],
+    text_color: [f32; 3],
+    text_program: Program,
+    color_program: Program,
+    squiggle_program: Program,
+    colors: Batch<ColorVertex>,
+    texts: Batch<TextureVertex>,
+    squiggles: Batch<SquiggleVertex>,
+    // shared by all batches since every quad is indexed the same way
+    indices: IndexBuffer<u32>,
+    draws: Vec<Draw>,
 }
 
 impl<'a> Application<'a> {
-    pub fn new(
-        window: &'a Window,
-        display: &'a Display<WindowSurface>,
-        program: &'a Program,
-        font: &'a BitmapFont,
-    ) -> Self {
+    pub fn new(display: Display<WindowSurface>, font: &'a BitmapFont) -> Self {
+        let text_program = Program::from_source(
+            &display,
+            include_str!("./shaders/texture.vert"),
+            include_str!("./shaders/texture.frag"),
+            None,
+        )
+        .unwrap();
+
+        let color_program = Program::from_source(
+            &display,
+            include_str!("./shaders/color.vert"),
+            include_str!("./shaders/color.frag"),
+            None,
+        )
+        .unwrap();
+
+        let squiggle_program = Program::from_source(
+            &display,
+            include_str!("./shaders/boundry.vert"),
+            include_str!("./shaders/boundry.frag"),
+            None,
+        )
+        .unwrap();
+
+        let atlas = font.image.lock().unwrap();
+        let atlas = RawImage2d::from_raw_rgba(atlas.to_vec(), atlas.dimensions());
+
+        // without an emoji font no glyph samples it
+        let emoji = match &font.emoji {
+            Some(page) => {
+                let image = page.image.lock().unwrap();
+                RawImage2d::from_raw_rgba(image.to_vec(), image.dimensions())
+            }
+            None => RawImage2d::from_raw_rgba(vec![0; 4], (1, 1)),
+        };
+
         Application {
-            window,
+            atlas: Texture2d::new(&display, atlas).unwrap(),
+            atlas_version: font.version.load(Ordering::Relaxed),
+            emoji: Texture2d::new(&display, emoji).unwrap(),
+            emoji_version: 0,
+            clear: [0.0, 0.0, 0.0],
+            view: [0.0, 0.0],
+            text_color: TEXT_COLOR,
+            text_program,
+            color_program,
+            squiggle_program,
+            colors: Batch::new(&display),
+            texts: Batch::new(&display),
+            squiggles: Batch::new(&display),
+            indices: quad_indices(&display, INITIAL_QUADS),
+            draws: Vec::new(),
             display,
-            indices: glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
-            program,
             font,
         }
     }
 
-    pub fn render<'n, T: Copy, U: AsUniformValue, R: Uniforms>(
+    // uploads everything added this frame and draws it, leaving the batches empty for the next
+    pub fn finish(&mut self, target: &mut Frame) {
+        let [red, green, blue] = self.clear;
+        target.clear_color(red, green, blue, 1.0);
+
+        // glyphs drawn for the first time this frame
+        if self.font.version.load(Ordering::Relaxed) != self.atlas_version {
+            let image = self.font.image.lock().unwrap();
+            let image = RawImage2d::from_raw_rgba(image.to_vec(), image.dimensions());
+
+            self.atlas = Texture2d::new(&self.display, image).unwrap();
+            self.atlas_version = self.font.version.load(Ordering::Relaxed);
+        }
+
+        // emoji drawn for the first time this frame
+        if let Some(page) = self
+            .font
+            .emoji
+            .as_ref()
+            .filter(|x| x.version.load(Ordering::Relaxed) != self.emoji_version)
+        {
+            let image = page.image.lock().unwrap();
+            let image = RawImage2d::from_raw_rgba(image.to_vec(), image.dimensions());
+
+            self.emoji = Texture2d::new(&self.display, image).unwrap();
+            self.emoji_version = page.version.load(Ordering::Relaxed);
+        }
+
+        self.colors.upload(&self.display);
+        self.texts.upload(&self.display);
+        self.squiggles.upload(&self.display);
+
+        let quads = self
+            .colors
+            .quads()
+            .max(self.texts.quads())
+            .max(self.squiggles.quads());
+
+        if quads * QUAD_INDICES.len() > self.indices.len() {
+            self.indices = quad_indices(&self.display, quads.next_power_of_two());
+        }
+
+        let (width, height) = target.get_dimensions();
+        let projection = projection(width, height);
+
+        for draw in &self.draws {
+            let uniforms = uniform! {
+                projection: projection,
+                view: view(draw.view),
+            };
+
+            let indices = self
+                .indices
+                .slice(draw.quads.start * QUAD_INDICES.len()..draw.quads.end * QUAD_INDICES.len())
+                .unwrap();
+
+            let params = DrawParameters {
+                scissor: draw.scissor.map(|x| glium::Rect {
+                    left: x.left,
+                    bottom: x.bottom,
+                    width: x.width,
+                    height: x.height,
+                }),
+                ..Default::default()
+            };
+
+            match draw.layer {
+                Layer::Color => target
+                    .draw(
+                        &self.colors.buffer,
+                        indices,
+                        &self.color_program,
+                        &uniforms,
+                        &params,
+                    )
+                    .unwrap(),
+                Layer::Squiggle => target
+                    .draw(
+                        &self.squiggles.buffer,
+                        indices,
+                        &self.squiggle_program,
+                        &uniforms,
+                        &params,
+                    )
+                    .unwrap(),
+                Layer::Text => self.draw_text(target, draw, &params, projection),
+            }
+        }
+
+        self.colors.vertices.clear();
+        self.texts.vertices.clear();
+        self.squiggles.vertices.clear();
+        self.draws.clear();
+    }
+
+    // text is blended over the target, with `subpixel` the coverage of each color channel is
+    // applied separately, first masking out the background and then adding the text
+    fn draw_text(
         &self,
-        vertex_buffer: &VertexBuffer<T>,
-        frame: &mut Frame,
-        uniforms: glium::uniforms::UniformsStorage<'n, U, R>,
+        target: &mut Frame,
+        draw: &Draw,
+        params: &DrawParameters,
+        projection: Matrix,
     ) {
-        frame
-            .draw(
-                vertex_buffer,
-                &self.indices,
-                &self.program,
-                &uniforms,
-                &Default::default(),
-            )
-            .unwrap();
+        let range = draw.quads.start * QUAD_INDICES.len()..draw.quads.end * QUAD_INDICES.len();
+
+        let tex = self
+            .atlas
+            .sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest);
+
+        let emoji = self
+            .emoji
+            .sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest);
+
+        let passes: &[(i32, Blend)] = if draw.subpixel {
+            &[
+                (
+                    1,
+                    Blend {
+                        color: BlendingFunction::Addition {
+                            source: LinearBlendingFactor::Zero,
+                            destination: LinearBlendingFactor::OneMinusSourceColor,
+                        },
+                        alpha: BlendingFunction::Addition {
+                            source: LinearBlendingFactor::Zero,
+                            destination: LinearBlendingFactor::One,
+                        },
+                        constant_value: (0.0, 0.0, 0.0, 0.0),
+                    },
+                ),
+                (
+                    2,
+                    Blend {
+                        color: BlendingFunction::Addition {
+                            source: LinearBlendingFactor::One,
+                            destination: LinearBlendingFactor::One,
+                        },
+                        alpha: BlendingFunction::Addition {
+                            source: LinearBlendingFactor::Zero,
+                            destination: LinearBlendingFactor::One,
+                        },
+                        constant_value: (0.0, 0.0, 0.0, 0.0),
+                    },
+                ),
+            ]
+        } else {
+            &[(0, Blend::alpha_blending())]
+        };
+
+        for &(pass, blend) in passes {
+            let uniforms = uniform! {
+                projection: projection,
+                view: view(draw.view),
+                tex: tex,
+                emoji: emoji,
+                gamma: GAMMA,
+                text_color: draw.text_color,
+                blend_pass: pass,
+            };
+
+            let params = DrawParameters {
+                blend,
+                ..params.clone()
+            };
+
+            target
+                .draw(
+                    &self.texts.buffer,
+                    self.indices.slice(range.clone()).unwrap(),
+                    &self.text_program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+        }
     }
 }
 
-trait Scale {
-    fn scale(&mut self, application: Application)
-    where
-        Self: Sized;
-}
+impl Backend for Application<'_> {
+    fn clear(&mut self, color: [f32; 3]) {
+        self.clear = color;
+    }
 
-impl Scale for TextureVertex {
-    fn scale(&mut self, application: Application)
-    where
-        Self: Sized + ScalePosition + ScaleTexture,
-    {
-        self.scale_position(
-            application.window.inner_size().width as f32,
-            application.window.inner_size().height as f32,
-        );
-        self.scale_texture(
-            application.font.texture.width() as f32,
-            application.font.texture.height() as f32,
-        );
+    fn view(&mut self, offset: [f32; 2]) {
+        self.view = offset;
     }
-}
 
-impl Scale for ColorVertex {
-    fn scale(&mut self, application: Application)
-    where
-        Self: Sized + ScalePosition,
-    {
-        self.scale_position(
-            application.window.inner_size().width as f32,
-            application.window.inner_size().height as f32,
-        );
+    fn text_color(&mut self, color: [f32; 3]) {
+        self.text_color = color;
     }
-}
 
-trait ScalePosition {
-    fn scale_position(&mut self, width: f32, height: f32);
-}
+    fn fill(&mut self, shapes: &[ColorVertex], scissor: Option<Rectangle>) {
+        let start = self.colors.quads();
+        self.colors.vertices.extend_from_slice(shapes);
+        push(
+            &mut self.draws,
+            Layer::Color,
+            start..self.colors.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            false,
+        );
+    }
 
-impl ScalePosition for ColorVertex {
-    fn scale_position(&mut self, width: f32, height: f32) {
-        let w_factor = self.position[0] / (width / 2.0);
-        let h_factor = self.position[1] / (height / 2.0);
-        self.position[0] = w_factor - 1.0;
-        self.position[1] = h_factor - 1.0;
+    fn text(&mut self, shapes: &[TextureVertex], scissor: Option<Rectangle>, subpixel: bool) {
+        let start = self.texts.quads();
+        self.texts.vertices.extend_from_slice(shapes);
+        push(
+            &mut self.draws,
+            Layer::Text,
+            start..self.texts.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            subpixel,
+        );
     }
-}
 
-impl ScalePosition for TextureVertex {
-    fn scale_position(&mut self, width: f32, height: f32) {
-        let w_factor = self.position[0] / (width / 2.0);
-        let h_factor = self.position[1] / (height / 2.0);
-        self.position[0] = w_factor - 1.0;
-        self.position[1] = h_factor - 1.0;
+    fn squiggle(&mut self, shapes: &[SquiggleVertex], scissor: Option<Rectangle>) {
+        let start = self.squiggles.quads();
+        self.squiggles.vertices.extend_from_slice(shapes);
+        push(
+            &mut self.draws,
+            Layer::Squiggle,
+            start..self.squiggles.quads(),
+            scissor,
+            self.view,
+            self.text_color,
+            false,
+        );
     }
 }
 
-trait ScaleTexture {
-    fn scale_texture(&mut self, width: f32, height: f32);
-}
+fn quad_indices(display: &Display<WindowSurface>, quads: usize) -> IndexBuffer<u32> {
+    let indices: Vec<u32> = (0..quads as u32)
+        .flat_map(|quad| QUAD_INDICES.map(|x| quad * 4 + x))
+        .collect();
 
-impl ScaleTexture for TextureVertex {
-    fn scale_texture(&mut self, width: f32, height: f32) {
-        self.tex_coords[0] = self.tex_coords[0] / width;
-        self.tex_coords[1] = self.tex_coords[1] / height;
-    }
+    IndexBuffer::new(display, PrimitiveType::TrianglesList, &indices).unwrap()
 }
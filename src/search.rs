@@ -0,0 +1,359 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, Sender, TryRecvError},
+    thread,
+};
+
+use ignore::{WalkBuilder, WalkState};
+use regex::Regex;
+
+use crate::waker::Waker;
+
+const CONTEXT_LINES: usize = 1;
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,   // 0-based line in the file
+    pub column: usize, // 0-based char column in the line
+}
+
+#[derive(Debug)]
+pub struct LineMatch {
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+pub struct Search {
+    pub query: String,
+    pub root: PathBuf,
+    pub error: Option<String>,
+    pub results: Vec<FileMatches>,
+    receiver: Option<Receiver<FileMatches>>,
+}
+
+impl Search {
+    pub fn new(root: PathBuf) -> Self {
+        Search {
+            query: String::new(),
+            root,
+            error: None,
+            results: Vec::new(),
+            receiver: None,
+        }
+    }
+
+    pub fn start(&mut self, waker: Waker) {
+        self.results.clear();
+        self.error = None;
+
+        let pattern = match Regex::new(&self.query) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                self.receiver = None;
+                return;
+            }
+        };
+
+        // dropping the previous receiver makes any running search stop on its next send
+        let (sender, receiver) = mpsc::channel();
+        self.receiver = Some(receiver);
+
+        let root = self.root.clone();
+        thread::spawn(move || {
+            search_dir(&root, &pattern, sender, &waker);
+            // the channel is closed now, which ends the search for `poll`
+            waker.wake();
+        });
+    }
+
+    // pulls in results streamed so far, returns true if anything new arrived
+    pub fn poll(&mut self) -> bool {
+        let Some(receiver) = &self.receiver else {
+            return false;
+        };
+
+        let mut changed = false;
+
+        loop {
+            match receiver.try_recv() {
+                // files come in whatever order the threads finish them, kept by path so the
+                // same search always reads the same
+                Ok(file) => {
+                    let idx = self.results.partition_point(|x| x.path < file.path);
+                    self.results.insert(idx, file);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                // the search thread is done
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    break;
+                }
+            }
+        }
+
+        changed
+    }
+
+    // renders results grouped by file, with the location each line jumps to
+    pub fn results_buffer(&self) -> (String, Vec<Option<Location>>) {
+        let mut text = String::new();
+        let mut locations = Vec::new();
+
+        let mut push = |text: &mut String, line: String, location: Option<Location>| {
+            text.push_str(&line);
+            text.push('\n');
+            locations.push(location);
+        };
+
+        let count: usize = self.results.iter().map(|file| file.matches.len()).sum();
+
+        push(
+            &mut text,
+            format!("{} results for /{}/", count, self.query),
+            None,
+        );
+
+        for file in &self.results {
+            let path = file.path.strip_prefix(&self.root).unwrap_or(&file.path);
+
+            push(&mut text, String::new(), None);
+            push(&mut text, path.display().to_string(), None);
+
+            // context shared by neighbouring matches is only printed once
+            let mut next_line = 0;
+
+            for (idx, line_match) in file.matches.iter().enumerate() {
+                let first = line_match.line - line_match.before.len();
+
+                for (i, line) in line_match.before.iter().enumerate() {
+                    if first + i < next_line {
+                        continue;
+                    }
+                    push(&mut text, format!("{:>6}- {}", first + i + 1, line), None);
+                }
+
+                let location = Location {
+                    path: file.path.clone(),
+                    line: line_match.line,
+                    column: line_match.column,
+                };
+
+                push(
+                    &mut text,
+                    format!("{:>6}: {}", line_match.line + 1, line_match.text),
+                    Some(location),
+                );
+
+                next_line = line_match.line + 1;
+
+                let next_match = file
+                    .matches
+                    .get(idx + 1)
+                    .map_or(usize::MAX, |next| next.line);
+
+                for line in &line_match.after {
+                    if next_line >= next_match {
+                        break;
+                    }
+                    push(&mut text, format!("{:>6}- {}", next_line + 1, line), None);
+                    next_line += 1;
+                }
+            }
+        }
+
+        (text, locations)
+    }
+}
+
+fn search_dir(root: &Path, pattern: &Regex, sender: Sender<FileMatches>, waker: &Waker) {
+    WalkBuilder::new(root).build_parallel().run(|| {
+        let sender = sender.clone();
+        let waker = waker.clone();
+
+        Box::new(move |entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+                return WalkState::Continue;
+            }
+
+            let Some(file) = search_file(entry.path(), pattern) else {
+                return WalkState::Continue;
+            };
+
+            match sender.send(file) {
+                Ok(()) => {
+                    waker.wake();
+                    WalkState::Continue
+                }
+                Err(_) => WalkState::Quit,
+            }
+        })
+    });
+}
+
+fn search_file(path: &Path, pattern: &Regex) -> Option<FileMatches> {
+    // skips binary and non utf-8 files
+    let content = fs::read_to_string(path).ok()?;
+
+    if content.contains('\0') {
+        return None;
+    }
+
+    let lines: Vec<&str> = content.lines().collect();
+
+    let matches: Vec<LineMatch> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let found = pattern.find(line)?;
+
+            let start = idx.saturating_sub(CONTEXT_LINES);
+            let end = (idx + 1 + CONTEXT_LINES).min(lines.len());
+
+            Some(LineMatch {
+                line: idx,
+                column: line[..found.start()].chars().count(),
+                text: line.to_string(),
+                before: lines[start..idx].iter().map(|x| x.to_string()).collect(),
+                after: lines[idx + 1..end].iter().map(|x| x.to_string()).collect(),
+            })
+        })
+        .collect();
+
+    if matches.is_empty() {
+        return None;
+    }
+
+    Some(FileMatches {
+        path: path.to_path_buf(),
+        matches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::{cursor::Cursor, utils::temp::TempDir};
+
+    fn line_match(line: usize, before: &[&str], text: &str, after: &[&str]) -> LineMatch {
+        LineMatch {
+            line,
+            column: 0,
+            text: text.to_string(),
+            before: before.iter().map(|x| x.to_string()).collect(),
+            after: after.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn results_share_context_between_neighbouring_matches() {
+        let mut search = Search::new(PathBuf::from("/project"));
+        search.query = "x".to_string();
+        search.results.push(FileMatches {
+            path: PathBuf::from("/project/src/a.rs"),
+            matches: vec![
+                line_match(1, &["a"], "x1", &["b"]),
+                line_match(2, &["x1"], "x2", &["c"]),
+            ],
+        });
+
+        let (text, locations) = search.results_buffer();
+
+        assert_eq!(
+            text,
+            "2 results for /x/\n\nsrc/a.rs\n     1- a\n     2: x1\n     3: x2\n     4- c\n"
+        );
+        assert_eq!(locations.len(), 7);
+        assert_eq!(locations[4].as_ref().map(|x| x.line), Some(1));
+        assert_eq!(locations[5].as_ref().map(|x| x.line), Some(2));
+        assert!(locations[6].is_none());
+    }
+
+    #[test]
+    fn jumping_to_a_match_after_multibyte_text() {
+        let dir = TempDir::new("search");
+        let content = "first\néé foo\n";
+        let path = dir.write("a.txt", content);
+
+        let file = search_file(&path, &Regex::new("foo").unwrap()).unwrap();
+        let found = &file.matches[0];
+        let mut cursor = Cursor::new();
+
+        cursor.move_to(content, found.line, found.column);
+
+        assert_eq!(found.column, 3);
+        assert_eq!(cursor.idx, content.find("foo").unwrap());
+        assert_eq!(&content[cursor.idx..cursor.idx + 3], "foo");
+    }
+
+    #[test]
+    fn searching_a_directory_lists_files_by_path() {
+        let dir = TempDir::new("search");
+        dir.write("b.txt", "one\nfoo\n");
+        dir.write("a/c.txt", "foo\nfoo bar\ntwo\n");
+        dir.write("d.bin", "foo\0");
+
+        let mut search = Search::new(dir.path().to_path_buf());
+        search.query = "fo+".to_string();
+        search.start(Waker::default());
+
+        let start = Instant::now();
+
+        while search.receiver.is_some() {
+            search.poll();
+
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "search didn't finish"
+            );
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let (text, locations) = search.results_buffer();
+        let c = PathBuf::from("a").join("c.txt");
+
+        assert_eq!(
+            text,
+            format!(
+                "3 results for /fo+/\n\n{}\n     1: foo\n     2: foo bar\n     3- two\n\nb.txt\n     1- one\n     2: foo\n",
+                c.display()
+            )
+        );
+
+        let jumps: Vec<(usize, PathBuf, usize)> = locations
+            .iter()
+            .enumerate()
+            .filter_map(|(row, x)| {
+                let x = x.as_ref()?;
+                Some((
+                    row,
+                    x.path.strip_prefix(dir.path()).unwrap().to_path_buf(),
+                    x.line,
+                ))
+            })
+            .collect();
+
+        assert_eq!(
+            jumps,
+            [(3, c.clone(), 0), (4, c, 1), (9, PathBuf::from("b.txt"), 1)]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+// the selected row of a list and how far it's scrolled, shared by the pickers, the explorer and
+// the problems panel
+#[derive(Default)]
+pub struct Selection {
+    pub selected: usize,
+    pub scroll: usize,
+}
+
+impl Selection {
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn move_down(&mut self, len: usize) {
+        if self.selected + 1 < len {
+            self.selected += 1;
+        }
+    }
+
+    // the list can shrink under the selection, keeps it on a row that exists
+    pub fn clamp(&mut self, len: usize) {
+        self.selected = self.selected.min(len.saturating_sub(1));
+    }
+
+    // keeps the selected row inside a viewport of `rows` rows
+    pub fn scroll_to_selected(&mut self, rows: usize) {
+        let rows = rows.max(1);
+
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+    }
+
+    // whether the `row`th row shown after the scroll is the selected one
+    pub fn is_selected(&self, row: usize) -> bool {
+        self.scroll + row == self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_follows_the_selection_both_ways() {
+        let mut selection = Selection::default();
+
+        (0..5).for_each(|_| selection.move_down(10));
+        selection.scroll_to_selected(3);
+        assert_eq!((selection.selected, selection.scroll), (5, 3));
+        assert!(selection.is_selected(2));
+
+        selection.move_up();
+        selection.scroll_to_selected(3);
+        assert_eq!(selection.scroll, 3);
+
+        selection.clamp(2);
+        selection.scroll_to_selected(3);
+        assert_eq!((selection.selected, selection.scroll), (1, 1));
+    }
+}
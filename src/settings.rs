@@ -0,0 +1,83 @@
+use serde_json::Value;
+use std::{fs, path::Path, time::Duration};
+
+use crate::{cursor::CursorStyle, editor::Mode};
+
+// how long the cursor stays on and then off
+const CURSOR_BLINK: Duration = Duration::from_millis(530);
+
+// editor preferences from `.rite/settings.json` in the project root, every key is optional:
+// { "reduced_motion": false, "cursor_blink": 530,
+//   "cursor": { "normal": "block", "prompt": "hollow" } }
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub reduced_motion: bool, // animations jump into place and the cursor doesn't blink
+    pub cursor_blink: Option<Duration>, // none keeps the cursor on, set with 0
+    pub cursor_normal: CursorStyle, // while editing
+    pub cursor_prompt: CursorStyle, // while typing into a prompt, the text waits underneath
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            reduced_motion: false,
+            cursor_blink: Some(CURSOR_BLINK),
+            cursor_normal: CursorStyle::Bar,
+            cursor_prompt: CursorStyle::Hollow,
+        }
+    }
+}
+
+impl Settings {
+    pub fn cursor_style(&self, mode: &Mode) -> CursorStyle {
+        match mode {
+            Mode::Normal => self.cursor_normal,
+            _ => self.cursor_prompt,
+        }
+    }
+}
+
+pub fn load(root: &Path) -> Settings {
+    let mut settings = Settings::default();
+
+    let Ok(config) = fs::read_to_string(root.join(".rite").join("settings.json")) else {
+        return settings;
+    };
+
+    let Ok(Value::Object(config)) = serde_json::from_str::<Value>(&config) else {
+        eprintln!("invalid .rite/settings.json");
+        return settings;
+    };
+
+    if let Some(reduced) = config.get("reduced_motion").and_then(|x| x.as_bool()) {
+        settings.reduced_motion = reduced;
+    }
+
+    if let Some(millis) = config.get("cursor_blink").and_then(|x| x.as_u64()) {
+        settings.cursor_blink = Some(Duration::from_millis(millis)).filter(|x| !x.is_zero());
+    }
+
+    let style = |mode: &str| {
+        let name = config.get("cursor")?.get(mode)?.as_str()?;
+        let style = CursorStyle::parse(name);
+
+        if style.is_none() {
+            eprintln!(
+                "unknown cursor style {}, expected block, bar, underline or hollow",
+                name
+            );
+        }
+
+        style
+    };
+
+    if let Some(style) = style("normal") {
+        settings.cursor_normal = style;
+    }
+
+    if let Some(style) = style("prompt") {
+        settings.cursor_prompt = style;
+    }
+
+    settings
+}
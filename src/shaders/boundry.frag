@@ -2,8 +2,19 @@
 
 precision highp float;
 
+in vec3 vertex_color;
+in vec2 vertex_origin; // bottom left of the rectangle in window pixels
+
 out vec4 color;
 
 void main() {
-    color = vec4(1.0f);
-}
\ No newline at end of file
+    // a wave through the middle of the rectangle, everything off it is dropped
+    vec2 local = gl_FragCoord.xy - vertex_origin;
+    float wave = 2.0f + 1.5f * sin(local.x * 0.8f);
+
+    if (abs(local.y - wave) > 0.9f) {
+        discard;
+    }
+
+    color = vec4(vertex_color, 1.0f);
+}
@@ -0,0 +1,18 @@
+#version 300 es
+
+in vec2 position;
+in vec3 color;
+in vec2 origin;
+
+uniform mat4 projection; // window pixels to device coordinates
+uniform mat4 view;
+
+out vec3 vertex_color;
+out vec2 vertex_origin;
+
+void main() {
+    vertex_color = color;
+    // where the origin ends up in the window, to compare with gl_FragCoord
+    vertex_origin = (view * vec4(origin, 0.0f, 1.0f)).xy;
+    gl_Position = projection * view * vec4(position, 0.0f, 1.0f);
+}
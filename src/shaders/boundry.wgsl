@@ -0,0 +1,48 @@
+struct Globals {
+    projection: mat4x4<f32>, // window pixels to device coordinates
+    gamma: f32,
+    size: vec2<f32>, // of the target in pixels
+}
+
+// what can change from one draw to the next
+struct Local {
+    view: mat4x4<f32>, // moves the document under the window
+    text_color: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var<uniform> local: Local;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) origin: vec2<f32>, // bottom left of the rectangle in window pixels
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) origin: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.projection * local.view * vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    // where the origin ends up in the window, to compare with the fragment's position
+    out.origin = (local.view * vec4<f32>(origin, 0.0, 1.0)).xy;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // a wave through the middle of the rectangle, everything off it is dropped. the position
+    // counts from the top, the origin from the bottom
+    let local = vec2<f32>(in.position.x, globals.size.y - in.position.y) - in.origin;
+    let wave = 2.0 + 1.5 * sin(local.x * 0.8);
+
+    if abs(local.y - wave) > 0.9 {
+        discard;
+    }
+
+    return vec4<f32>(in.color, 1.0);
+}
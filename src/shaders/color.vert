@@ -3,9 +3,12 @@
 in vec2 position;
 in vec3 color;
 
+uniform mat4 projection; // window pixels to device coordinates
+uniform mat4 view;
+
 out vec3 vertex_color;
 
 void main() {
     vertex_color = color;
-    gl_Position = vec4(position, 0.0f, 1.0f);
-}
\ No newline at end of file
+    gl_Position = projection * view * vec4(position, 0.0f, 1.0f);
+}
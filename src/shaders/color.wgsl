@@ -0,0 +1,32 @@
+struct Globals {
+    projection: mat4x4<f32>, // window pixels to device coordinates
+    gamma: f32,
+    size: vec2<f32>, // of the target in pixels
+}
+
+// what can change from one draw to the next
+struct Local {
+    view: mat4x4<f32>, // moves the document under the window
+    text_color: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var<uniform> local: Local;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.projection * local.view * vec4<f32>(position, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
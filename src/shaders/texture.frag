@@ -3,13 +3,39 @@
 precision highp float;
 
 in vec2 v_tex_coords;
+in float v_tint;
 
 uniform sampler2D tex;
+uniform sampler2D emoji;
+uniform float gamma;
+uniform vec3 text_color;
+// 0 blends with alpha, 1 and 2 are the mask and add passes of subpixel text
+uniform int blend_pass;
 
 out vec4 color;
 
 void main() {
-    if(texture(tex, v_tex_coords).a < 0.2f)
-        discard;
-    color = texture(tex, v_tex_coords);
-}
\ No newline at end of file
+    // emoji keep their own colors, everything else is coverage in the text color
+    if(v_tint < 0.5) {
+        vec4 sampled = texture(emoji, v_tex_coords);
+
+        if(blend_pass == 0)
+            color = sampled;
+        else if(blend_pass == 1)
+            color = vec4(vec3(sampled.a), 1.0);
+        else
+            color = vec4(sampled.rgb * sampled.a, 1.0);
+
+        return;
+    }
+
+    // rgb is the coverage of each subpixel, alpha the coverage of the whole pixel
+    vec4 coverage = pow(texture(tex, v_tex_coords), vec4(1.0 / gamma));
+
+    if(blend_pass == 0)
+        color = vec4(text_color, coverage.a);
+    else if(blend_pass == 1)
+        color = vec4(coverage.rgb, 1.0);
+    else
+        color = vec4(text_color * coverage.rgb, 1.0);
+}
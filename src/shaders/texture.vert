@@ -2,12 +2,16 @@
 
 in vec2 position;
 in vec2 tex_coords;
+in float tint;
 
-uniform mat4 matrix;
+uniform mat4 projection; // window pixels to device coordinates
+uniform mat4 view;
 
 out vec2 v_tex_coords;
+out float v_tint;
 
 void main() {
     v_tex_coords = tex_coords;
-    gl_Position = vec4(position, 0.0, 1.0) * matrix;
-}
\ No newline at end of file
+    v_tint = tint;
+    gl_Position = projection * view * vec4(position, 0.0, 1.0);
+}
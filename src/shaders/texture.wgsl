@@ -0,0 +1,79 @@
+struct Globals {
+    projection: mat4x4<f32>, // window pixels to device coordinates
+    gamma: f32,
+    size: vec2<f32>, // of the target in pixels
+}
+
+// what can change from one draw to the next
+struct Local {
+    view: mat4x4<f32>, // moves the document under the window
+    text_color: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(1) @binding(0) var<uniform> local: Local;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+@group(0) @binding(2) var emoji: texture_2d<f32>;
+@group(0) @binding(3) var nearest: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: f32,
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) tint: f32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = globals.projection * local.view * vec4<f32>(position, 0.0, 1.0);
+    out.tex_coords = tex_coords;
+    out.tint = tint;
+    return out;
+}
+
+// 0 blends with alpha, 1 and 2 are the mask and add passes of subpixel text
+fn shade(in: VertexOutput, blend_pass: i32) -> vec4<f32> {
+    // emoji keep their own colors, everything else is coverage in the text color
+    if in.tint < 0.5 {
+        let sampled = textureSampleLevel(emoji, nearest, in.tex_coords, 0.0);
+
+        if blend_pass == 0 {
+            return sampled;
+        } else if blend_pass == 1 {
+            return vec4<f32>(vec3<f32>(sampled.a), 1.0);
+        }
+
+        return vec4<f32>(sampled.rgb * sampled.a, 1.0);
+    }
+
+    // rgb is the coverage of each subpixel, alpha the coverage of the whole pixel
+    let sampled = textureSampleLevel(atlas, nearest, in.tex_coords, 0.0);
+    let coverage = pow(sampled, vec4<f32>(1.0 / globals.gamma));
+
+    if blend_pass == 0 {
+        return vec4<f32>(local.text_color, coverage.a);
+    } else if blend_pass == 1 {
+        return vec4<f32>(coverage.rgb, 1.0);
+    }
+
+    return vec4<f32>(local.text_color * coverage.rgb, 1.0);
+}
+
+@fragment
+fn fs_alpha(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, 0);
+}
+
+@fragment
+fn fs_mask(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, 1);
+}
+
+@fragment
+fn fs_add(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in, 2);
+}
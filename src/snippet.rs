@@ -0,0 +1,665 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    ops::Range,
+    path::Path,
+};
+
+use serde_json::Value;
+
+// a snippet offered by completion, the body uses TextMate/LSP snippet syntax
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pub prefix: String,
+    pub description: String,
+    pub body: String,
+    pub extensions: Vec<String>, // file types the snippet is offered in, empty for all
+}
+
+impl Definition {
+    fn new(extensions: &[&str], prefix: &str, description: &str, body: &str) -> Self {
+        Definition {
+            prefix: prefix.to_string(),
+            description: description.to_string(),
+            body: body.to_string(),
+            extensions: extensions.iter().map(|x| x.to_string()).collect(),
+        }
+    }
+
+    // whether the snippet is offered in the file
+    pub fn applies(&self, path: Option<&Path>) -> bool {
+        let extension = path
+            .and_then(|x| x.extension())
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+
+        self.extensions.is_empty() || self.extensions.iter().any(|x| x == extension)
+    }
+}
+
+pub fn builtin() -> Vec<Definition> {
+    let js = &["js", "jsx", "mjs", "ts", "tsx"];
+    let rust = &["rs"];
+
+    vec![
+        Definition::new(js, "log", "console.log", "console.log($1);$0"),
+        Definition::new(
+            js,
+            "fn",
+            "function",
+            "function ${1:name}(${2:args}) {\n    $0\n}",
+        ),
+        Definition::new(
+            js,
+            "for",
+            "for loop",
+            "for (let ${1:i} = 0; $1 < ${2:length}; $1++) {\n    $0\n}",
+        ),
+        Definition::new(js, "imp", "import", "import { $2 } from \"$1\";$0"),
+        Definition::new(rust, "fn", "function", "fn ${1:name}(${2}) {\n    $0\n}"),
+        Definition::new(
+            rust,
+            "test",
+            "test function",
+            "#[test]\nfn ${1:name}() {\n    $0\n}",
+        ),
+        Definition::new(
+            rust,
+            "match",
+            "match expression",
+            "match ${1:value} {\n    ${2:pattern} => $0,\n}",
+        ),
+        Definition::new(rust, "impl", "impl block", "impl ${1:Type} {\n    $0\n}"),
+    ]
+}
+
+// snippets from `.rite/snippets.json` in the project root come after the builtin ones:
+// { "log": { "prefix": "log", "body": ["console.log($1);$0"], "extensions": ["js"] } }
+pub fn load(root: &Path) -> Vec<Definition> {
+    let mut definitions = builtin();
+
+    let Ok(config) = fs::read_to_string(root.join(".rite").join("snippets.json")) else {
+        return definitions;
+    };
+
+    let Ok(Value::Object(snippets)) = serde_json::from_str::<Value>(&config) else {
+        eprintln!("invalid .rite/snippets.json");
+        return definitions;
+    };
+
+    let strings = |value: &Value| -> Vec<String> {
+        match value {
+            Value::String(x) => vec![x.clone()],
+            Value::Array(x) => x
+                .iter()
+                .filter_map(|x| x.as_str().map(String::from))
+                .collect(),
+            _ => Vec::new(),
+        }
+    };
+
+    for (name, snippet) in snippets {
+        // a body given as a list holds its lines
+        let body = strings(&snippet["body"]).join("\n");
+
+        for prefix in strings(&snippet["prefix"]) {
+            definitions.push(Definition {
+                prefix,
+                description: snippet["description"].as_str().unwrap_or(&name).to_string(),
+                body: body.clone(),
+                extensions: strings(&snippet["extensions"]),
+            });
+        }
+    }
+
+    definitions
+}
+
+// the snippet a trigger word expands to
+pub fn find<'a>(
+    definitions: &'a [Definition],
+    prefix: &str,
+    path: Option<&Path>,
+) -> Option<&'a Definition> {
+    definitions
+        .iter()
+        .rev()
+        .find(|x| x.prefix == prefix && x.applies(path))
+}
+
+// values of the TextMate variables at the cursor
+pub fn variables(
+    path: Option<&Path>,
+    content: &str,
+    cursor: usize,
+) -> HashMap<&'static str, String> {
+    let line_start = content[..cursor].rfind('\n').map_or(0, |x| x + 1);
+    let line_end = content[cursor..]
+        .find('\n')
+        .map_or(content.len(), |x| cursor + x);
+    let line = content[..cursor].matches('\n').count();
+
+    let is_word = |x: &char| x.is_alphanumeric() || *x == '_';
+    let word: String = content[line_start..cursor]
+        .chars()
+        .rev()
+        .take_while(is_word)
+        .collect::<Vec<char>>()
+        .into_iter()
+        .rev()
+        .chain(content[cursor..line_end].chars().take_while(is_word))
+        .collect();
+
+    let mut variables = HashMap::from([
+        ("TM_SELECTED_TEXT", String::new()),
+        ("TM_CURRENT_LINE", content[line_start..line_end].to_string()),
+        ("TM_CURRENT_WORD", word),
+        ("TM_LINE_INDEX", line.to_string()),
+        ("TM_LINE_NUMBER", (line + 1).to_string()),
+    ]);
+
+    if let Some(path) = path {
+        let name = |x: Option<&std::ffi::OsStr>| {
+            x.map_or(String::new(), |x| x.to_string_lossy().to_string())
+        };
+
+        variables.insert("TM_FILENAME", name(path.file_name()));
+        variables.insert("TM_FILENAME_BASE", name(path.file_stem()));
+        variables.insert(
+            "TM_DIRECTORY",
+            path.parent()
+                .map_or(String::new(), |x| x.display().to_string()),
+        );
+        variables.insert("TM_FILEPATH", path.display().to_string());
+    }
+
+    variables
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Stop {
+        number: usize,
+        placeholder: Vec<Node>,
+        choices: Vec<String>,
+    },
+    Variable {
+        name: String,
+        default: Vec<Node>,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, char: char) -> bool {
+        let matches = self.peek() == Some(char);
+
+        if matches {
+            self.pos += 1;
+        }
+
+        matches
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+
+        while self.peek().is_some_and(|x| x.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        if !self
+            .peek()
+            .is_some_and(|x| x.is_ascii_alphabetic() || x == '_')
+        {
+            return None;
+        }
+
+        let start = self.pos;
+
+        while self
+            .peek()
+            .is_some_and(|x| x.is_ascii_alphanumeric() || x == '_')
+        {
+            self.pos += 1;
+        }
+
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    // text and fields up to the `}` closing the enclosing field, or the end of the body
+    fn nodes(&mut self, nested: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+
+        while let Some(char) = self.peek() {
+            match char {
+                '}' if nested => break,
+                '\\' => {
+                    self.pos += 1;
+
+                    match self.peek() {
+                        Some(char @ ('$' | '}' | '\\')) => {
+                            text.push(char);
+                            self.pos += 1;
+                        }
+                        _ => text.push('\\'),
+                    }
+                }
+                '$' => {
+                    let start = self.pos;
+                    self.pos += 1;
+
+                    match self.field() {
+                        Some(node) => {
+                            if !text.is_empty() {
+                                nodes.push(Node::Text(std::mem::take(&mut text)));
+                            }
+                            nodes.push(node);
+                        }
+                        // not a field after all, the dollar is plain text
+                        None => {
+                            self.pos = start + 1;
+                            text.push('$');
+                        }
+                    }
+                }
+                char => {
+                    text.push(char);
+                    self.pos += 1;
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        nodes
+    }
+
+    // what follows a `$`
+    fn field(&mut self) -> Option<Node> {
+        let stop = |number, placeholder, choices| Node::Stop {
+            number,
+            placeholder,
+            choices,
+        };
+
+        if let Some(number) = self.number() {
+            return Some(stop(number, Vec::new(), Vec::new()));
+        }
+
+        if let Some(name) = self.name() {
+            return Some(Node::Variable {
+                name,
+                default: Vec::new(),
+            });
+        }
+
+        if !self.eat('{') {
+            return None;
+        }
+
+        if let Some(number) = self.number() {
+            if self.eat(':') {
+                let placeholder = self.nodes(true);
+                return self.eat('}').then(|| stop(number, placeholder, Vec::new()));
+            }
+
+            if self.eat('|') {
+                let choices = self.choices()?;
+                return self.eat('}').then(|| stop(number, Vec::new(), choices));
+            }
+
+            if self.eat('/') {
+                self.skip_transform()?;
+            }
+
+            return self.eat('}').then(|| stop(number, Vec::new(), Vec::new()));
+        }
+
+        let name = self.name()?;
+        let mut default = Vec::new();
+
+        if self.eat(':') {
+            default = self.nodes(true);
+        } else if self.eat('/') {
+            self.skip_transform()?;
+        }
+
+        self.eat('}').then_some(Node::Variable { name, default })
+    }
+
+    // `a,b,c|` after `${1|`
+    fn choices(&mut self) -> Option<Vec<String>> {
+        let mut choices = Vec::new();
+        let mut choice = String::new();
+
+        loop {
+            match self.peek()? {
+                '\\' => {
+                    self.pos += 1;
+                    choice.push(self.peek()?);
+                }
+                ',' => choices.push(std::mem::take(&mut choice)),
+                '|' => {
+                    self.pos += 1;
+                    choices.push(choice);
+                    return Some(choices);
+                }
+                char => choice.push(char),
+            }
+
+            self.pos += 1;
+        }
+    }
+
+    // regex transforms aren't supported, the field is used as if it had none
+    fn skip_transform(&mut self) -> Option<()> {
+        loop {
+            match self.peek()? {
+                '}' => return Some(()),
+                '\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+    }
+}
+
+// a tab stop, every range of it is edited together
+#[derive(Clone, Debug, Default)]
+pub struct Stop {
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+// the text a snippet inserts and where its tab stops ended up
+#[derive(Debug)]
+pub struct Expansion {
+    pub text: String,
+    pub stops: Vec<Stop>, // in tab order, the final cursor position last
+}
+
+struct Renderer<'a> {
+    variables: &'a HashMap<&'static str, String>,
+    indent: &'a str,
+    defaults: HashMap<usize, String>, // placeholder text of stops mirrored without one
+    text: String,
+    stops: BTreeMap<usize, Stop>,
+}
+
+impl Renderer<'_> {
+    // new lines continue at the indentation of the line the snippet starts on
+    fn push(&mut self, text: &str) {
+        for (idx, line) in text.split('\n').enumerate() {
+            if idx > 0 {
+                self.text.push('\n');
+                self.text.push_str(self.indent);
+            }
+            self.text.push_str(line);
+        }
+    }
+
+    fn render(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.push(text),
+                Node::Stop {
+                    number,
+                    placeholder,
+                    choices,
+                } => {
+                    let start = self.text.len();
+
+                    if !placeholder.is_empty() {
+                        self.render(placeholder);
+                    } else if let Some(choice) = choices.first() {
+                        self.push(choice);
+                    } else if let Some(default) = self.defaults.get(number) {
+                        self.text.push_str(&default.clone());
+                    }
+
+                    let stop = self.stops.entry(*number).or_default();
+                    stop.ranges.push(start..self.text.len());
+
+                    if stop.choices.is_empty() {
+                        stop.choices = choices.clone();
+                    }
+                }
+                Node::Variable { name, default } => match self.variables.get(name.as_str()) {
+                    Some(value) if !value.is_empty() => self.push(&value.clone()),
+                    _ if !default.is_empty() => self.render(default),
+                    Some(_) => (),
+                    // unknown variables insert their name
+                    None => self.push(name),
+                },
+            }
+        }
+    }
+}
+
+pub fn expand(body: &str, variables: &HashMap<&'static str, String>, indent: &str) -> Expansion {
+    let mut parser = Parser {
+        chars: body.chars().collect(),
+        pos: 0,
+    };
+    let nodes = parser.nodes(false);
+
+    let render = |defaults| {
+        let mut renderer = Renderer {
+            variables,
+            indent,
+            defaults,
+            text: String::new(),
+            stops: BTreeMap::new(),
+        };
+        renderer.render(&nodes);
+        renderer
+    };
+
+    // mirrors like the second `$1` in `${1:i} < $1` show the placeholder given elsewhere
+    let first = render(HashMap::new());
+    let defaults = first
+        .stops
+        .iter()
+        .filter_map(|(number, stop)| {
+            let text = stop
+                .ranges
+                .iter()
+                .map(|x| &first.text[x.clone()])
+                .find(|x| !x.is_empty())?;
+            Some((*number, text.to_string()))
+        })
+        .collect();
+
+    let Renderer {
+        text, mut stops, ..
+    } = render(defaults);
+
+    // without a `$0` the snippet ends after its text
+    let last = stops.remove(&0).unwrap_or_else(|| Stop {
+        ranges: std::iter::once(text.len()..text.len()).collect(),
+        choices: Vec::new(),
+    });
+
+    let mut stops: Vec<Stop> = stops.into_values().collect();
+    stops.push(last);
+
+    Expansion { text, stops }
+}
+
+// the text a snippet inserts, for previews
+pub fn plain(body: &str) -> String {
+    expand(body, &HashMap::new(), "").text
+}
+
+// a snippet being filled in, its ranges follow the edits made to the buffer
+#[derive(Debug)]
+pub struct Session {
+    pub stops: Vec<Stop>,
+    pub current: usize,
+    pub fresh: bool, // the current placeholder hasn't been typed over yet
+}
+
+impl Session {
+    pub fn new(start: usize, expansion: Expansion) -> Self {
+        let mut stops = expansion.stops;
+
+        for range in stops.iter_mut().flat_map(|x| x.ranges.iter_mut()) {
+            *range = range.start + start..range.end + start;
+        }
+
+        Session {
+            stops,
+            current: 0,
+            fresh: false,
+        }
+    }
+
+    pub fn stop(&self) -> &Stop {
+        &self.stops[self.current]
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.current + 1 >= self.stops.len()
+    }
+
+    // moves the ranges for `edit` being replaced by `len` bytes, the current stop grows with
+    // text typed at its ends while the others only grow with text typed inside them
+    pub fn adjust(&mut self, edit: Range<usize>, len: usize) {
+        let shift = |x: usize| x + len - (edit.end - edit.start);
+
+        for (idx, stop) in self.stops.iter_mut().enumerate() {
+            let current = idx == self.current;
+
+            for range in stop.ranges.iter_mut() {
+                let inside = if current {
+                    range.start <= edit.start && edit.end <= range.end
+                } else {
+                    range.start < edit.start && edit.end < range.end
+                };
+
+                if inside {
+                    range.end = shift(range.end);
+                    continue;
+                }
+
+                let start = if range.start < edit.start {
+                    range.start
+                } else if range.start >= edit.end {
+                    shift(range.start)
+                } else {
+                    edit.start + len
+                };
+
+                let end = if range.end <= edit.start {
+                    range.end
+                } else if range.end >= edit.end {
+                    shift(range.end)
+                } else {
+                    edit.start + len
+                };
+
+                *range = start..end.max(start);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // where each stop is, stops used once have a single range
+    fn ranges(expansion: &Expansion) -> Vec<Range<usize>> {
+        expansion
+            .stops
+            .iter()
+            .map(|x| x.ranges[0].clone())
+            .collect()
+    }
+
+    #[test]
+    fn tab_stops_end_at_the_final_one() {
+        let expansion = expand("console.log($1);$0", &HashMap::new(), "");
+
+        assert_eq!(expansion.text, "console.log();");
+        assert_eq!(ranges(&expansion), [12..12, 14..14]);
+
+        // without a `$0` the cursor ends up after the text
+        let expansion = expand("$2 and $1", &HashMap::new(), "");
+
+        assert_eq!(ranges(&expansion), [5..5, 0..0, 5..5]);
+    }
+
+    #[test]
+    fn placeholders_nest_and_are_mirrored() {
+        let expansion = expand("${1:a ${2:b}}!", &HashMap::new(), "");
+
+        assert_eq!(expansion.text, "a b!");
+        assert_eq!(ranges(&expansion), [0..3, 2..3, 4..4]);
+
+        let expansion = expand("for ${1:i} < $1", &HashMap::new(), "");
+
+        assert_eq!(expansion.text, "for i < i");
+        assert_eq!(expansion.stops[0].ranges, [4..5, 8..9]);
+    }
+
+    #[test]
+    fn choices_variables_and_indentation() {
+        let variables = HashMap::from([("TM_LINE_NUMBER", "3".to_string())]);
+        let expansion = expand(
+            "${1|one,two|} $TM_LINE_NUMBER ${NOPE:x} $NOPE\n$0",
+            &variables,
+            "  ",
+        );
+
+        assert_eq!(expansion.text, "one 3 x NOPE\n  ");
+        assert_eq!(expansion.stops[0].choices, ["one", "two"]);
+    }
+
+    #[test]
+    fn escaped_and_stray_characters_are_text() {
+        assert_eq!(plain("\\$1 \\} \\\\ $ ${ \\a"), "$1 } \\ $ ${ \\a");
+        assert_eq!(plain("${1/(.*)/$1/}x"), "x");
+    }
+
+    #[test]
+    fn stops_follow_edits() {
+        let mut session = Session::new(10, expand("${1:ab} $2", &HashMap::new(), ""));
+        let ranges = |session: &Session| -> Vec<Range<usize>> {
+            session.stops.iter().map(|x| x.ranges[0].clone()).collect()
+        };
+
+        // typing over the current placeholder replaces it, later stops move along
+        session.adjust(10..12, 3);
+        assert_eq!(ranges(&session), [10..13, 14..14, 14..14]);
+
+        // the current stop grows at its end, another stop is only pushed along
+        session.adjust(13..13, 1);
+        session.adjust(15..15, 1);
+        assert_eq!(ranges(&session), [10..14, 16..16, 16..16]);
+
+        // text removed across a stop's start cuts it short
+        session.adjust(8..11, 0);
+        assert_eq!(ranges(&session), [8..11, 13..13, 13..13]);
+    }
+}
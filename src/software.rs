@@ -0,0 +1,245 @@
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    font::BitmapFont,
+    render::{Backend, GAMMA, TEXT_COLOR},
+    vertex::{ColorVertex, SquiggleVertex, TextureVertex},
+    Rectangle,
+};
+
+// the two triangles of a quad, the same ones the GPU draws
+const TRIANGLES: [[usize; 3]; 2] = [[0, 1, 2], [2, 3, 0]];
+
+// draws frames into an image on the CPU, so rendering can be checked without a GPU
+pub struct Software<'a> {
+    pub image: RgbaImage,
+    font: &'a BitmapFont,
+    view: [f32; 2],
+    text_color: [f32; 3],
+}
+
+impl<'a> Software<'a> {
+    pub fn new(font: &'a BitmapFont, width: u32, height: u32) -> Self {
+        Software {
+            image: RgbaImage::new(width, height),
+            font,
+            view: [0.0, 0.0],
+            text_color: TEXT_COLOR,
+        }
+    }
+
+    // where a vertex ends up in the window once moved by the view
+    fn pixel(&self, position: [f32; 2]) -> [f32; 2] {
+        [position[0] + self.view[0], position[1] + self.view[1]]
+    }
+}
+
+impl Backend for Software<'_> {
+    fn clear(&mut self, color: [f32; 3]) {
+        let [red, green, blue] = color.map(channel);
+
+        for pixel in self.image.pixels_mut() {
+            *pixel = Rgba([red, green, blue, 255]);
+        }
+    }
+
+    fn view(&mut self, offset: [f32; 2]) {
+        self.view = offset;
+    }
+
+    fn text_color(&mut self, color: [f32; 3]) {
+        self.text_color = color;
+    }
+
+    fn fill(&mut self, shapes: &[ColorVertex], scissor: Option<Rectangle>) {
+        for quad in shapes.chunks_exact(4) {
+            let corners = [0, 1, 2, 3].map(|x| self.pixel(quad[x].position));
+
+            rasterize(&mut self.image, corners, scissor, |pixel, _, weights| {
+                let color = mix(weights, |x| quad[x].color);
+                *pixel = Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255]);
+            });
+        }
+    }
+
+    fn text(&mut self, shapes: &[TextureVertex], scissor: Option<Rectangle>, subpixel: bool) {
+        let atlas = self.font.image.lock().unwrap();
+        let emoji = self.font.emoji.as_ref().map(|x| x.image.lock().unwrap());
+
+        for quad in shapes.chunks_exact(4) {
+            let corners = [0, 1, 2, 3].map(|x| self.pixel(quad[x].position));
+
+            // emoji come from their own page in their own colors, glyphs are coverage
+            let page = match &emoji {
+                Some(emoji) if quad[0].tint < 0.5 => emoji,
+                _ => &atlas,
+            };
+
+            rasterize(&mut self.image, corners, scissor, |pixel, _, weights| {
+                let tex_coords = mix(weights, |x| quad[x].tex_coords);
+                let sampled = sample(page, tex_coords).0.map(|x| x as f32 / 255.0);
+
+                let (color, mask) = if quad[0].tint < 0.5 {
+                    ([sampled[0], sampled[1], sampled[2]], [sampled[3]; 3])
+                } else if subpixel {
+                    (
+                        self.text_color,
+                        [0, 1, 2].map(|x| sampled[x].powf(1.0 / GAMMA)),
+                    )
+                } else {
+                    (self.text_color, [sampled[3].powf(1.0 / GAMMA); 3])
+                };
+
+                for x in 0..3 {
+                    let under = pixel[x] as f32 / 255.0;
+                    pixel[x] = channel(under * (1.0 - mask[x]) + color[x] * mask[x]);
+                }
+            });
+        }
+    }
+
+    fn squiggle(&mut self, shapes: &[SquiggleVertex], scissor: Option<Rectangle>) {
+        for quad in shapes.chunks_exact(4) {
+            let corners = [0, 1, 2, 3].map(|x| self.pixel(quad[x].position));
+            let view = self.view;
+
+            rasterize(&mut self.image, corners, scissor, |pixel, at, weights| {
+                // the same wave as boundry.frag, around the origin moved with the view
+                let origin = mix(weights, |x| quad[x].origin);
+                let local = [at[0] - origin[0] - view[0], at[1] - origin[1] - view[1]];
+                let wave = 2.0 + 1.5 * (local[0] * 0.8).sin();
+
+                if (local[1] - wave).abs() > 0.9 {
+                    return;
+                }
+
+                let color = mix(weights, |x| quad[x].color);
+                *pixel = Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255]);
+            });
+        }
+    }
+}
+
+fn channel(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// an attribute interpolated between the corners of a triangle
+fn mix<const N: usize>(
+    weights: [(usize, f32); 3],
+    attribute: impl Fn(usize) -> [f32; N],
+) -> [f32; N] {
+    let mut value = [0.0; N];
+
+    for (corner, weight) in weights {
+        for (x, component) in attribute(corner).into_iter().enumerate() {
+            value[x] += component * weight;
+        }
+    }
+
+    value
+}
+
+// the texel under normalized coordinates, without filtering like the GPU's nearest sampling
+fn sample(page: &RgbaImage, tex_coords: [f32; 2]) -> Rgba<u8> {
+    let x = (tex_coords[0] * page.width() as f32).floor() as i64;
+    let y = (tex_coords[1] * page.height() as f32).floor() as i64;
+
+    *page.get_pixel(
+        x.clamp(0, page.width() as i64 - 1) as u32,
+        y.clamp(0, page.height() as i64 - 1) as u32,
+    )
+}
+
+// twice the signed area of the triangle a, b, p, positive if p is left of a to b
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+// calls `shade` for every pixel whose center is inside the quad, with the center in window
+// pixels and the weights of the corners of the triangle it's in
+fn rasterize(
+    image: &mut RgbaImage,
+    corners: [[f32; 2]; 4],
+    scissor: Option<Rectangle>,
+    mut shade: impl FnMut(&mut Rgba<u8>, [f32; 2], [(usize, f32); 3]),
+) {
+    let (width, height) = image.dimensions();
+
+    // whole pixels like the GPU's scissor test
+    let (clip_left, clip_bottom, clip_right, clip_top) = match scissor {
+        Some(rect) => {
+            let (left, bottom) = (rect.left.max(0.0) as u32, rect.bottom.max(0.0) as u32);
+            let (right, top) = (
+                left + rect.width.max(0.0) as u32,
+                bottom + rect.height.max(0.0) as u32,
+            );
+
+            (left, bottom, right.min(width), top.min(height))
+        }
+        None => (0, 0, width, height),
+    };
+
+    for [a, mut b, mut c] in TRIANGLES {
+        let mut area = edge(corners[a], corners[b], corners[c]);
+
+        // degenerate, like the repeated corner of a triangle drawn as a quad
+        if area == 0.0 {
+            continue;
+        }
+
+        // counter clockwise, so a shared edge runs opposite ways in the two triangles
+        if area < 0.0 {
+            std::mem::swap(&mut b, &mut c);
+            area = -area;
+        }
+
+        let points = [corners[a], corners[b], corners[c]];
+
+        // the triangle's bounds inside the scissor
+        let min = |axis: usize| points.iter().map(|x| x[axis]).fold(f32::MAX, f32::min);
+        let max = |axis: usize| points.iter().map(|x| x[axis]).fold(f32::MIN, f32::max);
+
+        let left = (min(0).max(0.0).floor() as u32).max(clip_left);
+        let right = (max(0).max(0.0).ceil() as u32).min(clip_right);
+        let bottom = (min(1).max(0.0).floor() as u32).max(clip_bottom);
+        let top = (max(1).max(0.0).ceil() as u32).min(clip_top);
+
+        for y in bottom..top {
+            for x in left..right {
+                let at = [x as f32 + 0.5, y as f32 + 0.5];
+
+                let weights = [
+                    edge(points[1], points[2], at),
+                    edge(points[2], points[0], at),
+                    edge(points[0], points[1], at),
+                ];
+
+                // a center right on an edge belongs to only one of the triangles sharing it
+                let inside = (0..3).all(|x| {
+                    let (from, to) = (points[(x + 1) % 3], points[(x + 2) % 3]);
+
+                    weights[x] > 0.0
+                        || (weights[x] == 0.0
+                            && (to[1] < from[1] || (to[1] == from[1] && to[0] > from[0])))
+                });
+
+                if !inside {
+                    continue;
+                }
+
+                let pixel = image.get_pixel_mut(x, height - 1 - y);
+
+                shade(
+                    pixel,
+                    at,
+                    [
+                        (a, weights[0] / area),
+                        (b, weights[1] / area),
+                        (c, weights[2] / area),
+                    ],
+                );
+            }
+        }
+    }
+}
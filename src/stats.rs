@@ -0,0 +1,60 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// how many of the latest frames the averages are taken over
+const HISTORY: usize = 120;
+
+// timing of recently drawn frames, shown in the debug overlay
+#[derive(Default)]
+pub struct FrameStats {
+    frames: VecDeque<(Instant, Duration)>, // when each frame finished and how long it took
+    pub total: usize,                      // frames drawn since startup
+}
+
+impl FrameStats {
+    pub fn record(&mut self, started: Instant) {
+        let now = Instant::now();
+
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back((now, now - started));
+        self.total += 1;
+    }
+
+    // frames finished within the last second, lower than the refresh rate while idle
+    pub fn per_second(&self, now: Instant) -> usize {
+        self.frames
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= Duration::from_secs(1))
+            .count()
+    }
+
+    pub fn average(&self) -> Duration {
+        let sum: Duration = self.frames.iter().map(|(_, took)| *took).sum();
+
+        sum.checked_div(self.frames.len() as u32)
+            .unwrap_or_default()
+    }
+
+    pub fn max(&self) -> Duration {
+        self.frames
+            .iter()
+            .map(|(_, took)| *took)
+            .max()
+            .unwrap_or_default()
+    }
+
+    pub fn text(&self, now: Instant) -> String {
+        format!(
+            "{} fps  {:.2} ms avg  {:.2} ms max  {} frames",
+            self.per_second(now),
+            self.average().as_secs_f64() * 1000.0,
+            self.max().as_secs_f64() * 1000.0,
+            self.total
+        )
+    }
+}
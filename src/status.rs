@@ -0,0 +1,233 @@
+use crate::{diagnostics::Severity, editor::Editor};
+
+// which end of the status bar a segment sits at
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+// a piece of the status bar worked out from the editor every frame, none hides it
+pub struct Segment {
+    pub id: &'static str,
+    pub side: Side,
+    pub text: fn(&Editor) -> Option<String>,
+}
+
+// the line at the bottom of the window. segments are registered like commands, so anything
+// holding the editor can add its own, left ones go from the left edge and right ones end at
+// the right edge in the order they're added
+#[derive(Default)]
+pub struct StatusBar {
+    pub segments: Vec<Segment>,
+}
+
+impl StatusBar {
+    // replaces a segment with the same id where it is, otherwise it goes last on its side
+    pub fn register(&mut self, id: &'static str, side: Side, text: fn(&Editor) -> Option<String>) {
+        let segment = Segment { id, side, text };
+
+        match self.segments.iter_mut().find(|x| x.id == id) {
+            Some(existing) => *existing = segment,
+            None => self.segments.push(segment),
+        }
+    }
+
+    // what the visible segments on a side say, in order
+    pub fn texts(&self, editor: &Editor, side: Side) -> Vec<String> {
+        self.segments
+            .iter()
+            .filter(|x| x.side == side)
+            .filter_map(|x| (x.text)(editor))
+            .collect()
+    }
+}
+
+// the segments every editor starts with
+pub fn default_segments() -> StatusBar {
+    let mut status = StatusBar::default();
+
+    status.register("file", Side::Left, |editor| {
+        let buffer = editor.workspace.active();
+
+        Some(if buffer.is_dirty() {
+            format!("{} *", buffer.name)
+        } else {
+            buffer.name.clone()
+        })
+    });
+    status.register("position", Side::Left, |editor| {
+        let buffer = editor.workspace.active();
+        let line = buffer.line(buffer.cursor.cursor_y);
+        let column = line[..buffer.cursor.cursor_x].chars().count();
+
+        Some(format!(
+            "Ln {}, Col {}",
+            buffer.cursor.cursor_y + 1,
+            column + 1
+        ))
+    });
+    status.register("selection", Side::Left, |editor| {
+        let buffer = editor.workspace.active();
+
+        if !buffer.cursors.is_empty() {
+            return Some(format!("{} cursors", buffer.cursors.len() + 1));
+        }
+
+        let selection = buffer.selection()?;
+        let selected = &buffer.content[selection];
+        let chars = selected.chars().count();
+        let lines = selected.matches('\n').count() + 1;
+
+        Some(if lines > 1 {
+            format!("{} selected, {} lines", chars, lines)
+        } else {
+            format!("{} selected", chars)
+        })
+    });
+    status.register("problems", Side::Left, |editor| {
+        let errors = editor.diagnostics.count(Severity::Error);
+        let warnings = editor.diagnostics.count(Severity::Warning);
+
+        (errors + warnings > 0).then(|| format!("{} errors, {} warnings", errors, warnings))
+    });
+    // the most severe problem on the cursor line
+    status.register("diagnostic", Side::Left, |editor| {
+        let buffer = editor.workspace.active();
+        let path = buffer.path.as_ref()?;
+
+        let diagnostic = editor
+            .diagnostics
+            .for_file(path)
+            .into_iter()
+            .filter(|x| (x.range.start.line..=x.range.end.line).contains(&buffer.cursor.cursor_y))
+            .min_by_key(|x| x.severity)?;
+
+        Some(match &diagnostic.source {
+            Some(source) => format!("{}: {}", source, diagnostic.message),
+            None => diagnostic.message.clone(),
+        })
+    });
+    status.register("indentation", Side::Right, |editor| {
+        let buffer = editor.workspace.active();
+
+        Some(buffer.indentation.text(&buffer.content, buffer.version))
+    });
+    // contents are read and written as UTF-8 only
+    status.register("encoding", Side::Right, |_| Some("UTF-8".to_string()));
+    status.register("line_ending", Side::Right, |editor| {
+        let crlf = editor.workspace.active().crlf;
+
+        Some(if crlf { "CRLF" } else { "LF" }.to_string())
+    });
+    status.register("language", Side::Right, |editor| {
+        let path = editor.workspace.active().path.as_ref();
+        let language = path.and_then(|x| editor.lsp.language(x));
+
+        Some(language.unwrap_or("plain text").to_string())
+    });
+
+    status
+}
+
+// the indentation segment's text, only worked out again once the buffer changed, like its folds
+#[derive(Debug, Default)]
+pub struct Indentation {
+    text: String,
+    version: Option<i32>, // buffer version the text was worked out for
+}
+
+impl Indentation {
+    pub fn refresh(&mut self, content: &str, version: i32) {
+        if self.version == Some(version) {
+            return;
+        }
+
+        self.text = indentation(content);
+        self.version = Some(version);
+    }
+
+    // the text for the buffer as it is, a version the editor hasn't refreshed for yet is scanned
+    pub fn text(&self, content: &str, version: i32) -> String {
+        if self.version == Some(version) {
+            self.text.clone()
+        } else {
+            indentation(content)
+        }
+    }
+}
+
+// how a buffer is indented going by its indented lines, the narrowest indent of spaces is taken
+// as one level
+fn indentation(content: &str) -> String {
+    let mut tabs = 0;
+    let mut spaces = Vec::new();
+
+    for line in content.split('\n').filter(|x| !x.trim().is_empty()) {
+        if line.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+
+        let width = line.len() - line.trim_start_matches(' ').len();
+
+        // the stars lining up a block comment are one space in
+        if width > 0 && !line.trim_start().starts_with('*') {
+            spaces.push(width);
+        }
+    }
+
+    if tabs > spaces.len() {
+        return "Tabs".to_string();
+    }
+
+    format!("Spaces: {}", spaces.into_iter().min().unwrap_or(4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indentation_is_the_narrowest_indent() {
+        assert_eq!(
+            indentation("fn main() {\n    if x {\n        y();\n    }\n}"),
+            "Spaces: 4"
+        );
+        assert_eq!(
+            indentation("a:\n  b:\n    c\n/*\n * comment\n */"),
+            "Spaces: 2"
+        );
+        assert_eq!(indentation("if x:\n\ty()\n\tz()\n"), "Tabs");
+        assert_eq!(indentation("no indent"), "Spaces: 4");
+    }
+
+    #[test]
+    fn indentation_is_worked_out_once_per_version() {
+        let mut cached = Indentation::default();
+
+        cached.refresh("a\n  b", 1);
+        assert_eq!(cached.text("a\n  b", 1), "Spaces: 2");
+
+        // the same version isn't looked at again
+        cached.refresh("a\n\tb", 1);
+        assert_eq!(cached.text("a\n\tb", 1), "Spaces: 2");
+
+        assert_eq!(cached.text("a\n\tb", 2), "Tabs");
+        cached.refresh("a\n\tb", 2);
+        assert_eq!(cached.text, "Tabs");
+    }
+
+    #[test]
+    fn registering_an_id_again_replaces_it_in_place() {
+        let mut status = default_segments();
+        let ids = |status: &StatusBar| status.segments.iter().map(|x| x.id).collect::<Vec<_>>();
+        let before = ids(&status);
+
+        status.register("encoding", Side::Right, |_| None);
+        status.register("vim", Side::Left, |_| Some("NORMAL".to_string()));
+
+        assert_eq!(ids(&status)[..before.len()], before[..]);
+        assert_eq!(ids(&status).last(), Some(&"vim"));
+    }
+}
@@ -1,94 +1,189 @@
-use core::panic;
+use std::rc::Rc;
 
-use winit::window::Window;
+use crate::{
+    buffer::Buffer,
+    fold::Folds,
+    font::{BitmapFont, SUBPIXEL_STEPS},
+    layout::Layout,
+    vertex::TextureVertex,
+    Rectangle,
+};
 
-use crate::{font::BitmapFont, scalable, vertex::TextureVertex, Rectangle};
+// space between the edges of a pane and its text
+pub const PADDING: f32 = 16.0;
 
 pub struct TextRenderer<'a> {
-    bitmap: &'a BitmapFont,
+    pub bitmap: &'a BitmapFont,
+    pub layout: Rc<Layout>,
 }
 
 impl<'a> TextRenderer<'a> {
-    pub fn new(bitmap: &'a BitmapFont) -> Self {
-        TextRenderer { bitmap }
+    pub fn new(bitmap: &'a BitmapFont, layout: Rc<Layout>) -> Self {
+        TextRenderer { bitmap, layout }
     }
 
-    fn generate_shapes(&self, string: &mut String, x: f32, y: f32) -> Vec<TextureVertex> {
+    // `first_line` is the buffer line the string starts at, the lines hidden by `folds` are
+    // skipped and their placeholder is drawn after the line they fold into
+    fn generate_shapes(
+        &self,
+        string: &str,
+        first_line: usize,
+        folds: &Folds,
+        x: f32,
+        y: f32,
+        viewport: Rectangle,
+    ) -> Vec<TextureVertex> {
         let mut shapes: Vec<_> = Vec::new();
 
-        let mut curr_x = x;
         let mut curr_y = y;
 
-        for letter in string.chars() {
-            if letter == '\n' {
-                curr_x = x;
-                curr_y -= self.bitmap.ascent.abs() + self.bitmap.descent.abs();
-                continue;
+        for (idx, line) in string.split('\n').enumerate() {
+            // the remaining lines are all below the viewport
+            if curr_y + self.bitmap.ascent < viewport.bottom {
+                break;
             }
 
-            if letter == ' ' {
-                curr_x += 12.0;
+            let line_number = first_line + idx;
+
+            if folds.is_hidden(line_number) {
                 continue;
             }
 
-            let char = self
-                .bitmap
-                .char
-                .get(&letter)
-                .unwrap_or_else(|| panic!("character {} not included in bitmap", letter));
+            let curr_x = self.generate_line(line, x, curr_y, viewport, &mut shapes);
+
+            if let Some(fold) = folds.folded_at(line_number) {
+                self.generate_line(&fold.placeholder(), curr_x, curr_y, viewport, &mut shapes);
+            }
+
+            curr_y -= self.layout.line_height();
+        }
+
+        shapes
+    }
+
+    // a single line starting at (x, y) shaped and placed by the layout, returns where it ends
+    fn generate_line(
+        &self,
+        line: &str,
+        x: f32,
+        y: f32,
+        viewport: Rectangle,
+        shapes: &mut Vec<TextureVertex>,
+    ) -> f32 {
+        let layout = self.layout.line(line);
+
+        for glyph in &layout.glyphs {
+            // clipped anyway, skip to the next line
+            if x + glyph.x > viewport.left + viewport.width {
+                break;
+            }
+
+            // snap the pen to a whole pixel plus one of the subpixel offsets cached in the atlas
+            let pen = x + glyph.x + glyph.dx;
+            let mut whole = pen.floor();
+            let mut step = ((pen - whole) * SUBPIXEL_STEPS as f32).round() as u8;
+
+            if step == SUBPIXEL_STEPS {
+                whole += 1.0;
+                step = 0;
+            }
+
+            // emoji come from their own page, missing chars are left blank like spaces
+            let char = match &self.bitmap.emoji {
+                Some(emoji) if glyph.color => {
+                    whole = pen.round();
+                    emoji.get(glyph.id)
+                }
+                _ => self.bitmap.glyph(glyph.id, step),
+            };
+
+            let Some(char) = char.filter(|_| glyph.id != 0) else {
+                continue;
+            };
 
             let rect = Rectangle {
-                bottom: curr_y - (char.offset_top),
-                left: curr_x + char.offset_left,
+                bottom: (y - char.offset_top + glyph.dy).round(),
+                left: whole + char.offset_left,
                 width: char.width,
                 height: char.height,
             };
 
+            let [left, top, right, bottom] = char.tex_coords;
+
             let texture_rect = Rectangle {
-                bottom: char.height,
-                left: char.id as f32,
-                width: char.width,
-                height: char.height,
+                bottom,
+                left,
+                width: right - left,
+                height: bottom - top,
             };
 
-            shapes.extend_from_slice(&TextureVertex::from(rect, texture_rect));
-
-            curr_x += char.advance;
+            shapes.extend(
+                TextureVertex::from(rect, texture_rect)
+                    .into_iter()
+                    .map(|vertex| TextureVertex {
+                        tint: if glyph.color { 0.0 } else { 1.0 },
+                        ..vertex
+                    }),
+            );
         }
 
-        shapes
+        x + layout.width
     }
 
-    fn scale_shapes(&self, shapes: &mut Vec<TextureVertex>, window: &Window) {
-        let bitmap_width = self.bitmap.texture.width();
-        let bitmap_height = self.bitmap.texture.height();
+    // horizontal advance of a single line, laid out the same way as generate_shapes
+    pub fn width(&self, string: &str) -> f32 {
+        self.layout.width(string)
+    }
 
-        let size = window.inner_size();
+    // lays out text with its first baseline at (x, y), measured from the top left of the viewport
+    pub fn render(&self, string: &str, viewport: Rectangle, x: f32, y: f32) -> Vec<TextureVertex> {
+        self.generate_shapes(
+            string,
+            0,
+            &Folds::default(),
+            viewport.left + x,
+            viewport.bottom + viewport.height - y,
+            viewport,
+        )
+    }
 
-        let mut shape_iter = shapes.iter_mut();
+    // how far a buffer's text is moved up to show its `scroll` line at the top, the text itself
+    // stays where it is in the document
+    pub fn scroll_offset(&self, buffer: &Buffer, scroll: usize) -> f32 {
+        let row = buffer
+            .folds
+            .row(buffer.folds.visible_line(scroll), 0)
+            .unwrap_or_default();
 
-        while let Some(vertex) = shape_iter.next() {
-            scalable::scale(
-                vertex,
-                bitmap_height as f32,
-                bitmap_width as f32,
-                size.height as f32,
-                size.width as f32,
-            );
-        }
+        row as f32 * self.layout.line_height()
     }
 
-    pub fn render(
+    // like `render`, for a buffer from its `scroll` line on with folded regions collapsed. the
+    // lines are placed where they are in the document and the view moves them up by `offset`
+    pub fn render_buffer(
         &self,
-        string: &mut String,
+        buffer: &Buffer,
+        scroll: usize,
+        offset: f32,
+        viewport: Rectangle,
         x: f32,
         y: f32,
-        window: &Window,
     ) -> Vec<TextureVertex> {
-        let mut shapes = self.generate_shapes(string, x, y);
-
-        self.scale_shapes(&mut shapes, window);
-
-        shapes
+        let top = viewport.bottom + viewport.height - self.scroll_offset(buffer, scroll);
+
+        // the viewport where it is in the document
+        let viewport = Rectangle {
+            bottom: viewport.bottom - offset,
+            ..viewport
+        };
+
+        self.generate_shapes(
+            buffer.content_from(scroll),
+            scroll,
+            &buffer.folds,
+            viewport.left + x,
+            top - y,
+            viewport,
+        )
     }
 }
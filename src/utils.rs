@@ -1 +1,3 @@
 pub mod interpolation;
+#[cfg(test)]
+pub mod temp;
@@ -1,3 +1,27 @@
 pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
     a + (b - a) * t
 }
+
+// how far along an animation is for a fraction `t` of its time, both from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Easing {
+    EaseOut,   // fast start, slows into place
+    EaseInOut, // slow at both ends
+}
+
+impl Easing {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        match self {
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t.powi(3)
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) / 2.0
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,19 @@
+use bytemuck::{Pod, Zeroable};
 use glium::implement_vertex;
 
 use crate::Rectangle;
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
 pub struct TextureVertex {
     pub position: [f32; 2],
     pub tex_coords: [f32; 2],
+    pub tint: f32, // 1 for glyph coverage in the text color, 0 for the emoji page's own colors
 }
-implement_vertex!(TextureVertex, position, tex_coords);
+implement_vertex!(TextureVertex, position, tex_coords, tint);
 
 impl TextureVertex {
+    // a quad as its four corners, drawn as two triangles by the renderer
     pub fn from(rect: Rectangle, texture_rect: Rectangle) -> Vec<TextureVertex> {
         let bottom = rect.bottom as f32;
         let left = rect.left as f32;
@@ -17,6 +21,7 @@ impl TextureVertex {
         let width = rect.width as f32;
 
         let texture_bottom = texture_rect.bottom as f32;
+        let texture_top = texture_bottom - texture_rect.height;
         let texture_left = texture_rect.left as f32;
         let texture_width = texture_rect.width as f32;
 
@@ -24,39 +29,34 @@ impl TextureVertex {
             TextureVertex {
                 // top left
                 position: [left, bottom + height],
-                tex_coords: [texture_left, 0.0],
+                tex_coords: [texture_left, texture_top],
+                tint: 1.0,
             },
             TextureVertex {
                 // top right
                 position: [left + width, bottom + height],
-                tex_coords: [texture_left + texture_width, 0.0],
-            },
-            TextureVertex {
-                // bottom right
-                position: [left + width, bottom],
-                tex_coords: [texture_left + texture_width, texture_bottom],
+                tex_coords: [texture_left + texture_width, texture_top],
+                tint: 1.0,
             },
             TextureVertex {
                 // bottom right
                 position: [left + width, bottom],
                 tex_coords: [texture_left + texture_width, texture_bottom],
+                tint: 1.0,
             },
             TextureVertex {
                 // bottom left
                 position: [left, bottom],
                 tex_coords: [texture_left, texture_bottom],
-            },
-            TextureVertex {
-                // top left
-                position: [left, bottom + height],
-                tex_coords: [texture_left, 0.0],
+                tint: 1.0,
             },
         ];
         shape
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
 pub struct ColorVertex {
     pub position: [f32; 2],
     pub color: [f32; 3],
@@ -64,6 +64,7 @@ pub struct ColorVertex {
 implement_vertex!(ColorVertex, position, color);
 
 impl ColorVertex {
+    // a quad as its four corners, drawn as two triangles by the renderer
     pub fn from(rect: Rectangle, color: [f32; 3]) -> Vec<ColorVertex> {
         let bottom = rect.bottom as f32;
         let left = rect.left as f32;
@@ -86,22 +87,35 @@ impl ColorVertex {
                 position: [left + width, bottom],
                 color,
             },
-            ColorVertex {
-                // bottom right
-                position: [left + width, bottom],
-                color,
-            },
             ColorVertex {
                 // bottom left
                 position: [left, bottom],
                 color,
             },
-            ColorVertex {
-                // top left
-                position: [left, bottom + height],
-                color,
-            },
         ];
         shape
     }
 }
+
+// a color rectangle that remembers where it starts, so the fragment shader can draw a wave in it
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[repr(C)]
+pub struct SquiggleVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 3],
+    pub origin: [f32; 2],
+}
+implement_vertex!(SquiggleVertex, position, color, origin);
+
+impl SquiggleVertex {
+    pub fn from(rect: Rectangle, color: [f32; 3]) -> Vec<SquiggleVertex> {
+        ColorVertex::from(rect, color)
+            .into_iter()
+            .map(|vertex| SquiggleVertex {
+                position: vertex.position,
+                color,
+                origin: [rect.left, rect.bottom],
+            })
+            .collect()
+    }
+}
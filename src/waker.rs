@@ -0,0 +1,21 @@
+use std::sync::{Arc, Mutex};
+
+type Wake = Box<dyn Fn() + Send>;
+
+// lets background threads wake the event loop once they have something for the editor, so the
+// loop can sleep instead of checking back on a timer. clones share the wake up, which is set once
+// there's a loop to wake and does nothing before that
+#[derive(Clone, Default)]
+pub struct Waker(Arc<Mutex<Option<Wake>>>);
+
+impl Waker {
+    pub fn set(&self, wake: impl Fn() + Send + 'static) {
+        *self.0.lock().unwrap() = Some(Box::new(wake));
+    }
+
+    pub fn wake(&self) {
+        if let Some(wake) = &*self.0.lock().unwrap() {
+            wake();
+        }
+    }
+}
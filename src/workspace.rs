@@ -0,0 +1,318 @@
+use std::{io, path::Path};
+
+use crate::{
+    buffer::Buffer,
+    cursor::Cursor,
+    pane::{Direction, Layout, Pane},
+    Rectangle,
+};
+
+pub struct Workspace {
+    pub buffers: Vec<Buffer>,
+    pub active: usize, // buffer shown in the focused pane
+    pub layout: Layout,
+    pub focused: usize, // id of the focused pane
+    next_pane_id: usize,
+}
+
+impl Workspace {
+    pub fn new(buffer: Buffer) -> Self {
+        Workspace {
+            buffers: vec![buffer],
+            active: 0,
+            layout: Layout::Leaf(Pane {
+                id: 0,
+                buffer: 0,
+                cursor: Cursor::new(),
+                scroll: 0,
+            }),
+            focused: 0,
+            next_pane_id: 1,
+        }
+    }
+
+    pub fn active(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.active]
+    }
+
+    pub fn add(&mut self, buffer: Buffer) -> usize {
+        self.buffers.push(buffer);
+        self.active = self.buffers.len() - 1;
+        self.active
+    }
+
+    // focuses the buffer if the file is already open
+    pub fn open(&mut self, path: &Path) -> io::Result<usize> {
+        if let Some(idx) = self.find(path) {
+            self.active = idx;
+            return Ok(idx);
+        }
+
+        let buffer = Buffer::open(path)?;
+
+        // an untouched scratch buffer is replaced instead of kept around
+        if self.buffers.len() == 1
+            && self.active().path.is_none()
+            && self.active().content.is_empty()
+        {
+            self.buffers[0] = buffer;
+            self.active = 0;
+            return Ok(0);
+        }
+
+        Ok(self.add(buffer))
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        let path = std::path::absolute(path).ok()?;
+
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.path.as_deref() == Some(&path))
+    }
+
+    // index of the buffer holding the file, loaded without switching to it if needed
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        if let Some(idx) = self.find(path) {
+            return Ok(idx);
+        }
+
+        self.buffers.push(Buffer::open(path)?);
+        Ok(self.buffers.len() - 1)
+    }
+
+    pub fn next(&mut self) {
+        self.active = (self.active + 1) % self.buffers.len();
+    }
+
+    pub fn prev(&mut self) {
+        self.active = (self.active + self.buffers.len() - 1) % self.buffers.len();
+    }
+
+    pub fn close(&mut self, idx: usize) {
+        self.follow_edits();
+        self.buffers.remove(idx);
+
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::new("untitled", String::new()));
+        }
+
+        if self.active > idx {
+            self.active -= 1;
+        }
+
+        self.active = self.active.min(self.buffers.len() - 1);
+
+        // other panes showing the closed buffer fall back to the focused one
+        let focused = self.focused;
+        let active = self.active;
+        let cursor = self.buffers[active].cursor.clone();
+
+        for pane in self.layout.panes_mut() {
+            if pane.id == focused {
+                continue;
+            }
+
+            if pane.buffer == idx {
+                pane.buffer = active;
+                pane.cursor = cursor.clone();
+                pane.scroll = 0;
+            } else if pane.buffer > idx {
+                pane.buffer -= 1;
+            }
+        }
+    }
+
+    pub fn split(&mut self, direction: Direction) {
+        let pane = Pane {
+            id: self.next_pane_id,
+            buffer: self.active,
+            cursor: self.active().cursor.clone(),
+            scroll: self.active().scroll,
+        };
+
+        self.next_pane_id += 1;
+
+        let id = pane.id;
+
+        if self.layout.split(self.focused, direction, pane).is_ok() {
+            self.focus(id);
+        }
+    }
+
+    pub fn close_pane(&mut self) {
+        if self.layout.panes().len() == 1 {
+            return;
+        }
+
+        self.layout.remove(self.focused);
+
+        let id = self.layout.panes()[0].id;
+        self.load_pane(id);
+    }
+
+    pub fn focus(&mut self, id: usize) {
+        if id == self.focused {
+            return;
+        }
+
+        let active = self.active;
+        let cursor = self.active().cursor.clone();
+        let scroll = self.active().scroll;
+
+        if let Some(pane) = self.layout.pane_mut(self.focused) {
+            pane.buffer = active;
+            pane.cursor = cursor;
+            pane.scroll = scroll;
+        }
+
+        self.load_pane(id);
+    }
+
+    // moves focus to the closest pane in the given direction
+    pub fn focus_towards(&mut self, area: Rectangle, dx: f32, dy: f32) {
+        let viewports = self.layout.viewports(area);
+
+        let Some((_, current)) = viewports.iter().find(|(id, _)| *id == self.focused) else {
+            return;
+        };
+
+        let center = |rect: &Rectangle| {
+            (
+                rect.left + rect.width / 2.0,
+                rect.bottom + rect.height / 2.0,
+            )
+        };
+
+        let (x, y) = center(current);
+
+        let closest = viewports
+            .iter()
+            .filter(|(id, _)| *id != self.focused)
+            .filter_map(|(id, rect)| {
+                let (other_x, other_y) = center(rect);
+                let along = (other_x - x) * dx + (other_y - y) * dy;
+                let across = (other_x - x) * dy - (other_y - y) * dx;
+
+                (along > 0.0).then_some((*id, along + across.abs() * 2.0))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((id, _)) = closest {
+            self.focus(id);
+        }
+    }
+
+    fn load_pane(&mut self, id: usize) {
+        self.follow_edits();
+
+        let Some(pane) = self.layout.pane_mut(id) else {
+            return;
+        };
+
+        let buffer = pane.buffer;
+        let cursor = pane.cursor.clone();
+        let scroll = pane.scroll;
+
+        self.focused = id;
+        self.active = buffer.min(self.buffers.len() - 1);
+
+        let buffer = self.active_mut();
+        let idx = buffer.content.floor_char_boundary(cursor.idx);
+        buffer.cursor = cursor;
+        buffer.cursor.set_idx(&buffer.content, idx);
+        buffer.scroll = scroll;
+    }
+
+    // moves the cursors of unfocused panes along with edits made to their buffers elsewhere,
+    // the same way `Buffer::replace` moves its own cursor. content replaced without edits, like
+    // search results, only gets them back inside it
+    pub fn follow_edits(&mut self) {
+        for pane in self.layout.panes_mut() {
+            let Some(buffer) = self.buffers.get(pane.buffer) else {
+                continue;
+            };
+
+            let stale = !buffer.content.is_char_boundary(pane.cursor.idx);
+
+            if pane.id == self.focused || (buffer.edits.is_empty() && !stale) {
+                continue;
+            }
+
+            let idx = buffer
+                .edits
+                .iter()
+                .fold(pane.cursor.idx, |idx, (range, inserted)| {
+                    if idx >= range.end {
+                        idx + inserted - range.len()
+                    } else if idx > range.start {
+                        range.start + inserted
+                    } else {
+                        idx
+                    }
+                });
+
+            pane.cursor
+                .set_idx(&buffer.content, buffer.content.floor_char_boundary(idx));
+        }
+
+        for buffer in self.buffers.iter_mut() {
+            buffer.edits.clear();
+        }
+    }
+
+    // buffer, cursor and scroll each pane currently shows
+    pub fn views(&self) -> Vec<(usize, &Buffer, &Cursor, usize)> {
+        self.layout
+            .panes()
+            .into_iter()
+            .map(|pane| {
+                if pane.id == self.focused {
+                    let buffer = self.active();
+                    (pane.id, buffer, &buffer.cursor, buffer.scroll)
+                } else {
+                    let buffer = &self.buffers[pane.buffer];
+                    (pane.id, buffer, &pane.cursor, pane.scroll)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_panes_follow_edits_to_their_buffer() {
+        let mut workspace = Workspace::new(Buffer::new("a", "one\ntwo".to_string()));
+        workspace.active_mut().set_cursor(5);
+        workspace.split(Direction::Vertical);
+
+        // the new pane types before where the first one's cursor is
+        workspace.active_mut().set_cursor(0);
+        "éé\n"
+            .chars()
+            .for_each(|x| workspace.active_mut().insert(x));
+        workspace.follow_edits();
+
+        let views = workspace.views();
+        let (_, buffer, cursor, _) = views.iter().find(|x| x.0 == 0).unwrap();
+
+        assert_eq!(&buffer.content[cursor.idx..], "wo");
+        assert_eq!((cursor.cursor_y, cursor.cursor_x), (2, 1));
+
+        // undoing there moves it back too, and focusing it keeps it in place
+        workspace.active_mut().undo();
+        workspace.focus(0);
+
+        assert_eq!(
+            &workspace.active().content[workspace.active().cursor.idx..],
+            "wo"
+        );
+    }
+}